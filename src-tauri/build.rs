@@ -43,5 +43,8 @@ fn main() {
         }
     }
 
-    tauri_build::build()
+    // The headless build has no app to generate a context for
+    if env::var_os("CARGO_FEATURE_GUI").is_some() {
+        tauri_build::build()
+    }
 }
@@ -0,0 +1,63 @@
+--- Audit trail of every status a bitcoin lock has moved through
+CREATE TABLE BitcoinLockTransitions (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  utxoId INTEGER NOT NULL,
+  fromStatus TEXT,
+  toStatus TEXT NOT NULL,
+  createdAt DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX BitcoinLockTransitionsUtxoId ON BitcoinLockTransitions (utxoId);
+
+--- Backfill existing locks: every lock started as initialized when it was created, and the last
+--- write is the best record we have of when it reached its current status
+INSERT INTO BitcoinLockTransitions (utxoId, fromStatus, toStatus, createdAt)
+SELECT utxoId, NULL, 'initialized', COALESCE(createdAt, updatedAt, CURRENT_TIMESTAMP) FROM BitcoinLocks;
+
+INSERT INTO BitcoinLockTransitions (utxoId, fromStatus, toStatus, createdAt)
+SELECT utxoId, 'initialized', status, COALESCE(updatedAt, createdAt, CURRENT_TIMESTAMP)
+FROM BitcoinLocks WHERE status != 'initialized';
+
+CREATE TRIGGER BitcoinLocksInsertTransition
+AFTER INSERT ON BitcoinLocks
+BEGIN
+  INSERT INTO BitcoinLockTransitions (utxoId, fromStatus, toStatus) VALUES (NEW.utxoId, NULL, NEW.status);
+END;
+
+CREATE TRIGGER BitcoinLocksUpdateTransition
+AFTER UPDATE OF status ON BitcoinLocks
+WHEN NEW.status != OLD.status
+BEGIN
+  INSERT INTO BitcoinLockTransitions (utxoId, fromStatus, toStatus) VALUES (NEW.utxoId, OLD.status, NEW.status);
+END;
+
+--- Keep in sync with BitcoinLockStatus::can_transition_to in bitcoin_locks.rs
+CREATE TRIGGER BitcoinLocksValidateTransition
+BEFORE UPDATE OF status ON BitcoinLocks
+WHEN NEW.status != OLD.status AND NOT (
+  (OLD.status = 'initialized' AND NEW.status IN ('pendingMint', 'verificationFailed', 'releaseRequested'))
+  OR (OLD.status = 'pendingMint' AND NEW.status IN ('minted', 'releaseRequested'))
+  OR (OLD.status = 'minted' AND NEW.status IN ('pendingMint', 'releaseRequested'))
+  OR (OLD.status = 'verificationFailed' AND NEW.status IN ('releaseRequested'))
+  OR (OLD.status = 'releaseRequested' AND NEW.status IN ('vaultCosigned'))
+  OR (OLD.status = 'vaultCosigned' AND NEW.status IN ('released'))
+)
+BEGIN
+  SELECT RAISE(ABORT, 'Illegal bitcoin lock status transition');
+END;
+
+CREATE TRIGGER BitcoinLocksValidateRequiredFields
+BEFORE UPDATE OF status ON BitcoinLocks
+WHEN NEW.status != OLD.status AND (
+  (NEW.status = 'pendingMint' AND (NEW.txid IS NULL OR NEW.vout IS NULL))
+  OR (NEW.status = 'releaseRequested' AND (
+    NEW.requestedReleaseAtHeight IS NULL
+    OR NEW.releaseToDestinationAddress IS NULL
+    OR NEW.releaseBitcoinNetworkFee IS NULL
+  ))
+  OR (NEW.status = 'vaultCosigned' AND (NEW.releaseCosignSignature IS NULL OR NEW.releaseCosignHeight IS NULL))
+  OR (NEW.status = 'released' AND NEW.releasedAtHeight IS NULL)
+)
+BEGIN
+  SELECT RAISE(ABORT, 'Bitcoin lock is missing fields required for its new status');
+END;
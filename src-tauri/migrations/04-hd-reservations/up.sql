@@ -0,0 +1,24 @@
+--- Every hd index handed out for a vault's bitcoin locks. BitcoinLockVaultHdSeq stays the high-water mark.
+CREATE TABLE BitcoinLockHdReservations (
+  vaultId INTEGER NOT NULL,
+  hdIndex INTEGER NOT NULL,
+  hdPath TEXT NOT NULL,
+  status TEXT NOT NULL CHECK(status IN ('reserved', 'used', 'abandoned')),
+  reservedAt DATETIME DEFAULT CURRENT_TIMESTAMP,
+  updatedAt DATETIME DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (vaultId, hdIndex)
+);
+
+CREATE UNIQUE INDEX BitcoinLockHdReservationsHdPath ON BitcoinLockHdReservations (hdPath);
+
+CREATE TRIGGER BitcoinLockHdReservationsUpdateTimestamp
+AFTER UPDATE ON BitcoinLockHdReservations
+BEGIN
+  UPDATE BitcoinLockHdReservations SET updatedAt = CURRENT_TIMESTAMP WHERE vaultId = NEW.vaultId AND hdIndex = NEW.hdIndex;
+END;
+
+CREATE TRIGGER BitcoinLocksUseHdReservation
+AFTER INSERT ON BitcoinLocks
+BEGIN
+  UPDATE BitcoinLockHdReservations SET status = 'used' WHERE hdPath = NEW.hdPath;
+END;
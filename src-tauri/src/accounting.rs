@@ -0,0 +1,137 @@
+use crate::db::BigIntJson;
+use std::fs;
+use std::path::PathBuf;
+use time::OffsetDateTime;
+
+pub const MICROGONS_PER_ARGON: i64 = 1_000_000;
+pub const MICRONOTS_PER_ARGONOT: i64 = 1_000_000;
+
+/// Formats an integer amount with 6 implied decimals (microgons/micronots) without going through
+/// floating point.
+pub fn format_micro_units(amount: i64) -> String {
+    let sign = if amount < 0 { "-" } else { "" };
+    let abs = amount.unsigned_abs();
+    let unit = MICROGONS_PER_ARGON as u64;
+    format!("{}{}.{:06}", sign, abs / unit, abs % unit)
+}
+
+pub fn serialize_micro_units<S: serde::Serializer>(amount: &i64, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&format_micro_units(*amount))
+}
+
+pub fn average(samples: &BigIntJson) -> Option<f64> {
+    let samples = samples.0.iter().filter(|x| **x > 0).collect::<Vec<_>>();
+    if samples.is_empty() {
+        return None;
+    }
+    Some(samples.iter().map(|x| **x as f64).sum::<f64>() / samples.len() as f64)
+}
+
+pub fn millis_to_date(millis: i64) -> String {
+    OffsetDateTime::from_unix_timestamp_nanos(millis as i128 * 1_000_000)
+        .map(|x| x.date().to_string())
+        .unwrap_or_default()
+}
+
+/// None when there is no price to convert with.
+pub fn microgons_to_usd(microgons: i64, microgons_per_usd: Option<f64>) -> Option<f64> {
+    microgons_per_usd.map(|per_usd| microgons as f64 / per_usd)
+}
+
+/// Values argonots in microgons at the given price. Only None when there are argonots to value
+/// and no price.
+pub fn micronots_to_microgons(micronots: i64, microgons_per_argonot: Option<f64>) -> Option<i64> {
+    if micronots == 0 {
+        return Some(0);
+    }
+    microgons_per_argonot.map(|per_argonot| {
+        (micronots as f64 * per_argonot / MICRONOTS_PER_ARGONOT as f64).round() as i64
+    })
+}
+
+fn csv_escape(value: &serde_json::Value) -> String {
+    let text = match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+/// Writes rows as csv. Columns are the serialized (camelCase) field names, in the given order.
+pub fn write_csv<T: serde::Serialize>(
+    path: PathBuf,
+    columns: &[&str],
+    rows: &[T],
+) -> Result<PathBuf, String> {
+    let mut out = columns.join(",");
+    out.push('\n');
+    for row in rows {
+        let value = serde_json::to_value(row).map_err(|e| e.to_string())?;
+        let line = columns
+            .iter()
+            .map(|c| csv_escape(value.get(c).unwrap_or(&serde_json::Value::Null)))
+            .collect::<Vec<_>>();
+        out.push_str(&line.join(","));
+        out.push('\n');
+    }
+    fs::write(&path, out).map_err(|e| format!("Error writing file {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_micro_units_without_rounding() {
+        assert_eq!(format_micro_units(1_500_000), "1.500000");
+        assert_eq!(format_micro_units(-7), "-0.000007");
+        assert_eq!(format_micro_units(i64::MIN), "-9223372036854.775808");
+    }
+
+    #[test]
+    fn escapes_csv_cells_only_when_needed() {
+        let escape = |x: serde_json::Value| csv_escape(&x);
+        assert_eq!(escape(serde_json::json!("plain")), "plain");
+        assert_eq!(escape(serde_json::json!("a,b")), "\"a,b\"");
+        assert_eq!(
+            escape(serde_json::json!("say \"hi\"")),
+            "\"say \"\"hi\"\"\""
+        );
+        assert_eq!(escape(serde_json::json!("two\nlines")), "\"two\nlines\"");
+        assert_eq!(escape(serde_json::json!(null)), "");
+        assert_eq!(escape(serde_json::json!(1.5)), "1.5");
+        assert_eq!(escape(serde_json::json!(true)), "true");
+    }
+
+    #[test]
+    fn writes_the_given_columns_in_order() {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Row {
+            name: &'static str,
+            usd_value: Option<f64>,
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let rows = [
+            Row {
+                name: "a, b",
+                usd_value: Some(2.5),
+            },
+            Row {
+                name: "c",
+                usd_value: None,
+            },
+        ];
+        let path = write_csv(dir.path().join("rows.csv"), &["usdValue", "name"], &rows).unwrap();
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "usdValue,name\n2.5,\"a, b\"\n,c\n"
+        );
+    }
+}
@@ -0,0 +1,571 @@
+use crate::error::{CommanderError, ErrorCode, ResultExt};
+use crate::utils::Utils;
+use crate::{
+    bitcoin_locks, cost_basis, db, earnings, export, hd_paths, instances, migrations, network_env,
+    security, ssh, ssh_pool, vm, vm_snapshots,
+};
+use log::trace;
+use nosleep::{NoSleep, NoSleepType};
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Listener, Manager};
+use tauri::{Emitter, State};
+use tauri_plugin_log::fern::colors::ColoredLevelConfig;
+use time::OffsetDateTime;
+use tokio::sync::Mutex;
+#[cfg(target_os = "macos")]
+use window_vibrancy::*;
+use zip::DateTime;
+
+struct NoSleepState {
+    nosleep: Mutex<Option<NoSleep>>,
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn open_ssh_connection(
+    address: &str,
+    host: &str,
+    port: u16,
+    username: String,
+    private_key_path: String,
+) -> Result<String, CommanderError> {
+    log::info!("ensure_ssh_connection");
+    ssh_pool::open_connection(address, host, port, username, private_key_path)
+        .await
+        .map_err(|e| {
+            log::error!("Error connecting to SSH: {:#}", e);
+            CommanderError::from(e)
+        })?;
+
+    Ok("success".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn close_ssh_connection(address: &str) -> Result<String, CommanderError> {
+    log::info!("close_ssh_connection");
+    ssh_pool::close_connection(address).await?;
+
+    Ok("success".to_string())
+}
+
+async fn get_ssh_connection(address: &str) -> Result<ssh::SSH, CommanderError> {
+    ssh_pool::get_connection(address)
+        .await?
+        .ok_or_else(|| CommanderError::new(ErrorCode::NoConnection, "No SSH connection"))
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn ssh_run_command(address: &str, command: String) -> Result<(String, u32), CommanderError> {
+    let ssh = get_ssh_connection(address).await?;
+    let response = ssh.run_command(&command).await?;
+    Ok(response)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn ssh_upload_file(
+    address: &str,
+    contents: String,
+    remote_path: String,
+) -> Result<String, CommanderError> {
+    log::info!("ssh_upload_file: {}, {}", contents, remote_path);
+    let ssh = get_ssh_connection(address).await?;
+    ssh.upload_file(contents.as_bytes(), &remote_path)
+        .await
+        .context(format!("Uploading {}", remote_path))?;
+    Ok("success".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn ssh_download_file(
+    app: AppHandle,
+    address: &str,
+    remote_path: String,
+    download_path: String,
+    event_progress_key: String,
+) -> Result<String, CommanderError> {
+    log::info!("ssh_download_file: {}, {}", remote_path, download_path);
+    let ssh = get_ssh_connection(address).await?;
+    ssh.download_remote_file(&app, &remote_path, &download_path, event_progress_key)
+        .await
+        .context(format!("Downloading {}", remote_path))?;
+    Ok("success".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn ssh_upload_embedded_file(
+    app: AppHandle,
+    address: &str,
+    local_relative_path: String,
+    remote_path: String,
+    event_progress_key: String,
+) -> Result<String, CommanderError> {
+    log::info!(
+        "ssh_upload_embedded_file: {}, {}",
+        local_relative_path,
+        remote_path
+    );
+    let ssh = get_ssh_connection(address).await?;
+    ssh.upload_embedded_file(&app, &local_relative_path, &remote_path, event_progress_key)
+        .await
+        .context(format!("Uploading {}", local_relative_path))?;
+    Ok("success".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn read_embedded_file(
+    app: AppHandle,
+    local_relative_path: String,
+) -> Result<String, CommanderError> {
+    log::info!("read_embedded_file: {}", local_relative_path);
+    let absolute_local_path = Utils::get_embedded_path(&app, local_relative_path.clone())
+        .context("Error resolving embedded path")?;
+
+    if !absolute_local_path.exists() {
+        return Err(CommanderError::new(
+            ErrorCode::FileSystemError,
+            format!("File does not exist: {}", local_relative_path),
+        ));
+    }
+
+    let content = fs::read_to_string(&absolute_local_path)
+        .context(format!("Error reading file {}", local_relative_path))?;
+    Ok(content)
+}
+
+/// The env file for the current network when it's a custom one. Built-in networks ship their env
+/// file with the server files, so this returns None for them.
+#[tauri::command]
+#[specta::specta]
+async fn read_custom_network_env() -> Result<Option<String>, CommanderError> {
+    let network_name = Utils::get_network_name();
+    if !Utils::is_custom_network(&network_name) {
+        return Ok(None);
+    }
+    let env_text = Utils::get_network_env_text(&network_name).code(ErrorCode::InvalidInput)?;
+    Ok(Some(env_text))
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn overwrite_security(
+    app: AppHandle,
+    master_mnemonic: String,
+    ssh_public_key: String,
+    ssh_private_key_path: String,
+) -> Result<String, CommanderError> {
+    log::info!("overwrite_security");
+    let new_security = security::Security {
+        master_mnemonic,
+        ssh_public_key,
+        ssh_private_key_path,
+    };
+    new_security.save(&app)?;
+
+    Ok("success".to_string())
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn overwrite_mnemonic(app: AppHandle, mnemonic: String) -> Result<String, CommanderError> {
+    log::info!("overwrite_mnemonic");
+    let mut security = security::Security::load(&app)?;
+    security.master_mnemonic = mnemonic;
+    security.save(&app)?;
+    Ok("success".to_string())
+}
+#[tauri::command]
+#[specta::specta]
+async fn run_db_migrations(app: AppHandle) -> Result<(), CommanderError> {
+    log::info!("run_db_migrations");
+    app.state::<db::DbPool>().close().await;
+    app.state::<earnings::EarningsCache>().clear().await;
+    migrations::migrate_instance_db(&app).await
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn await_db_migrations(
+    db_migrations: State<'_, migrations::DbMigrations>,
+) -> Result<(), CommanderError> {
+    let result = db_migrations.wait().await;
+    // the webview opens its own handle once this returns, even after a failure
+    db_migrations.mark_opened();
+    result
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn db_migration_status(
+    app: AppHandle,
+) -> Result<Vec<migrations::MigrationStatus>, CommanderError> {
+    log::info!("db_migration_status");
+    let absolute_db_path = Utils::get_absolute_config_instance_dir(&app).join("database.sqlite");
+    migrations::get_db_migration_status(absolute_db_path)
+        .await
+        .code(ErrorCode::DatabaseError)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn db_migration_dry_run(
+    app: AppHandle,
+) -> Result<Vec<migrations::MigrationDryRun>, CommanderError> {
+    log::info!("db_migration_dry_run");
+    let absolute_db_path = Utils::get_absolute_config_instance_dir(&app).join("database.sqlite");
+    migrations::dry_run_db_migrations(absolute_db_path)
+        .await
+        .code(ErrorCode::MigrationFailed)
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn toggle_nosleep(
+    nosleep_state: State<'_, NoSleepState>,
+    enable: bool,
+) -> Result<(), CommanderError> {
+    let Some(ref mut nosleep) = *nosleep_state.nosleep.lock().await else {
+        return Err("NoSleep not initialized".into());
+    };
+    if enable {
+        log::info!("KeepAwake enabled");
+        nosleep.start(NoSleepType::PreventUserIdleSystemSleep)
+    } else {
+        log::info!("KeepAwake disabled");
+        nosleep.stop()
+    }
+    .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+async fn create_zip(
+    paths_with_prefixes: Vec<(PathBuf, PathBuf)>,
+    zip_name: PathBuf,
+) -> Result<PathBuf, CommanderError> {
+    let zip_error = |e: zip::result::ZipError| CommanderError::new(ErrorCode::FileSystemError, e);
+    let file = fs::File::create(&zip_name).context(format!("Creating {}", zip_name.display()))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let opts = zip::write::SimpleFileOptions::default();
+
+    for (prefix, p) in paths_with_prefixes {
+        // Walk children and prefix entries with the root directory name
+        for entry in walkdir::WalkDir::new(&p).into_iter().flatten() {
+            if entry.file_type().is_dir() {
+                continue;
+            }
+            let path = entry.path();
+            let rel = if p.is_file() {
+                // If the path is a file, we need to strip the parent directory
+                path.strip_prefix(p.parent().unwrap_or(&PathBuf::from("")))
+            } else {
+                path.strip_prefix(&p)
+            }
+            .unwrap_or(path);
+
+            println!(
+                "Processing entry: {} {}",
+                rel.display(),
+                path.to_string_lossy()
+            );
+
+            // Skip the directory itself; it"s already added
+            if rel.as_os_str().is_empty() {
+                continue;
+            }
+
+            let name = prefix.join(rel).to_string_lossy().replace("\\", "/");
+            let mut file_opts = opts;
+            let metadata = entry
+                .metadata()
+                .map_err(|e| CommanderError::new(ErrorCode::FileSystemError, e))?;
+            if let Ok(mtime) = metadata.modified() {
+                if let Ok(zdt) = DateTime::try_from(OffsetDateTime::from(mtime)) {
+                    file_opts = file_opts.last_modified_time(zdt);
+                }
+            }
+            zip.start_file(name, file_opts).map_err(zip_error)?;
+            let mut f = fs::File::open(path).context(format!("Opening {}", path.display()))?;
+            std::io::copy(&mut f, &mut zip)?;
+        }
+    }
+
+    zip.finish().map_err(zip_error)?;
+    Ok(zip_name)
+}
+
+#[tauri::command]
+#[specta::specta]
+fn calculate_free_space(path: Option<String>) -> Result<u64, CommanderError> {
+    let p = path
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::env::current_dir().unwrap());
+    Ok(Utils::get_free_space(&p)?)
+}
+
+////////////////////////////////////////////////////////////
+
+fn init_logger(network_name: &String, instance_name: &String) -> tauri_plugin_log::Builder {
+    let mut logger = tauri_plugin_log::Builder::new()
+        .clear_targets()
+        .target(tauri_plugin_log::Target::new(
+            tauri_plugin_log::TargetKind::LogDir {
+                file_name: Some(format!("{}-{}", network_name, instance_name)),
+            },
+        ))
+        .target(tauri_plugin_log::Target::new(
+            tauri_plugin_log::TargetKind::Stdout,
+        ))
+        .max_file_size(10_000_000)
+        .with_colors(ColoredLevelConfig::default());
+
+    // load rust log from runtime env, then build, then default
+    let rust_log = std::env::var("RUST_LOG").unwrap_or(
+        std::option_env!("RUST_LOG")
+            .unwrap_or("info, russh=error, hyper=info, hyper_util=info")
+            .to_string(),
+    );
+
+    for part in rust_log.split(',') {
+        if let Some((target, level)) = part.split_once('=') {
+            if let Ok(level) = level.parse::<log::LevelFilter>() {
+                logger = logger.level_for(target.trim().to_owned(), level);
+            }
+        } else if let Ok(level) = part.parse::<log::LevelFilter>() {
+            logger = logger.level(level);
+        }
+    }
+
+    logger
+}
+
+fn init_config_instance_dir(
+    app: &AppHandle,
+    relative_config_dir: &PathBuf,
+) -> Result<(), tauri::Error> {
+    let config_instance_dir = app
+        .path()
+        .resolve(relative_config_dir, tauri::path::BaseDirectory::AppConfig)?;
+    if !config_instance_dir.exists() {
+        trace!(
+            "Creating config directory at: {}",
+            config_instance_dir.to_string_lossy()
+        );
+        std::fs::create_dir_all(&config_instance_dir).expect("Failed to create config directory");
+    }
+    Ok(())
+}
+
+/// Every command and event exposed to the webview. Debug builds also write the matching
+/// TypeScript bindings to `src-vue/lib/bindings.ts`.
+fn specta_builder() -> tauri_specta::Builder<tauri::Wry> {
+    let builder = tauri_specta::Builder::<tauri::Wry>::new()
+        .commands(tauri_specta::collect_commands![
+            open_ssh_connection,
+            close_ssh_connection,
+            ssh_run_command,
+            ssh_upload_file,
+            ssh_download_file,
+            ssh_upload_embedded_file,
+            read_embedded_file,
+            read_custom_network_env,
+            overwrite_security,
+            overwrite_mnemonic,
+            run_db_migrations,
+            await_db_migrations,
+            db_migration_status,
+            db_migration_dry_run,
+            db::get_frames,
+            db::get_frame,
+            db::upsert_frame,
+            db::get_cohorts,
+            db::upsert_cohort,
+            db::get_cohort_frames,
+            db::upsert_cohort_frame,
+            db::get_frame_bids,
+            db::upsert_frame_bid,
+            bitcoin_locks::transition_bitcoin_lock,
+            bitcoin_locks::get_bitcoin_lock_transitions,
+            hd_paths::reserve_bitcoin_lock_hd_path,
+            hd_paths::release_bitcoin_lock_hd_path,
+            hd_paths::scan_bitcoin_lock_hd_paths,
+            earnings::get_frame_earnings,
+            earnings::get_cohort_earnings,
+            earnings::get_rolling_earnings,
+            export::export_history,
+            cost_basis::generate_cost_basis_report,
+            create_zip,
+            toggle_nosleep,
+            calculate_free_space,
+            vm::create_local_vm,
+            vm::activate_local_vm,
+            vm::upgrade_local_vm,
+            vm::remove_local_vm,
+            vm::local_vm_status,
+            vm::set_local_vm_limits,
+            vm::get_local_vm_logs,
+            vm::follow_local_vm_logs,
+            vm::stop_local_vm_logs,
+            vm::export_local_vm_logs,
+            vm::remove_local_vm_logs_export,
+            vm_snapshots::create_local_vm_snapshot,
+            vm_snapshots::list_local_vm_snapshots,
+            vm_snapshots::restore_local_vm_snapshot,
+            vm_snapshots::delete_local_vm_snapshot,
+            vm_snapshots::import_local_vm_snapshot,
+            vm::is_docker_running,
+            vm::get_container_runtime,
+            vm::get_local_vm_backend,
+            vm::check_needed_ports,
+            vm::remap_needed_ports,
+            vm::sync_local_vm_ports,
+            instances::list_networks,
+            instances::list_instances,
+            instances::create_instance,
+            instances::clone_instance,
+            instances::delete_instance,
+            instances::relaunch_into_instance,
+            instances::update_instance_metadata,
+        ])
+        .events(tauri_specta::collect_events![
+            migrations::MigrationProgress,
+            vm::VmProgress,
+            vm::VmLogLine,
+        ])
+        .error_handling(tauri_specta::ErrorHandlingMode::Throw)
+        .typ::<security::Security>()
+        .typ::<vm::Vm>()
+        .typ::<network_env::NetworkEnv>();
+
+    #[cfg(debug_assertions)]
+    builder
+        .export(
+            // ids, ticks and counts stay far below 2^53; amounts are sent as strings (see db::bigint)
+            specta_typescript::Typescript::default()
+                .bigint(specta_typescript::BigIntExportBehavior::Number),
+            concat!(env!("CARGO_MANIFEST_DIR"), "/../src-vue/lib/bindings.ts"),
+        )
+        .expect("Failed to export typescript bindings");
+
+    builder
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    color_backtrace::install();
+
+    let network_name = Utils::get_network_name();
+    let instance_name = Utils::get_instance_name();
+    let enable_auto_update =
+        option_env!("COMMANDER_ENABLE_AUTOUPDATE").map_or(true, |v| v == "true");
+    let is_test = option_env!("CI").map_or(false, |v| v == "true" || v == "1");
+    let logger = init_logger(&network_name, &instance_name);
+
+    let relative_config_dir = Utils::get_relative_config_instance_dir();
+    let db_relative_path = relative_config_dir.join("database.sqlite");
+
+    let network_name_clone = network_name.clone();
+    let instance_name_clone = instance_name.clone();
+    let network_env = Utils::get_server_env();
+    let network_env_json = network_env
+        .as_ref()
+        .ok()
+        .and_then(|env| serde_json::to_string(env).ok())
+        .unwrap_or("{}".to_string());
+    // an invalid env file shouldn't stop the app from opening, so the webview can show what's wrong
+    let network_env_error_json = network_env
+        .as_ref()
+        .err()
+        .and_then(|e| serde_json::to_string(e).ok())
+        .unwrap_or("null".to_string());
+    let specta_builder = specta_builder();
+    let invoke_handler = specta_builder.invoke_handler();
+
+    tauri::Builder::default()
+          .on_page_load(move |window, _payload| {
+            if window.label() != "main" {
+              return;
+            }
+            log::info!("Page loaded for instance '{}'", instance_name_clone);
+            window.emit("tauri://page-loaded", ()).unwrap();
+            window.eval(format!("window.__COMMANDER_INSTANCE__ = '{}'", instance_name_clone)).expect("Failed to set instance name in window");
+            window.eval(format!("window.__ARGON_NETWORK_NAME__ = '{}'", network_name_clone)).expect("Failed to set network name in window");
+            window.eval(format!("window.__COMMANDER_ENABLE_AUTOUPDATE__ = {}", enable_auto_update)).expect("Failed to set experimental flag in window");
+            window.eval(format!("window.__SERVER_ENV_VARS__ = {}", network_env_json)).expect("Failed to set env vars in window");
+            window.eval(format!("window.__SERVER_ENV_ERROR__ = {}", network_env_error_json)).expect("Failed to set env error in window");
+            window.eval(format!("window.__IS_TEST__ = {}", is_test)).expect("Failed to set is test flag in window");
+          })
+        .setup(move |app| {
+            specta_builder.mount_events(app);
+            let handle = app.handle();
+            let config_path = Utils::get_absolute_config_instance_dir(handle);
+            log::info!(
+                "Starting instance '{}' on network '{}'. Config = {:?}",
+                instance_name,
+                network_name,
+                config_path
+            );
+            log::info!("Database URL = {}", db_relative_path.display());
+            if let Err(e) = &network_env {
+                log::error!("Invalid network settings: {}", e);
+            }
+            let security = security::Security::load(handle)?;
+            let security_json = serde_json::to_string(&security).map_err(|e| e.to_string())?;
+
+            let nosleep = NoSleep::new().map_err(|e| e.to_string())?;
+            app.manage(NoSleepState { nosleep: Mutex::new(Some(nosleep)) });
+
+            let window = app.get_webview_window("main").unwrap();
+
+            app.listen("tauri://page-loaded", move |_event| {
+                window.eval(format!("window.__COMMANDER_SECURITY__ = {}", security_json)).expect("Failed to set security in window");
+            });
+            let app_id = &app.config().identifier;
+
+            if app_id.to_lowercase().contains("experimental")  && option_env!("ARGON_EXPERIMENTAL").is_none()  {
+                panic!("Experimental app built without the ARGON_EXPERIMENTAL environment variable set. Please set it to 'true' to enable experimental features.");
+            }
+
+            init_config_instance_dir(handle, &relative_config_dir)?;
+            app.manage(instances::OpenInstance::open(&config_path)?);
+
+            app.manage(migrations::DbMigrations::default());
+            app.manage(db::DbPool::default());
+            app.manage(earnings::EarningsCache::default());
+            migrations::DbMigrations::start(handle);
+
+            #[cfg(target_os = "macos")]{
+                let window = app.get_webview_window("main").unwrap();
+
+                apply_vibrancy(&window, NSVisualEffectMaterial::HudWindow, None, Some(16.0))
+                    .expect("Unsupported platform! 'apply_vibrancy' is only supported on macOS");
+            }
+
+            Ok(())
+        })
+        .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_http::init())
+        .plugin(logger.build())
+        .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_autostart::Builder::new()
+                .app_name("Argon Commander")
+                .build(),
+        )
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_sql::Builder::default().build())
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_os::init())
+        .invoke_handler(invoke_handler)
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,3 @@
+fn main() -> std::process::ExitCode {
+    argon_commander_lib::cli::run()
+}
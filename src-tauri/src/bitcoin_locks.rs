@@ -0,0 +1,456 @@
+#[cfg(feature = "gui")]
+use crate::db::DbPool;
+use crate::error::{CommanderError, ErrorCode, ResultExt};
+use sqlx::SqlitePool;
+#[cfg(feature = "gui")]
+use tauri::AppHandle;
+
+/// Lifecycle of a bitcoin lock. The webview moves locks through [`transition`]; the database
+/// enforces the same rules through triggers (see `migrations/03-lock-lifecycle`) as a backstop for
+/// any other write.
+#[derive(serde::Serialize, serde::Deserialize, specta::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BitcoinLockStatus {
+    Initialized,
+    PendingMint,
+    VerificationFailed,
+    Minted,
+    ReleaseRequested,
+    VaultCosigned,
+    Released,
+}
+
+impl BitcoinLockStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Initialized => "initialized",
+            Self::PendingMint => "pendingMint",
+            Self::VerificationFailed => "verificationFailed",
+            Self::Minted => "minted",
+            Self::ReleaseRequested => "releaseRequested",
+            Self::VaultCosigned => "vaultCosigned",
+            Self::Released => "released",
+        }
+    }
+
+    pub fn can_transition_to(&self, next: BitcoinLockStatus) -> bool {
+        use BitcoinLockStatus::*;
+        if *self == next {
+            return true;
+        }
+        matches!(
+            (self, next),
+            (
+                Initialized,
+                PendingMint | VerificationFailed | ReleaseRequested
+            ) | (PendingMint, Minted | ReleaseRequested)
+                | (Minted, PendingMint | ReleaseRequested)
+                | (VerificationFailed, ReleaseRequested)
+                | (ReleaseRequested, VaultCosigned)
+                | (VaultCosigned, Released)
+        )
+    }
+}
+
+impl TryFrom<String> for BitcoinLockStatus {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        serde_json::from_value(serde_json::Value::String(value.clone()))
+            .map_err(|_| format!("Unknown bitcoin lock status {}", value))
+    }
+}
+
+#[derive(sqlx::FromRow, Debug, Clone)]
+#[sqlx(rename_all = "camelCase")]
+struct LockLifecycleFields {
+    #[sqlx(try_from = "String")]
+    status: BitcoinLockStatus,
+    txid: Option<String>,
+    vout: Option<i64>,
+    requested_release_at_height: Option<i64>,
+    release_bitcoin_network_fee: Option<i64>,
+    release_to_destination_address: Option<String>,
+    release_cosign_signature: Option<Vec<u8>>,
+    release_cosign_height: Option<i64>,
+    released_at_height: Option<i64>,
+    released_txid: Option<String>,
+}
+
+/// A requested status change along with any fields the new status needs. Fields left as `None`
+/// keep their stored value.
+#[derive(serde::Deserialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BitcoinLockTransition {
+    pub utxo_id: i64,
+    pub status: BitcoinLockStatus,
+    pub txid: Option<String>,
+    pub vout: Option<i64>,
+    pub requested_release_at_height: Option<i64>,
+    #[serde(with = "crate::db::bigint::option")]
+    #[specta(type = Option<String>)]
+    pub release_bitcoin_network_fee: Option<i64>,
+    pub release_to_destination_address: Option<String>,
+    pub release_cosign_signature: Option<Vec<u8>>,
+    pub release_cosign_height: Option<i64>,
+    pub released_at_height: Option<i64>,
+    pub released_txid: Option<String>,
+}
+
+#[derive(serde::Serialize, specta::Type, sqlx::FromRow, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(rename_all = "camelCase")]
+pub struct BitcoinLockTransitionRecord {
+    pub id: i64,
+    pub utxo_id: i64,
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub created_at: String,
+}
+
+impl BitcoinLockTransition {
+    fn apply_to(self, current: &mut LockLifecycleFields) {
+        current.status = self.status;
+        current.txid = self.txid.or(current.txid.take());
+        current.vout = self.vout.or(current.vout);
+        current.requested_release_at_height = self
+            .requested_release_at_height
+            .or(current.requested_release_at_height);
+        current.release_bitcoin_network_fee = self
+            .release_bitcoin_network_fee
+            .or(current.release_bitcoin_network_fee);
+        current.release_to_destination_address = self
+            .release_to_destination_address
+            .or(current.release_to_destination_address.take());
+        current.release_cosign_signature = self
+            .release_cosign_signature
+            .or(current.release_cosign_signature.take());
+        current.release_cosign_height =
+            self.release_cosign_height.or(current.release_cosign_height);
+        current.released_at_height = self.released_at_height.or(current.released_at_height);
+        current.released_txid = self.released_txid.or(current.released_txid.take());
+    }
+}
+
+impl LockLifecycleFields {
+    fn missing_fields(&self) -> Vec<&'static str> {
+        let required: Vec<(&'static str, bool)> = match self.status {
+            BitcoinLockStatus::PendingMint => {
+                vec![("txid", self.txid.is_some()), ("vout", self.vout.is_some())]
+            }
+            BitcoinLockStatus::ReleaseRequested => vec![
+                (
+                    "requestedReleaseAtHeight",
+                    self.requested_release_at_height.is_some(),
+                ),
+                (
+                    "releaseBitcoinNetworkFee",
+                    self.release_bitcoin_network_fee.is_some(),
+                ),
+                (
+                    "releaseToDestinationAddress",
+                    self.release_to_destination_address.is_some(),
+                ),
+            ],
+            BitcoinLockStatus::VaultCosigned => vec![
+                (
+                    "releaseCosignSignature",
+                    self.release_cosign_signature.is_some(),
+                ),
+                ("releaseCosignHeight", self.release_cosign_height.is_some()),
+            ],
+            BitcoinLockStatus::Released => {
+                vec![("releasedAtHeight", self.released_at_height.is_some())]
+            }
+            _ => vec![],
+        };
+        required
+            .into_iter()
+            .filter(|(_, present)| !present)
+            .map(|(name, _)| name)
+            .collect()
+    }
+}
+
+pub async fn transition(
+    pool: &SqlitePool,
+    transition: BitcoinLockTransition,
+) -> Result<BitcoinLockStatus, CommanderError> {
+    let utxo_id = transition.utxo_id;
+    let mut tx = pool.begin().await.context("Failed to start transaction")?;
+    let mut lock = sqlx::query_as::<_, LockLifecycleFields>(
+        "SELECT status, txid, vout, requestedReleaseAtHeight, releaseBitcoinNetworkFee,
+            releaseToDestinationAddress, releaseCosignSignature, releaseCosignHeight,
+            releasedAtHeight, releasedTxid
+        FROM BitcoinLocks WHERE utxoId = ?",
+    )
+    .bind(utxo_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .context(format!("Failed to load bitcoin lock {}", utxo_id))?
+    .ok_or_else(|| {
+        CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!("Bitcoin lock {} not found", utxo_id),
+        )
+    })?;
+
+    let from = lock.status;
+    if !from.can_transition_to(transition.status) {
+        return Err(CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Bitcoin lock {} can't move from {} to {}",
+                utxo_id,
+                from.as_str(),
+                transition.status.as_str()
+            ),
+        ));
+    }
+    transition.apply_to(&mut lock);
+    let missing = lock.missing_fields();
+    if !missing.is_empty() {
+        return Err(CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Bitcoin lock {} is missing {} for {}",
+                utxo_id,
+                missing.join(", "),
+                lock.status.as_str()
+            ),
+        ));
+    }
+
+    sqlx::query(
+        "UPDATE BitcoinLocks SET status = ?, txid = ?, vout = ?, requestedReleaseAtHeight = ?,
+            releaseBitcoinNetworkFee = ?, releaseToDestinationAddress = ?,
+            releaseCosignSignature = ?, releaseCosignHeight = ?, releasedAtHeight = ?,
+            releasedTxid = ?
+        WHERE utxoId = ?",
+    )
+    .bind(lock.status.as_str())
+    .bind(&lock.txid)
+    .bind(lock.vout)
+    .bind(lock.requested_release_at_height)
+    .bind(lock.release_bitcoin_network_fee)
+    .bind(&lock.release_to_destination_address)
+    .bind(&lock.release_cosign_signature)
+    .bind(lock.release_cosign_height)
+    .bind(lock.released_at_height)
+    .bind(&lock.released_txid)
+    .bind(utxo_id)
+    .execute(&mut *tx)
+    .await
+    .context(format!("Failed to update bitcoin lock {}", utxo_id))?;
+
+    tx.commit()
+        .await
+        .context(format!("Failed to update bitcoin lock {}", utxo_id))?;
+    log::info!(
+        "Bitcoin lock {} moved from {} to {}",
+        utxo_id,
+        from.as_str(),
+        lock.status.as_str()
+    );
+    Ok(lock.status)
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn transition_bitcoin_lock(
+    app: AppHandle,
+    transition: BitcoinLockTransition,
+) -> Result<BitcoinLockStatus, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    self::transition(&pool, transition).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn get_bitcoin_lock_transitions(
+    app: AppHandle,
+    utxo_id: i64,
+) -> Result<Vec<BitcoinLockTransitionRecord>, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    sqlx::query_as::<_, BitcoinLockTransitionRecord>(
+        "SELECT id, utxoId, fromStatus, toStatus, CAST(createdAt AS TEXT) AS createdAt
+        FROM BitcoinLockTransitions WHERE utxoId = ? ORDER BY id ASC",
+    )
+    .bind(utxo_id)
+    .fetch_all(&pool)
+    .await
+    .context(format!("Failed to load transitions for {}", utxo_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitcoinLockStatus::{self, *};
+    use super::*;
+    use crate::test_utils::{TestDb, block_on, insert_bitcoin_lock};
+
+    const ALL: [BitcoinLockStatus; 7] = [
+        Initialized,
+        PendingMint,
+        VerificationFailed,
+        Minted,
+        ReleaseRequested,
+        VaultCosigned,
+        Released,
+    ];
+
+    fn to(utxo_id: i64, status: BitcoinLockStatus) -> BitcoinLockTransition {
+        BitcoinLockTransition {
+            utxo_id,
+            status,
+            txid: None,
+            vout: None,
+            requested_release_at_height: None,
+            release_bitcoin_network_fee: None,
+            release_to_destination_address: None,
+            release_cosign_signature: None,
+            release_cosign_height: None,
+            released_at_height: None,
+            released_txid: None,
+        }
+    }
+
+    async fn status_of(pool: &SqlitePool, utxo_id: i64) -> String {
+        sqlx::query_scalar("SELECT status FROM BitcoinLocks WHERE utxoId = ?")
+            .bind(utxo_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn allows_only_the_lifecycle_transitions() {
+        let allowed = [
+            (Initialized, PendingMint),
+            (Initialized, VerificationFailed),
+            (Initialized, ReleaseRequested),
+            (PendingMint, Minted),
+            (PendingMint, ReleaseRequested),
+            (Minted, PendingMint),
+            (Minted, ReleaseRequested),
+            (VerificationFailed, ReleaseRequested),
+            (ReleaseRequested, VaultCosigned),
+            (VaultCosigned, Released),
+        ];
+        for from in ALL {
+            for to in ALL {
+                let expected = from == to || allowed.contains(&(from, to));
+                assert_eq!(
+                    from.can_transition_to(to),
+                    expected,
+                    "{:?} -> {:?}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_status_names() {
+        for status in ALL {
+            assert_eq!(
+                BitcoinLockStatus::try_from(status.as_str().to_string()),
+                Ok(status)
+            );
+        }
+        assert!(BitcoinLockStatus::try_from("burned".to_string()).is_err());
+    }
+
+    #[test]
+    fn matches_the_database_triggers() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            let mut utxo_id = 0;
+            for from in ALL {
+                for next in ALL {
+                    utxo_id += 1;
+                    // every lifecycle field is set so only the move itself is checked
+                    sqlx::query(
+                        "INSERT INTO BitcoinLocks (utxoId, status, satoshis, peggedPrice, ratchets,
+                            cosignVersion, lockDetails, network, hdPath, vaultId, txid, vout,
+                            requestedReleaseAtHeight, releaseBitcoinNetworkFee,
+                            releaseToDestinationAddress, releaseCosignSignature,
+                            releaseCosignHeight, releasedAtHeight)
+                        VALUES (?, ?, 1000, 1000, '[]', 'v1', '{}', 'signet', ?, 1, 'tx', 0, 1, 1,
+                            'bc1', x'00', 1, 1)",
+                    )
+                    .bind(utxo_id)
+                    .bind(from.as_str())
+                    .bind(format!("m/{}", utxo_id))
+                    .execute(&db.pool)
+                    .await
+                    .unwrap();
+
+                    let result = sqlx::query("UPDATE BitcoinLocks SET status = ? WHERE utxoId = ?")
+                        .bind(next.as_str())
+                        .bind(utxo_id)
+                        .execute(&db.pool)
+                        .await;
+                    assert_eq!(
+                        result.is_ok(),
+                        from.can_transition_to(next),
+                        "{:?} -> {:?}",
+                        from,
+                        next
+                    );
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn checks_the_fields_a_new_status_needs() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            insert_bitcoin_lock(&db.pool, 1, 1, "m/1").await;
+
+            let error = transition(&db.pool, to(1, PendingMint)).await.unwrap_err();
+            assert_eq!(error.code, ErrorCode::InvalidInput);
+            assert!(error.message.contains("txid, vout"), "{}", error);
+
+            let pending = BitcoinLockTransition {
+                txid: Some("tx".to_string()),
+                vout: Some(1),
+                ..to(1, PendingMint)
+            };
+            assert_eq!(transition(&db.pool, pending).await.unwrap(), PendingMint);
+            // the stored txid still satisfies pendingMint when the lock returns to it
+            transition(&db.pool, to(1, Minted)).await.unwrap();
+            transition(&db.pool, to(1, PendingMint)).await.unwrap();
+
+            let error = transition(&db.pool, to(1, Released)).await.unwrap_err();
+            assert!(error.message.contains("can't move"), "{}", error);
+            assert_eq!(status_of(&db.pool, 1).await, "pendingMint");
+
+            let error = transition(&db.pool, to(2, Minted)).await.unwrap_err();
+            assert!(error.message.contains("not found"), "{}", error);
+
+            let history: Vec<(Option<String>, String)> = sqlx::query_as(
+                "SELECT fromStatus, toStatus FROM BitcoinLockTransitions WHERE utxoId = 1 ORDER BY id",
+            )
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+            let history = history
+                .iter()
+                .map(|(from, to)| (from.as_deref(), to.as_str()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                history,
+                [
+                    (None, "initialized"),
+                    (Some("initialized"), "pendingMint"),
+                    (Some("pendingMint"), "minted"),
+                    (Some("minted"), "pendingMint")
+                ]
+            );
+        });
+    }
+}
@@ -0,0 +1,337 @@
+use crate::error::{CommanderError, ErrorCode, ResultExt};
+use crate::migrations::{self, MigrationProgress, MigrationState};
+use crate::security::Security;
+use crate::ssh_pool;
+use crate::utils::Utils;
+use crate::vm::{self, Vm, VmProgress, VmResourceLimits};
+use crate::vm_snapshots;
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Headless Argon Commander. Uses the same instance directory as the app, resolved from
+/// ARGON_NETWORK_NAME and COMMANDER_INSTANCE.
+#[derive(Parser, Debug)]
+#[command(name = "commander", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Shows the instance, database and local VM state
+    Status,
+    /// Works with the mining machine over ssh
+    Ssh {
+        #[command(subcommand)]
+        command: SshCommand,
+    },
+    /// Manages the local docker VM
+    Vm {
+        #[command(subcommand)]
+        command: VmCommand,
+    },
+    /// Snapshots the instance database into its backup directory
+    Backup,
+    /// Applies pending database migrations
+    Migrate {
+        /// Runs the pending migrations against a throwaway transaction and reports any failures
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SshCommand {
+    /// Runs a command and exits with its status. Targets the local VM unless --host is given.
+    Exec {
+        #[arg(long)]
+        host: Option<String>,
+        #[arg(long, default_value_t = 22)]
+        port: u16,
+        #[arg(long, default_value = "root")]
+        user: String,
+        #[arg(trailing_var_arg = true, required = true)]
+        command: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum VmCommand {
+    /// Starts the local VM, creating it first if needed. Limits only apply when it's created.
+    Up {
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Stops the local VM without removing it
+    Down,
+    /// Changes the local VM's resource limits. Omitted limits are removed.
+    Limits {
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Rebuilds the local VM from this build's files, keeping its synced chain data
+    Upgrade,
+    /// Removes the local VM and its images
+    Remove,
+    /// Archives the local VM's chain data, stopping it while the archive is written
+    Snapshot,
+    /// Lists the local VM snapshots, newest first
+    Snapshots,
+    /// Replaces the local VM's chain data with a snapshot
+    Restore { file_name: String },
+}
+
+#[derive(Args, Debug)]
+struct LimitArgs {
+    /// Cpus the VM may use, ie, 1.5
+    #[arg(long)]
+    cpus: Option<f64>,
+    #[arg(long)]
+    memory_bytes: Option<u64>,
+    /// Free space the VM's chain data must have room to grow into. Sizes the disk of a QEMU VM
+    #[arg(long)]
+    reserved_disk_bytes: Option<u64>,
+}
+
+impl From<LimitArgs> for VmResourceLimits {
+    fn from(args: LimitArgs) -> Self {
+        Self {
+            cpus: args.cpus,
+            memory_bytes: args.memory_bytes,
+            reserved_disk_bytes: args.reserved_disk_bytes,
+        }
+    }
+}
+
+pub fn run() -> ExitCode {
+    let cli = Cli::parse();
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Error starting the async runtime: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match runtime.block_on(run_command(cli.command)) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error [{:?}]: {}", e.code, e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run_command(command: Command) -> Result<ExitCode, CommanderError> {
+    let config_dir = Utils::get_headless_config_instance_dir()?;
+    let db_path = config_dir.join("database.sqlite");
+    match command {
+        Command::Status => {
+            print_status(&config_dir, db_path).await?;
+        }
+        Command::Ssh {
+            command:
+                SshCommand::Exec {
+                    host,
+                    port,
+                    user,
+                    command,
+                },
+        } => {
+            let security = get_security(&config_dir)?;
+            let (host, port) = match host {
+                Some(host) => (host, port),
+                None => ("127.0.0.1".to_string(), get_local_vm(&config_dir)?.ssh_port),
+            };
+            let address = format!("{}:{}", host, port);
+            let ssh = ssh_pool::open_connection(
+                &address,
+                &host,
+                port,
+                user,
+                security.ssh_private_key_path,
+            )
+            .await?;
+            let (output, code) = ssh.run_command(command.join(" ")).await?;
+            print!("{}", output);
+            ssh_pool::close_connection(&address).await?;
+            return Ok(ExitCode::from(u8::try_from(code).unwrap_or(u8::MAX)));
+        }
+        Command::Vm { command } => {
+            let vm_path = vm::get_vm_path_in(&config_dir);
+            let work_dir = vm_path.join("app");
+            match command {
+                VmCommand::Up { limits } => {
+                    let vm = if vm_path.exists() {
+                        Vm::activate(&vm_path, &work_dir, &print_vm_progress)?
+                    } else {
+                        let security = get_security(&config_dir)?;
+                        let env_text = format!(
+                            "COMPOSE_PROJECT_NAME={}\nSSH_PUBKEY=\"{}\"",
+                            vm::get_compose_project_name(),
+                            security.ssh_public_key.trim()
+                        );
+                        Vm::create(
+                            vm_path,
+                            work_dir,
+                            env_text,
+                            limits.into(),
+                            &print_vm_progress,
+                        )?
+                    };
+                    println!("Local VM is up. SSH port: {}", vm.ssh_port);
+                }
+                VmCommand::Upgrade => {
+                    let vm = Vm::upgrade(&vm_path, &print_vm_progress)?;
+                    println!("Local VM upgraded. SSH port: {}", vm.ssh_port);
+                }
+                VmCommand::Limits { limits } => {
+                    Vm::set_limits(&vm_path, &limits.into())?;
+                    println!("Local VM limits updated");
+                }
+                VmCommand::Down => {
+                    Vm::stop(&vm_path)?;
+                    println!("Local VM stopped");
+                }
+                VmCommand::Snapshot => {
+                    let snapshot = vm_snapshots::create_snapshot(&config_dir)?;
+                    println!("Local VM snapshot written to {}", snapshot.path.display());
+                }
+                VmCommand::Snapshots => {
+                    let snapshots = vm_snapshots::list_snapshots(&config_dir);
+                    if snapshots.is_empty() {
+                        println!("No local VM snapshots");
+                    }
+                    for snapshot in snapshots {
+                        println!("{} ({} bytes)", snapshot.file_name, snapshot.size_bytes);
+                    }
+                }
+                VmCommand::Restore { file_name } => {
+                    vm_snapshots::restore_snapshot(&config_dir, &file_name)?;
+                    println!("Local VM restored from {}", file_name);
+                }
+                VmCommand::Remove => {
+                    Vm::destroy(&vm_path)?;
+                    println!("Local VM removed");
+                }
+            }
+        }
+        Command::Backup => {
+            let backup_path = migrations::backup_instance_db(db_path)
+                .await
+                .code(ErrorCode::DatabaseError)?;
+            println!("Database backed up to {}", backup_path.display());
+        }
+        Command::Migrate { dry_run: true } => {
+            let results = migrations::dry_run_db_migrations(db_path)
+                .await
+                .code(ErrorCode::MigrationFailed)?;
+            if results.is_empty() {
+                println!("No pending migrations");
+            }
+            let mut has_failures = false;
+            for result in results {
+                match result.error {
+                    Some(error) => {
+                        has_failures = true;
+                        println!(
+                            "{} {}: FAILED {}",
+                            result.version, result.description, error
+                        );
+                    }
+                    None => println!("{} {}: ok", result.version, result.description),
+                }
+            }
+            if has_failures {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Migrate { dry_run: false } => {
+            migrations::migrate_instance_dir(config_dir, print_progress).await?;
+            println!("Database is up to date");
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The cli never creates keys, since a mnemonic generated here would silently become the
+/// instance's wallet.
+fn get_security(config_dir: &Path) -> Result<Security, CommanderError> {
+    Security::read_from_dir(config_dir)?.ok_or_else(|| {
+        CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Instance not initialized. Open {} in the app first",
+                config_dir.display()
+            ),
+        )
+    })
+}
+
+fn get_local_vm(config_dir: &Path) -> Result<Vm, CommanderError> {
+    Vm::get_vm(&vm::get_vm_path_in(config_dir))
+}
+
+fn print_progress(progress: MigrationProgress) {
+    match (progress.version, progress.description) {
+        (Some(version), Some(description)) => println!(
+            "{:?} {} {} ({}/{})",
+            progress.phase, version, description, progress.completed, progress.total
+        ),
+        _ => println!("{:?}", progress.phase),
+    }
+}
+
+fn print_vm_progress(progress: VmProgress) {
+    match progress.output {
+        Some(output) => println!("  {}", output),
+        None => println!("{:?}", progress.phase),
+    }
+}
+
+async fn print_status(config_dir: &Path, db_path: PathBuf) -> Result<(), CommanderError> {
+    println!("Network:   {}", Utils::get_network_name());
+    println!("Instance:  {}", Utils::get_instance_name());
+    println!("Config:    {}", config_dir.display());
+
+    let has_keys = config_dir.join("serverkey.pub").exists();
+    println!(
+        "Security:  {}",
+        if has_keys {
+            "initialized"
+        } else {
+            "not initialized"
+        }
+    );
+
+    if db_path.exists() {
+        let statuses = migrations::get_db_migration_status(db_path)
+            .await
+            .code(ErrorCode::DatabaseError)?;
+        let applied = statuses
+            .iter()
+            .filter(|x| x.state == MigrationState::Applied)
+            .count();
+        let problems = statuses
+            .iter()
+            .filter(|x| x.state != MigrationState::Applied)
+            .map(|x| format!("{} {:?}", x.version, x.state))
+            .collect::<Vec<_>>();
+        print!("Database:  {} migrations applied", applied);
+        if problems.is_empty() {
+            println!();
+        } else {
+            println!(" ({})", problems.join(", "));
+        }
+    } else {
+        println!("Database:  not created");
+    }
+
+    match get_local_vm(config_dir) {
+        Ok(vm) => println!("Local VM:  running, ssh port {}", vm.ssh_port),
+        Err(e) if e.code == ErrorCode::VmNotFound => println!("Local VM:  not created"),
+        Err(e) => println!("Local VM:  unavailable ({})", e.message),
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use crate::docker_api::DockerApi;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+
+static DETECTED: Mutex<Option<ContainerRuntime>> = Mutex::new(None);
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ContainerRuntimeKind {
+    Docker,
+    Podman,
+    Nerdctl,
+}
+
+impl ContainerRuntimeKind {
+    const ALL: [Self; 3] = [Self::Docker, Self::Podman, Self::Nerdctl];
+
+    pub fn binary(&self) -> &'static str {
+        match self {
+            Self::Docker => "docker",
+            Self::Podman => "podman",
+            Self::Nerdctl => "nerdctl",
+        }
+    }
+}
+
+/// The container engine used for the local VM. All three accept `<binary> compose`; docker and
+/// podman also serve the Docker Engine API on a unix socket, containerd (nerdctl) does not.
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerRuntime {
+    pub kind: ContainerRuntimeKind,
+    pub is_rootless: bool,
+    pub socket_path: Option<PathBuf>,
+}
+
+impl ContainerRuntime {
+    /// The first runtime that responds, in the order docker, podman, nerdctl, unless
+    /// `COMMANDER_CONTAINER_RUNTIME` picks one. The detected runtime is cached, but it's pinged on
+    /// every call so a stopped engine returns None, and detection reruns until one responds again.
+    pub fn get() -> Option<ContainerRuntime> {
+        let mut detected = DETECTED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(runtime) = detected.as_ref() {
+            if runtime.is_alive() {
+                return Some(runtime.clone());
+            }
+            log::info!("Container runtime {:?} is not responding", runtime.kind);
+            *detected = None;
+        }
+        let runtime = Self::detect()?;
+        log::info!("Using container runtime {:?}", runtime);
+        *detected = Some(runtime.clone());
+        Some(runtime)
+    }
+
+    /// Pings the engine's API socket, or runs `<binary> info` when it doesn't serve one.
+    fn is_alive(&self) -> bool {
+        match &self.socket_path {
+            Some(socket_path) => DockerApi::new(socket_path.clone()).ping(),
+            None => Command::new(self.kind.binary())
+                .arg("info")
+                .output()
+                .is_ok_and(|output| output.status.success()),
+        }
+    }
+
+    fn detect() -> Option<Self> {
+        let kinds = match std::env::var("COMMANDER_CONTAINER_RUNTIME") {
+            Ok(name) => ContainerRuntimeKind::ALL
+                .into_iter()
+                .filter(|kind| kind.binary() == name.to_lowercase())
+                .collect(),
+            Err(_) => ContainerRuntimeKind::ALL.to_vec(),
+        };
+        kinds.into_iter().find_map(Self::probe)
+    }
+
+    fn probe(kind: ContainerRuntimeKind) -> Option<Self> {
+        let output = Command::new(kind.binary()).arg("info").output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let info = String::from_utf8_lossy(&output.stdout);
+        let is_rootless = match kind {
+            ContainerRuntimeKind::Podman => info.contains("rootless: true"),
+            // docker lists it under security options, nerdctl as its own line
+            ContainerRuntimeKind::Docker | ContainerRuntimeKind::Nerdctl => {
+                info.contains("rootless")
+            }
+        };
+        Some(Self {
+            kind,
+            is_rootless,
+            socket_path: Self::find_socket(kind, is_rootless),
+        })
+    }
+
+    fn find_socket(kind: ContainerRuntimeKind, is_rootless: bool) -> Option<PathBuf> {
+        let host_var = match kind {
+            ContainerRuntimeKind::Docker => "DOCKER_HOST",
+            ContainerRuntimeKind::Podman => "CONTAINER_HOST",
+            ContainerRuntimeKind::Nerdctl => return None,
+        };
+        if let Ok(host) = std::env::var(host_var) {
+            // tcp and ssh hosts are left to the cli
+            return host.strip_prefix("unix://").map(PathBuf::from);
+        }
+
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok().map(PathBuf::from);
+        let mut candidates = vec![];
+        match kind {
+            ContainerRuntimeKind::Docker => {
+                if is_rootless {
+                    candidates.extend(runtime_dir.map(|dir| dir.join("docker.sock")));
+                }
+                candidates.push(PathBuf::from("/var/run/docker.sock"));
+                if let Some(home) = dirs::home_dir() {
+                    candidates.push(home.join(".docker/run/docker.sock"));
+                }
+            }
+            ContainerRuntimeKind::Podman => {
+                if is_rootless {
+                    candidates.extend(runtime_dir.map(|dir| dir.join("podman/podman.sock")));
+                }
+                candidates.push(PathBuf::from("/run/podman/podman.sock"));
+            }
+            ContainerRuntimeKind::Nerdctl => {}
+        }
+        candidates.into_iter().find(|path| path.exists())
+    }
+
+    /// `<binary> compose` with the remaining args left to the caller.
+    pub fn compose_command(&self) -> Command {
+        let mut command = Command::new(self.kind.binary());
+        command.arg("compose");
+        command
+    }
+
+    /// Rootless engines can't share the host cgroup tree or grant real SYS_ADMIN, so the VM uses
+    /// a compose file without them. Podman's needs its own user namespace options.
+    pub fn vm_compose_file(&self) -> &'static str {
+        match (self.is_rootless, self.kind) {
+            (false, _) => "docker-compose.yml",
+            (true, ContainerRuntimeKind::Podman) => "docker-compose.rootless-podman.yml",
+            (true, ContainerRuntimeKind::Docker | ContainerRuntimeKind::Nerdctl) => {
+                "docker-compose.rootless.yml"
+            }
+        }
+    }
+}
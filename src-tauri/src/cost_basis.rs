@@ -0,0 +1,659 @@
+use crate::accounting::{
+    average, format_micro_units, microgons_to_usd, micronots_to_microgons, millis_to_date,
+    serialize_micro_units, write_csv,
+};
+#[cfg(feature = "gui")]
+use crate::db::DbPool;
+use crate::db::{Cohort, Frame, FrameRange};
+use crate::error::CommanderError;
+#[cfg(feature = "gui")]
+use crate::error::ResultExt;
+use sqlx::SqlitePool;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+#[cfg(feature = "gui")]
+use tauri::AppHandle;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum Asset {
+    Argon,
+    Argonot,
+}
+
+#[derive(serde::Deserialize, specta::Type, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum CostBasisMethod {
+    Fifo,
+    /// Disposals consume the lots listed in `lot_selections` first, then fall back to FIFO
+    SpecificId,
+}
+
+#[derive(serde::Deserialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CostBasisRequest {
+    pub output_dir: PathBuf,
+    pub method: CostBasisMethod,
+    pub start_frame_id: Option<i64>,
+    pub end_frame_id: Option<i64>,
+    pub tick_millis: i64,
+    /// Disposal id (the cohort id) to the lot ids it should be matched against
+    #[serde(default)]
+    pub lot_selections: HashMap<i64, Vec<String>>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Lot {
+    pub lot_id: String,
+    pub asset: Asset,
+    pub frame_id: i64,
+    pub acquired_on: String,
+    #[serde(serialize_with = "serialize_micro_units")]
+    pub amount: i64,
+    #[serde(serialize_with = "serialize_micro_units")]
+    pub remaining: i64,
+    /// USD values are None when a frame they depend on has no price.
+    pub cost_basis_usd: Option<f64>,
+    pub unrealized_gain_usd: Option<f64>,
+}
+
+impl Lot {
+    fn basis_for(&self, amount: i64) -> Option<f64> {
+        if self.amount == 0 {
+            return Some(0.0);
+        }
+        self.cost_basis_usd
+            .map(|basis| basis * amount as f64 / self.amount as f64)
+    }
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LotMatch {
+    pub disposal_id: i64,
+    pub lot_id: String,
+    pub asset: Asset,
+    pub acquired_on: String,
+    pub disposed_on: String,
+    #[serde(serialize_with = "serialize_micro_units")]
+    pub amount: i64,
+    pub proceeds_usd: Option<f64>,
+    pub cost_basis_usd: Option<f64>,
+    pub realized_gain_usd: Option<f64>,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CostBasisSummary {
+    pub asset: String,
+    pub acquired: String,
+    pub disposed: String,
+    pub remaining: String,
+    /// Disposed amounts that no mined lot could cover (ie, argons bought elsewhere)
+    pub unmatched: String,
+    /// Totals are None if any row they add up is missing a price.
+    pub proceeds_usd: Option<f64>,
+    pub realized_cost_basis_usd: Option<f64>,
+    pub realized_gain_usd: Option<f64>,
+    pub unrealized_cost_basis_usd: Option<f64>,
+    pub unrealized_value_usd: Option<f64>,
+    pub unrealized_gain_usd: Option<f64>,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CostBasisReport {
+    pub lots: Vec<Lot>,
+    pub matches: Vec<LotMatch>,
+    pub summary: Vec<CostBasisSummary>,
+}
+
+struct Prices {
+    microgons_per_usd: Option<f64>,
+    microgons_per_argonot: Option<f64>,
+}
+
+impl Prices {
+    fn usd_value(&self, asset: Asset, amount: i64) -> Option<f64> {
+        let microgons = match asset {
+            Asset::Argon => Some(amount),
+            Asset::Argonot => micronots_to_microgons(amount, self.microgons_per_argonot),
+        };
+        microgons.and_then(|x| microgons_to_usd(x, self.microgons_per_usd))
+    }
+}
+
+fn prices_for(frame: &Frame) -> Prices {
+    Prices {
+        microgons_per_usd: average(&frame.microgon_to_usd),
+        microgons_per_argonot: average(&frame.microgon_to_argonot),
+    }
+}
+
+fn gain(value: Option<f64>, cost: Option<f64>) -> Option<f64> {
+    Some(value? - cost?)
+}
+
+/// Builds lots from mined argons/argonots and matches the argons spent on seat bids against them.
+/// `frames` must start from the first frame so earlier lots are matched first; only lots acquired
+/// and disposals made from `report_from_frame_id` on are reported.
+pub fn build_report(
+    frames: &[Frame],
+    cohorts: &[Cohort],
+    method: CostBasisMethod,
+    lot_selections: &HashMap<i64, Vec<String>>,
+    tick_millis: i64,
+    report_from_frame_id: Option<i64>,
+) -> CostBasisReport {
+    let report_from_frame_id = report_from_frame_id.unwrap_or(0);
+    let cohorts_by_id = cohorts.iter().map(|x| (x.id, x)).collect::<HashMap<_, _>>();
+    let mut open: HashMap<Asset, VecDeque<Lot>> = HashMap::new();
+    let mut closed: Vec<Lot> = Vec::new();
+    let mut matches = Vec::new();
+    let mut unmatched: HashMap<Asset, i64> = HashMap::new();
+
+    for frame in frames {
+        let prices = prices_for(frame);
+        let date = millis_to_date(frame.first_tick * tick_millis);
+        let acquisitions = [
+            (
+                Asset::Argon,
+                frame.microgons_mined_total + frame.microgons_minted_total,
+            ),
+            (Asset::Argonot, frame.micronots_mined_total),
+        ];
+        for (asset, amount) in acquisitions {
+            if amount <= 0 {
+                continue;
+            }
+            open.entry(asset).or_default().push_back(Lot {
+                lot_id: format!("{:?}-{}", asset, frame.id).to_lowercase(),
+                asset,
+                frame_id: frame.id,
+                acquired_on: date.clone(),
+                amount,
+                remaining: amount,
+                cost_basis_usd: prices.usd_value(asset, amount),
+                unrealized_gain_usd: None,
+            });
+        }
+
+        // a cohort's bid is paid in the frame it is won
+        let Some(cohort) = cohorts_by_id.get(&frame.id) else {
+            continue;
+        };
+        let mut to_dispose = cohort.microgons_bid_per_seat * cohort.seat_count_won;
+        if to_dispose <= 0 {
+            continue;
+        }
+        let lots = open.entry(Asset::Argon).or_default();
+        let mut order = (0..lots.len()).collect::<Vec<_>>();
+        if method == CostBasisMethod::SpecificId {
+            if let Some(selected) = lot_selections.get(&cohort.id) {
+                order.sort_by_key(|i| {
+                    selected
+                        .iter()
+                        .position(|id| *id == lots[*i].lot_id)
+                        .unwrap_or(usize::MAX)
+                });
+            }
+        }
+        for i in order {
+            if to_dispose == 0 {
+                break;
+            }
+            let lot = &mut lots[i];
+            let amount = lot.remaining.min(to_dispose);
+            if amount == 0 {
+                continue;
+            }
+            let cost_basis_usd = lot.basis_for(amount);
+            let proceeds_usd = prices.usd_value(Asset::Argon, amount);
+            lot.remaining -= amount;
+            to_dispose -= amount;
+            matches.push(LotMatch {
+                disposal_id: cohort.id,
+                lot_id: lot.lot_id.clone(),
+                asset: Asset::Argon,
+                acquired_on: lot.acquired_on.clone(),
+                disposed_on: date.clone(),
+                amount,
+                proceeds_usd,
+                cost_basis_usd,
+                realized_gain_usd: gain(proceeds_usd, cost_basis_usd),
+            });
+        }
+        if cohort.id >= report_from_frame_id {
+            *unmatched.entry(Asset::Argon).or_default() += to_dispose;
+        }
+        let (spent, still_open): (Vec<_>, Vec<_>) = lots.drain(..).partition(|x| x.remaining == 0);
+        closed.extend(spent);
+        lots.extend(still_open);
+    }
+
+    let latest_prices = frames.last().map(prices_for);
+    let mut lots = closed;
+    for (_, remaining) in open {
+        lots.extend(remaining);
+    }
+    lots.retain(|x| x.frame_id >= report_from_frame_id);
+    matches.retain(|x| x.disposal_id >= report_from_frame_id);
+    for lot in lots.iter_mut() {
+        let value = latest_prices
+            .as_ref()
+            .and_then(|p| p.usd_value(lot.asset, lot.remaining));
+        lot.unrealized_gain_usd = gain(value, lot.basis_for(lot.remaining));
+    }
+    lots.sort_by(|a, b| {
+        a.frame_id
+            .cmp(&b.frame_id)
+            .then_with(|| a.lot_id.cmp(&b.lot_id))
+    });
+
+    let summary = [Asset::Argon, Asset::Argonot]
+        .into_iter()
+        .map(|asset| {
+            let asset_lots = lots.iter().filter(|x| x.asset == asset);
+            let asset_matches = matches.iter().filter(|x| x.asset == asset);
+            let acquired = asset_lots.clone().map(|x| x.amount).sum::<i64>();
+            let remaining = asset_lots.clone().map(|x| x.remaining).sum::<i64>();
+            let unrealized_cost_basis_usd = asset_lots
+                .clone()
+                .map(|x| x.basis_for(x.remaining))
+                .sum::<Option<f64>>();
+            let unrealized_gain_usd = asset_lots
+                .map(|x| x.unrealized_gain_usd)
+                .sum::<Option<f64>>();
+            let proceeds_usd = asset_matches
+                .clone()
+                .map(|x| x.proceeds_usd)
+                .sum::<Option<f64>>();
+            let realized_cost_basis_usd = asset_matches
+                .clone()
+                .map(|x| x.cost_basis_usd)
+                .sum::<Option<f64>>();
+            CostBasisSummary {
+                asset: format!("{:?}", asset).to_lowercase(),
+                acquired: format_micro_units(acquired),
+                disposed: format_micro_units(asset_matches.map(|x| x.amount).sum::<i64>()),
+                remaining: format_micro_units(remaining),
+                unmatched: format_micro_units(unmatched.get(&asset).copied().unwrap_or(0)),
+                proceeds_usd,
+                realized_cost_basis_usd,
+                realized_gain_usd: gain(proceeds_usd, realized_cost_basis_usd),
+                unrealized_cost_basis_usd,
+                unrealized_value_usd: unrealized_cost_basis_usd
+                    .zip(unrealized_gain_usd)
+                    .map(|(cost, gain)| cost + gain),
+                unrealized_gain_usd,
+            }
+        })
+        .collect();
+
+    CostBasisReport {
+        lots,
+        matches,
+        summary,
+    }
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_table<T: serde::Serialize>(title: &str, columns: &[&str], rows: &[T]) -> String {
+    let mut out = format!("<h2>{}</h2>\n<table>\n<thead><tr>", html_escape(title));
+    for column in columns {
+        out.push_str(&format!("<th>{}</th>", html_escape(column)));
+    }
+    out.push_str("</tr></thead>\n<tbody>\n");
+    for row in rows {
+        let value = serde_json::to_value(row).unwrap_or_default();
+        out.push_str("<tr>");
+        for column in columns {
+            let cell = match value.get(column) {
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(serde_json::Value::Number(n)) if n.is_f64() => {
+                    format!("{:.2}", n.as_f64().unwrap_or_default())
+                }
+                Some(serde_json::Value::Null) | None => String::new(),
+                Some(other) => other.to_string(),
+            };
+            out.push_str(&format!("<td>{}</td>", html_escape(&cell)));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</tbody>\n</table>\n");
+    out
+}
+
+const SUMMARY_COLUMNS: &[&str] = &[
+    "asset",
+    "acquired",
+    "disposed",
+    "remaining",
+    "unmatched",
+    "proceedsUsd",
+    "realizedCostBasisUsd",
+    "realizedGainUsd",
+    "unrealizedCostBasisUsd",
+    "unrealizedValueUsd",
+    "unrealizedGainUsd",
+];
+const MATCH_COLUMNS: &[&str] = &[
+    "disposalId",
+    "lotId",
+    "asset",
+    "acquiredOn",
+    "disposedOn",
+    "amount",
+    "proceedsUsd",
+    "costBasisUsd",
+    "realizedGainUsd",
+];
+const LOT_COLUMNS: &[&str] = &[
+    "lotId",
+    "asset",
+    "frameId",
+    "acquiredOn",
+    "amount",
+    "remaining",
+    "costBasisUsd",
+    "unrealizedGainUsd",
+];
+
+fn render_html(report: &CostBasisReport, method: CostBasisMethod) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>Argon Commander Cost Basis Report</title>\n<style>\n\
+        body { font-family: sans-serif; font-size: 11px; }\n\
+        table { border-collapse: collapse; width: 100%; margin-bottom: 24px; }\n\
+        th, td { border: 1px solid #ccc; padding: 3px 6px; text-align: right; }\n\
+        th { background: #f2f2f2; }\n\
+        tr { page-break-inside: avoid; }\n\
+        @page { size: landscape; margin: 12mm; }\n\
+        </style>\n</head>\n<body>\n<h1>Cost Basis Report</h1>\n",
+    );
+    out.push_str(&format!("<p>Method: {:?}</p>\n", method));
+    out.push_str(&html_table("Summary", SUMMARY_COLUMNS, &report.summary));
+    out.push_str(&html_table("Realized", MATCH_COLUMNS, &report.matches));
+    out.push_str(&html_table("Lots", LOT_COLUMNS, &report.lots));
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+pub async fn write_report(
+    pool: &SqlitePool,
+    request: CostBasisRequest,
+) -> Result<Vec<PathBuf>, CommanderError> {
+    // lots acquired before the range can still be matched against disposals inside it
+    let range = FrameRange {
+        start_frame_id: None,
+        end_frame_id: request.end_frame_id,
+        limit: None,
+    };
+    let frames = Frame::fetch_range(pool, range).await?;
+    let cohorts = Cohort::fetch_range(pool, range).await?;
+    let report = build_report(
+        &frames,
+        &cohorts,
+        request.method,
+        &request.lot_selections,
+        request.tick_millis.max(1),
+        request.start_frame_id,
+    );
+
+    let dir = &request.output_dir;
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Error creating directory {}: {}", dir.display(), e))?;
+    let html_path = dir.join("cost-basis-report.html");
+    fs::write(&html_path, render_html(&report, request.method))
+        .map_err(|e| format!("Error writing file {}: {}", html_path.display(), e))?;
+
+    Ok(vec![
+        write_csv(
+            dir.join("cost-basis-summary.csv"),
+            SUMMARY_COLUMNS,
+            &report.summary,
+        )?,
+        write_csv(
+            dir.join("cost-basis-realized.csv"),
+            MATCH_COLUMNS,
+            &report.matches,
+        )?,
+        write_csv(dir.join("cost-basis-lots.csv"), LOT_COLUMNS, &report.lots)?,
+        html_path,
+    ])
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn generate_cost_basis_report(
+    app: AppHandle,
+    request: CostBasisRequest,
+) -> Result<Vec<PathBuf>, CommanderError> {
+    log::info!("generate_cost_basis_report: {:?}", request);
+    let pool = DbPool::get(&app).await?;
+    write_report(&pool, request)
+        .await
+        .context("Failed to generate cost basis report")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounting::MICROGONS_PER_ARGON;
+    use crate::db::BigIntJson;
+    use crate::test_utils::{self, TestDb, block_on};
+
+    const TICK_MILLIS: i64 = 60_000;
+
+    /// A frame that mined `argons` while an argon was worth `usd_per_argon`.
+    fn frame(id: i64, argons: i64, usd_per_argon: f64) -> Frame {
+        Frame {
+            microgon_to_usd: BigIntJson(vec![(MICROGONS_PER_ARGON as f64 / usd_per_argon) as i64]),
+            microgons_mined_total: argons * MICROGONS_PER_ARGON,
+            ..test_utils::frame(id)
+        }
+    }
+
+    fn cohort(id: i64, argons_bid: i64) -> Cohort {
+        Cohort {
+            id,
+            progress: 100.0,
+            seat_count_won: 2,
+            transaction_fees_total: 0,
+            micronots_staked_per_seat: 0,
+            microgons_bid_per_seat: argons_bid * MICROGONS_PER_ARGON / 2,
+            microgons_to_be_mined_per_seat: 0,
+            micronots_to_be_mined_per_seat: 0,
+        }
+    }
+
+    /// 10 argons mined at $1, 5 at $2, then 12 spent on a bid at $4.
+    fn build(
+        method: CostBasisMethod,
+        lot_selections: &HashMap<i64, Vec<String>>,
+    ) -> CostBasisReport {
+        let frames = [frame(1, 10, 1.0), frame(2, 5, 2.0), frame(3, 0, 4.0)];
+        build_report(
+            &frames,
+            &[cohort(3, 12)],
+            method,
+            lot_selections,
+            TICK_MILLIS,
+            None,
+        )
+    }
+
+    fn assert_usd(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn matched(report: &CostBasisReport) -> Vec<(&str, i64, Option<f64>, Option<f64>)> {
+        report
+            .matches
+            .iter()
+            .map(|x| {
+                (
+                    x.lot_id.as_str(),
+                    x.amount / MICROGONS_PER_ARGON,
+                    x.cost_basis_usd,
+                    x.proceeds_usd,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_oldest_lots_first_with_fifo() {
+        let report = build(CostBasisMethod::Fifo, &HashMap::new());
+        assert_eq!(
+            matched(&report),
+            [
+                ("argon-1", 10, Some(10.0), Some(40.0)),
+                ("argon-2", 2, Some(4.0), Some(8.0))
+            ]
+        );
+        let remaining = report
+            .lots
+            .iter()
+            .map(|x| (x.lot_id.as_str(), x.remaining / MICROGONS_PER_ARGON))
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, [("argon-1", 0), ("argon-2", 3)]);
+        // the 3 argons left cost $6 and are worth $12 at the latest price
+        assert_usd(report.lots[1].unrealized_gain_usd, 6.0);
+
+        let argon = &report.summary[0];
+        assert_eq!(argon.acquired, "15.000000");
+        assert_eq!(argon.disposed, "12.000000");
+        assert_eq!(argon.remaining, "3.000000");
+        assert_eq!(argon.unmatched, "0.000000");
+        assert_usd(argon.realized_gain_usd, 34.0);
+        assert_usd(argon.unrealized_value_usd, 12.0);
+    }
+
+    #[test]
+    fn matches_selected_lots_first_then_falls_back_to_fifo() {
+        let selections = HashMap::from([(3, vec!["argon-2".to_string()])]);
+        let report = build(CostBasisMethod::SpecificId, &selections);
+        assert_eq!(
+            matched(&report),
+            [
+                ("argon-2", 5, Some(10.0), Some(20.0)),
+                ("argon-1", 7, Some(7.0), Some(28.0))
+            ]
+        );
+
+        // selections are ignored under fifo
+        let report = build(CostBasisMethod::Fifo, &selections);
+        assert_eq!(matched(&report)[0].0, "argon-1");
+    }
+
+    #[test]
+    fn reports_disposals_no_lot_covers_as_unmatched() {
+        let frames = [frame(1, 10, 1.0), frame(2, 0, 1.0)];
+        let report = build_report(
+            &frames,
+            &[cohort(2, 25)],
+            CostBasisMethod::Fifo,
+            &HashMap::new(),
+            TICK_MILLIS,
+            None,
+        );
+        assert_eq!(matched(&report), [("argon-1", 10, Some(10.0), Some(10.0))]);
+        assert_eq!(report.summary[0].unmatched, "15.000000");
+        assert_eq!(report.matches[0].acquired_on, "1970-01-02");
+        assert_eq!(report.matches[0].disposed_on, "1970-01-03");
+    }
+
+    #[test]
+    fn leaves_usd_missing_without_a_price() {
+        let unpriced = Frame {
+            microgon_to_usd: BigIntJson(vec![]),
+            ..frame(2, 5, 1.0)
+        };
+        let argonots = Frame {
+            micronots_mined_total: MICROGONS_PER_ARGON,
+            ..frame(3, 0, 1.0)
+        };
+        let frames = [frame(1, 10, 1.0), unpriced, argonots];
+        let report = build_report(
+            &frames,
+            &[cohort(2, 4)],
+            CostBasisMethod::Fifo,
+            &HashMap::new(),
+            TICK_MILLIS,
+            None,
+        );
+        assert_eq!(matched(&report), [("argon-1", 4, Some(4.0), None)]);
+        let basis = report
+            .lots
+            .iter()
+            .map(|x| (x.lot_id.as_str(), x.cost_basis_usd))
+            .collect::<Vec<_>>();
+        // argonots aren't valued at par when there's no argonot price
+        assert_eq!(
+            basis,
+            [
+                ("argon-1", Some(10.0)),
+                ("argon-2", None),
+                ("argonot-3", None)
+            ]
+        );
+        assert_usd(report.lots[0].unrealized_gain_usd, 0.0);
+
+        let argon = &report.summary[0];
+        assert_eq!(argon.proceeds_usd, None);
+        assert_eq!(argon.realized_gain_usd, None);
+        assert_eq!(argon.unrealized_cost_basis_usd, None);
+        assert_usd(argon.realized_cost_basis_usd, 4.0);
+    }
+
+    #[test]
+    fn matches_disposals_in_range_against_earlier_lots() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            for frame in [frame(1, 10, 1.0), frame(2, 5, 2.0), frame(3, 0, 4.0)] {
+                frame.upsert(&db.pool).await.unwrap();
+            }
+            cohort(3, 12).upsert(&db.pool).await.unwrap();
+            let out = db.dir.path().join("report");
+            let request = CostBasisRequest {
+                output_dir: out.clone(),
+                method: CostBasisMethod::Fifo,
+                start_frame_id: Some(2),
+                end_frame_id: None,
+                tick_millis: TICK_MILLIS,
+                lot_selections: HashMap::new(),
+            };
+            let files = write_report(&db.pool, request).await.unwrap();
+            assert_eq!(files.len(), 4);
+
+            let realized = fs::read_to_string(out.join("cost-basis-realized.csv")).unwrap();
+            let lots = realized
+                .lines()
+                .skip(1)
+                .map(|x| x.split(',').nth(1).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(lots, ["argon-1", "argon-2"]);
+
+            let lots = fs::read_to_string(out.join("cost-basis-lots.csv")).unwrap();
+            assert_eq!(lots.lines().count(), 2);
+            assert!(lots.lines().nth(1).unwrap().starts_with("argon-2,"));
+        });
+    }
+}
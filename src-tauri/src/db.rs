@@ -0,0 +1,215 @@
+#[cfg(feature = "gui")]
+use crate::error::{CommanderError, ResultExt};
+#[cfg(feature = "gui")]
+use crate::migrations::DbMigrations;
+#[cfg(feature = "gui")]
+use crate::utils::Utils;
+#[cfg(feature = "gui")]
+use sqlx::SqlitePool;
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, Manager};
+#[cfg(feature = "gui")]
+use tokio::sync::Mutex;
+
+pub mod bigint;
+mod cohort_frames;
+mod cohorts;
+mod frame_bids;
+mod frames;
+
+pub use cohort_frames::*;
+pub use cohorts::*;
+pub use frame_bids::*;
+pub use frames::*;
+
+/// Shared sqlx pool for the instance database. It is only opened once the migration run for this
+/// process has succeeded, so every query sees the final schema.
+#[cfg(feature = "gui")]
+#[derive(Default)]
+pub struct DbPool {
+    pool: Mutex<Option<SqlitePool>>,
+}
+
+#[cfg(feature = "gui")]
+impl DbPool {
+    pub async fn get(app: &AppHandle) -> Result<SqlitePool, CommanderError> {
+        let state = app.state::<DbPool>();
+        let mut pool = state.pool.lock().await;
+        if let Some(pool) = pool.as_ref() {
+            return Ok(pool.clone());
+        }
+
+        app.state::<DbMigrations>().wait().await?;
+        let absolute_db_path = Utils::get_absolute_config_instance_dir(app).join("database.sqlite");
+        let opts = sqlx::sqlite::SqliteConnectOptions::new().filename(&absolute_db_path);
+        let new_pool = SqlitePool::connect_with(opts)
+            .await
+            .context("Failed to connect to database")?;
+        *pool = Some(new_pool.clone());
+        Ok(new_pool)
+    }
+
+    /// Closes the pool so the next query reconnects, ie, after the database file is recreated.
+    pub async fn close(&self) {
+        if let Some(pool) = self.pool.lock().await.take() {
+            pool.close().await;
+        }
+    }
+}
+
+/// Price arrays are stored by the frontend as JSON with bigints suffixed by `n`, ie, `["123n"]`.
+#[derive(serde::Serialize, serde::Deserialize, specta::Type, Debug, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct BigIntJson(
+    #[serde(with = "bigint::vec")]
+    #[specta(type = Vec<String>)]
+    pub Vec<i64>,
+);
+
+impl TryFrom<String> for BigIntJson {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let raw: Vec<serde_json::Value> = serde_json::from_str(&value)
+            .map_err(|e| format!("Invalid bigint json {}: {}", value, e))?;
+        let mut out = Vec::with_capacity(raw.len());
+        for entry in raw {
+            let parsed = match &entry {
+                serde_json::Value::String(s) => s.trim_end_matches('n').parse::<i64>().ok(),
+                serde_json::Value::Number(n) => n.as_i64(),
+                _ => None,
+            };
+            out.push(parsed.ok_or_else(|| format!("Invalid bigint {} in {}", entry, value))?);
+        }
+        Ok(BigIntJson(out))
+    }
+}
+
+/// Decoded from the TEXT column so `query_as!` can map it with a `"column: BigIntJson"` override.
+impl sqlx::Type<sqlx::Sqlite> for BigIntJson {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <String as sqlx::Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Sqlite> for BigIntJson {
+    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let text = <String as sqlx::Decode<sqlx::Sqlite>>::decode(value)?;
+        Ok(BigIntJson::try_from(text)?)
+    }
+}
+
+impl std::fmt::Display for BigIntJson {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entries = self
+            .0
+            .iter()
+            .map(|x| format!("\"{}n\"", x))
+            .collect::<Vec<_>>();
+        write!(f, "[{}]", entries.join(","))
+    }
+}
+
+#[derive(serde::Deserialize, specta::Type, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameRange {
+    pub start_frame_id: Option<i64>,
+    pub end_frame_id: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl FrameRange {
+    fn start(&self) -> i64 {
+        self.start_frame_id.unwrap_or(0)
+    }
+
+    fn end(&self) -> i64 {
+        self.end_frame_id.unwrap_or(i64::MAX)
+    }
+
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(-1)
+    }
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn get_frames(app: AppHandle, range: FrameRange) -> Result<Vec<Frame>, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    Frame::fetch_range(&pool, range).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn get_frame(app: AppHandle, frame_id: i64) -> Result<Option<Frame>, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    Frame::fetch_by_id(&pool, frame_id).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn upsert_frame(app: AppHandle, frame: Frame) -> Result<(), CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    frame.upsert(&pool).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn get_cohorts(app: AppHandle, range: FrameRange) -> Result<Vec<Cohort>, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    Cohort::fetch_range(&pool, range).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn upsert_cohort(app: AppHandle, cohort: Cohort) -> Result<(), CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    cohort.upsert(&pool).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn get_cohort_frames(
+    app: AppHandle,
+    range: FrameRange,
+) -> Result<Vec<CohortFrame>, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    CohortFrame::fetch_range(&pool, range).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn upsert_cohort_frame(
+    app: AppHandle,
+    cohort_frame: CohortFrame,
+) -> Result<(), CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    cohort_frame.upsert(&pool).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn get_frame_bids(
+    app: AppHandle,
+    frame_id: i64,
+    limit: Option<i64>,
+) -> Result<Vec<FrameBid>, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    FrameBid::fetch_for_frame(&pool, frame_id, limit.unwrap_or(100)).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn upsert_frame_bid(app: AppHandle, frame_bid: FrameBid) -> Result<(), CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    frame_bid.upsert(&pool).await
+}
@@ -0,0 +1,102 @@
+//! Microgon/micronot amounts cross the IPC boundary as decimal strings so values above 2^53 keep
+//! their precision. The webview reads them with `BigInt(x)`. Use with
+//! `#[serde(with = "crate::db::bigint")]` and `#[specta(type = String)]`.
+
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// Plain numbers are still accepted so older callers keep working.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrNumber {
+    String(String),
+    Number(i64),
+}
+
+impl StringOrNumber {
+    fn parse<E: serde::de::Error>(self) -> Result<i64, E> {
+        match self {
+            Self::String(s) => s
+                .trim_end_matches('n')
+                .parse()
+                .map_err(|_| E::custom(format!("Invalid bigint {}", s))),
+            Self::Number(n) => Ok(n),
+        }
+    }
+}
+
+pub fn serialize<S: Serializer>(amount: &i64, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(amount)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<i64, D::Error> {
+    StringOrNumber::deserialize(d)?.parse()
+}
+
+pub mod option {
+    use super::StringOrNumber;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(amount: &Option<i64>, s: S) -> Result<S::Ok, S::Error> {
+        match amount {
+            Some(amount) => s.collect_str(amount),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<i64>, D::Error> {
+        Option::<StringOrNumber>::deserialize(d)?
+            .map(StringOrNumber::parse)
+            .transpose()
+    }
+}
+
+pub mod vec {
+    use super::StringOrNumber;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(amounts: &[i64], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(amounts.iter().map(|x| x.to_string()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<i64>, D::Error> {
+        Vec::<StringOrNumber>::deserialize(d)?
+            .into_iter()
+            .map(StringOrNumber::parse)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Amounts {
+        #[serde(with = "super")]
+        amount: i64,
+        #[serde(with = "super::option")]
+        fee: Option<i64>,
+    }
+
+    #[test]
+    fn round_trips_amounts_above_2_pow_53_as_strings() {
+        let amounts = Amounts {
+            amount: i64::MAX,
+            fee: None,
+        };
+        let json = serde_json::to_string(&amounts).unwrap();
+        assert_eq!(json, r#"{"amount":"9223372036854775807","fee":null}"#);
+        assert_eq!(serde_json::from_str::<Amounts>(&json).unwrap(), amounts);
+    }
+
+    #[test]
+    fn accepts_numbers_and_suffixed_strings() {
+        let amounts: Amounts = serde_json::from_str(r#"{"amount":5,"fee":"7n"}"#).unwrap();
+        assert_eq!(
+            amounts,
+            Amounts {
+                amount: 5,
+                fee: Some(7)
+            }
+        );
+        assert!(serde_json::from_str::<Amounts>(r#"{"amount":"5.5","fee":null}"#).is_err());
+    }
+}
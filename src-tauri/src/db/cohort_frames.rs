@@ -0,0 +1,102 @@
+#[cfg(feature = "gui")]
+use super::FrameRange;
+use crate::error::{CommanderError, ResultExt};
+use sqlx::SqlitePool;
+
+#[derive(serde::Serialize, serde::Deserialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CohortFrame {
+    pub frame_id: i64,
+    pub cohort_id: i64,
+    pub blocks_mined_total: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgon_fees_collected_total: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub micronots_mined_total: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgons_mined_total: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgons_minted_total: i64,
+}
+
+impl CohortFrame {
+    /// Fetches the cohort earnings for every frame in the range (by `frameId`)
+    #[cfg(feature = "gui")]
+    pub async fn fetch_range(
+        pool: &SqlitePool,
+        range: FrameRange,
+    ) -> Result<Vec<CohortFrame>, CommanderError> {
+        let (start, end, limit) = (range.start(), range.end(), range.limit());
+        sqlx::query_as!(
+            CohortFrame,
+            "SELECT frameId AS frame_id, cohortId AS cohort_id,
+                blocksMinedTotal AS blocks_mined_total,
+                microgonFeesCollectedTotal AS microgon_fees_collected_total,
+                micronotsMinedTotal AS micronots_mined_total,
+                microgonsMinedTotal AS microgons_mined_total,
+                microgonsMintedTotal AS microgons_minted_total
+            FROM CohortFrames WHERE frameId >= ? AND frameId <= ?
+            ORDER BY frameId ASC, cohortId ASC LIMIT ?",
+            start,
+            end,
+            limit
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch cohort frames")
+    }
+
+    /// Fetches every frame of a single cohort's earnings
+    pub async fn fetch_for_cohort(
+        pool: &SqlitePool,
+        cohort_id: i64,
+    ) -> Result<Vec<CohortFrame>, CommanderError> {
+        sqlx::query_as!(
+            CohortFrame,
+            "SELECT frameId AS frame_id, cohortId AS cohort_id,
+                blocksMinedTotal AS blocks_mined_total,
+                microgonFeesCollectedTotal AS microgon_fees_collected_total,
+                micronotsMinedTotal AS micronots_mined_total,
+                microgonsMinedTotal AS microgons_mined_total,
+                microgonsMintedTotal AS microgons_minted_total
+            FROM CohortFrames WHERE cohortId = ? ORDER BY frameId ASC",
+            cohort_id
+        )
+        .fetch_all(pool)
+        .await
+        .context(format!("Failed to fetch frames of cohort {}", cohort_id))
+    }
+
+    pub async fn upsert(&self, pool: &SqlitePool) -> Result<(), CommanderError> {
+        sqlx::query!(
+            "INSERT INTO CohortFrames (
+                frameId, cohortId, blocksMinedTotal, microgonFeesCollectedTotal,
+                micronotsMinedTotal, microgonsMinedTotal, microgonsMintedTotal
+            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(frameId, cohortId) DO UPDATE SET
+                blocksMinedTotal = excluded.blocksMinedTotal,
+                microgonFeesCollectedTotal = excluded.microgonFeesCollectedTotal,
+                micronotsMinedTotal = excluded.micronotsMinedTotal,
+                microgonsMinedTotal = excluded.microgonsMinedTotal,
+                microgonsMintedTotal = excluded.microgonsMintedTotal",
+            self.frame_id,
+            self.cohort_id,
+            self.blocks_mined_total,
+            self.microgon_fees_collected_total,
+            self.micronots_mined_total,
+            self.microgons_mined_total,
+            self.microgons_minted_total
+        )
+        .execute(pool)
+        .await
+        .context(format!(
+            "Failed to save cohort frame {}/{}",
+            self.cohort_id, self.frame_id
+        ))?;
+        Ok(())
+    }
+}
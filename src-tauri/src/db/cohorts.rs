@@ -0,0 +1,81 @@
+use super::FrameRange;
+use crate::error::{CommanderError, ResultExt};
+use sqlx::SqlitePool;
+
+/// A cohort is keyed by the frame id it activated in.
+#[derive(serde::Serialize, serde::Deserialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Cohort {
+    pub id: i64,
+    pub progress: f64,
+    pub seat_count_won: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub transaction_fees_total: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub micronots_staked_per_seat: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgons_bid_per_seat: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgons_to_be_mined_per_seat: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub micronots_to_be_mined_per_seat: i64,
+}
+
+impl Cohort {
+    pub async fn fetch_range(
+        pool: &SqlitePool,
+        range: FrameRange,
+    ) -> Result<Vec<Cohort>, CommanderError> {
+        let (start, end, limit) = (range.start(), range.end(), range.limit());
+        sqlx::query_as!(
+            Cohort,
+            "SELECT id, progress, seatCountWon AS seat_count_won,
+                transactionFeesTotal AS transaction_fees_total,
+                micronotsStakedPerSeat AS micronots_staked_per_seat,
+                microgonsBidPerSeat AS microgons_bid_per_seat,
+                microgonsToBeMinedPerSeat AS microgons_to_be_mined_per_seat,
+                micronotsToBeMinedPerSeat AS micronots_to_be_mined_per_seat
+            FROM Cohorts WHERE id >= ? AND id <= ? ORDER BY id ASC LIMIT ?",
+            start,
+            end,
+            limit
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch cohorts")
+    }
+
+    pub async fn upsert(&self, pool: &SqlitePool) -> Result<(), CommanderError> {
+        sqlx::query!(
+            "INSERT INTO Cohorts (
+                id, progress, seatCountWon, transactionFeesTotal, micronotsStakedPerSeat,
+                microgonsBidPerSeat, microgonsToBeMinedPerSeat, micronotsToBeMinedPerSeat
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                progress = excluded.progress,
+                seatCountWon = excluded.seatCountWon,
+                transactionFeesTotal = excluded.transactionFeesTotal,
+                micronotsStakedPerSeat = excluded.micronotsStakedPerSeat,
+                microgonsBidPerSeat = excluded.microgonsBidPerSeat,
+                microgonsToBeMinedPerSeat = excluded.microgonsToBeMinedPerSeat,
+                micronotsToBeMinedPerSeat = excluded.micronotsToBeMinedPerSeat",
+            self.id,
+            self.progress,
+            self.seat_count_won,
+            self.transaction_fees_total,
+            self.micronots_staked_per_seat,
+            self.microgons_bid_per_seat,
+            self.microgons_to_be_mined_per_seat,
+            self.micronots_to_be_mined_per_seat
+        )
+        .execute(pool)
+        .await
+        .context(format!("Failed to save cohort {}", self.id))?;
+        Ok(())
+    }
+}
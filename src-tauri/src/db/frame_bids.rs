@@ -0,0 +1,93 @@
+use super::FrameRange;
+use crate::error::{CommanderError, ResultExt};
+use sqlx::SqlitePool;
+
+#[derive(serde::Serialize, serde::Deserialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameBid {
+    pub frame_id: i64,
+    pub confirmed_at_block_number: i64,
+    pub address: String,
+    pub sub_account_index: Option<i64>,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgons_per_seat: i64,
+    pub bid_position: i64,
+    pub last_bid_at_tick: Option<i64>,
+}
+
+impl FrameBid {
+    #[cfg(feature = "gui")]
+    pub async fn fetch_for_frame(
+        pool: &SqlitePool,
+        frame_id: i64,
+        limit: i64,
+    ) -> Result<Vec<FrameBid>, CommanderError> {
+        sqlx::query_as!(
+            FrameBid,
+            "SELECT frameId AS frame_id, confirmedAtBlockNumber AS confirmed_at_block_number,
+                address, subAccountIndex AS sub_account_index,
+                microgonsPerSeat AS microgons_per_seat, bidPosition AS bid_position,
+                lastBidAtTick AS last_bid_at_tick
+            FROM FrameBids WHERE frameId = ? ORDER BY microgonsPerSeat DESC LIMIT ?",
+            frame_id,
+            limit
+        )
+        .fetch_all(pool)
+        .await
+        .context(format!("Failed to fetch bids for frame {}", frame_id))
+    }
+
+    /// Every bid of the frames in the range, highest bid first within each frame. The range's
+    /// limit is ignored.
+    pub async fn fetch_range(
+        pool: &SqlitePool,
+        range: FrameRange,
+    ) -> Result<Vec<FrameBid>, CommanderError> {
+        let (start, end) = (range.start(), range.end());
+        sqlx::query_as!(
+            FrameBid,
+            "SELECT frameId AS frame_id, confirmedAtBlockNumber AS confirmed_at_block_number,
+                address, subAccountIndex AS sub_account_index,
+                microgonsPerSeat AS microgons_per_seat, bidPosition AS bid_position,
+                lastBidAtTick AS last_bid_at_tick
+            FROM FrameBids WHERE frameId >= ? AND frameId <= ?
+            ORDER BY frameId ASC, microgonsPerSeat DESC",
+            start,
+            end
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch frame bids")
+    }
+
+    #[cfg(feature = "gui")]
+    pub async fn upsert(&self, pool: &SqlitePool) -> Result<(), CommanderError> {
+        sqlx::query!(
+            "INSERT INTO FrameBids (
+                frameId, confirmedAtBlockNumber, address, subAccountIndex, microgonsPerSeat,
+                bidPosition, lastBidAtTick
+            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(frameId, address) DO UPDATE SET
+                confirmedAtBlockNumber = excluded.confirmedAtBlockNumber,
+                subAccountIndex = excluded.subAccountIndex,
+                microgonsPerSeat = excluded.microgonsPerSeat,
+                bidPosition = excluded.bidPosition,
+                lastBidAtTick = excluded.lastBidAtTick",
+            self.frame_id,
+            self.confirmed_at_block_number,
+            self.address,
+            self.sub_account_index,
+            self.microgons_per_seat,
+            self.bid_position,
+            self.last_bid_at_tick
+        )
+        .execute(pool)
+        .await
+        .context(format!(
+            "Failed to save bid for frame {} ({})",
+            self.frame_id, self.address
+        ))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+use super::{BigIntJson, FrameRange};
+use crate::error::{CommanderError, ResultExt};
+use sqlx::SqlitePool;
+
+#[derive(serde::Serialize, serde::Deserialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Frame {
+    pub id: i64,
+    pub first_tick: i64,
+    pub last_tick: i64,
+    pub first_block_number: i64,
+    pub last_block_number: i64,
+    pub microgon_to_usd: BigIntJson,
+    pub microgon_to_btc: BigIntJson,
+    pub microgon_to_argonot: BigIntJson,
+    pub all_miners_count: i64,
+    pub seat_count_active: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub seat_cost_total_framed: i64,
+    pub blocks_mined_total: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgon_fees_collected_total: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub micronots_mined_total: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgons_mined_total: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgons_minted_total: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub accrued_microgon_profits: i64,
+    pub progress: f64,
+    pub is_processed: bool,
+}
+
+impl Frame {
+    /// The single query every frame read goes through. `updated_since` is a sqlite `updatedAt`
+    /// timestamp; timestamps only have second precision, so frames from `since` itself come back
+    /// again.
+    async fn fetch(
+        pool: &SqlitePool,
+        range: FrameRange,
+        updated_since: Option<&str>,
+    ) -> Result<Vec<Frame>, CommanderError> {
+        let (start, end, limit) = (range.start(), range.end(), range.limit());
+        let frames = sqlx::query_as!(
+            Frame,
+            r#"SELECT id, firstTick AS first_tick, lastTick AS last_tick,
+                firstBlockNumber AS first_block_number, lastBlockNumber AS last_block_number,
+                microgonToUsd AS "microgon_to_usd: BigIntJson",
+                microgonToBtc AS "microgon_to_btc: BigIntJson",
+                microgonToArgonot AS "microgon_to_argonot: BigIntJson",
+                allMinersCount AS all_miners_count, seatCountActive AS seat_count_active,
+                seatCostTotalFramed AS seat_cost_total_framed,
+                blocksMinedTotal AS blocks_mined_total,
+                microgonFeesCollectedTotal AS microgon_fees_collected_total,
+                micronotsMinedTotal AS micronots_mined_total,
+                microgonsMinedTotal AS microgons_mined_total,
+                microgonsMintedTotal AS microgons_minted_total,
+                accruedMicrogonProfits AS accrued_microgon_profits, progress,
+                isProcessed AS "is_processed: bool"
+            FROM Frames
+            WHERE id >= ?1 AND id <= ?2 AND (?3 IS NULL OR updatedAt >= ?3)
+            ORDER BY id ASC LIMIT ?4"#,
+            start,
+            end,
+            updated_since,
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(frames)
+    }
+
+    pub async fn fetch_range(
+        pool: &SqlitePool,
+        range: FrameRange,
+    ) -> Result<Vec<Frame>, CommanderError> {
+        Self::fetch(pool, range, None)
+            .await
+            .context("Failed to fetch frames")
+    }
+
+    pub async fn fetch_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Frame>, CommanderError> {
+        let range = FrameRange {
+            start_frame_id: Some(id),
+            end_frame_id: Some(id),
+            limit: Some(1),
+        };
+        let frames = Self::fetch(pool, range, None)
+            .await
+            .context(format!("Failed to fetch frame {}", id))?;
+        Ok(frames.into_iter().next())
+    }
+
+    /// Frames written at or after `since`, a sqlite `updatedAt` timestamp. Timestamps only have
+    /// second precision, so callers should expect to see the frames from `since` itself again.
+    pub async fn fetch_updated_since(
+        pool: &SqlitePool,
+        since: &str,
+    ) -> Result<Vec<Frame>, CommanderError> {
+        Self::fetch(pool, FrameRange::default(), Some(since))
+            .await
+            .context("Failed to fetch updated frames")
+    }
+
+    /// The latest `updatedAt` of any frame, or None when there are no frames.
+    pub async fn fetch_last_updated_at(
+        pool: &SqlitePool,
+    ) -> Result<Option<String>, CommanderError> {
+        sqlx::query_scalar!(r#"SELECT MAX(updatedAt) AS "updated_at: String" FROM Frames"#)
+            .fetch_one(pool)
+            .await
+            .context("Failed to fetch frame update time")
+    }
+
+    pub async fn upsert(&self, pool: &SqlitePool) -> Result<(), CommanderError> {
+        let microgon_to_usd = self.microgon_to_usd.to_string();
+        let microgon_to_btc = self.microgon_to_btc.to_string();
+        let microgon_to_argonot = self.microgon_to_argonot.to_string();
+        sqlx::query!(
+            "INSERT INTO Frames (
+                id, firstTick, lastTick, firstBlockNumber, lastBlockNumber, microgonToUsd,
+                microgonToBtc, microgonToArgonot, allMinersCount, seatCountActive,
+                seatCostTotalFramed, blocksMinedTotal, microgonFeesCollectedTotal,
+                micronotsMinedTotal, microgonsMinedTotal, microgonsMintedTotal,
+                accruedMicrogonProfits, progress, isProcessed
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                firstTick = excluded.firstTick,
+                lastTick = excluded.lastTick,
+                firstBlockNumber = excluded.firstBlockNumber,
+                lastBlockNumber = excluded.lastBlockNumber,
+                microgonToUsd = excluded.microgonToUsd,
+                microgonToBtc = excluded.microgonToBtc,
+                microgonToArgonot = excluded.microgonToArgonot,
+                allMinersCount = excluded.allMinersCount,
+                seatCountActive = excluded.seatCountActive,
+                seatCostTotalFramed = excluded.seatCostTotalFramed,
+                blocksMinedTotal = excluded.blocksMinedTotal,
+                microgonFeesCollectedTotal = excluded.microgonFeesCollectedTotal,
+                micronotsMinedTotal = excluded.micronotsMinedTotal,
+                microgonsMinedTotal = excluded.microgonsMinedTotal,
+                microgonsMintedTotal = excluded.microgonsMintedTotal,
+                accruedMicrogonProfits = excluded.accruedMicrogonProfits,
+                progress = excluded.progress,
+                isProcessed = excluded.isProcessed",
+            self.id,
+            self.first_tick,
+            self.last_tick,
+            self.first_block_number,
+            self.last_block_number,
+            microgon_to_usd,
+            microgon_to_btc,
+            microgon_to_argonot,
+            self.all_miners_count,
+            self.seat_count_active,
+            self.seat_cost_total_framed,
+            self.blocks_mined_total,
+            self.microgon_fees_collected_total,
+            self.micronots_mined_total,
+            self.microgons_mined_total,
+            self.microgons_minted_total,
+            self.accrued_microgon_profits,
+            self.progress,
+            self.is_processed
+        )
+        .execute(pool)
+        .await
+        .context(format!("Failed to save frame {}", self.id))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{self, TestDb, block_on};
+
+    fn frame(id: i64) -> Frame {
+        Frame {
+            microgon_to_usd: BigIntJson(vec![id, 2]),
+            is_processed: id % 2 == 0,
+            ..test_utils::frame(id)
+        }
+    }
+
+    fn ids(frames: &[Frame]) -> Vec<i64> {
+        frames.iter().map(|x| x.id).collect()
+    }
+
+    #[test]
+    fn fetches_frames_by_range_id_and_update_time() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            for id in 1..=4 {
+                frame(id).upsert(&db.pool).await.unwrap();
+            }
+
+            let range = FrameRange {
+                start_frame_id: Some(2),
+                end_frame_id: None,
+                limit: Some(2),
+            };
+            assert_eq!(
+                ids(&Frame::fetch_range(&db.pool, range).await.unwrap()),
+                [2, 3]
+            );
+
+            let fetched = Frame::fetch_by_id(&db.pool, 4).await.unwrap().unwrap();
+            assert_eq!(fetched.microgon_to_usd, BigIntJson(vec![4, 2]));
+            assert!(fetched.is_processed);
+            assert!(Frame::fetch_by_id(&db.pool, 5).await.unwrap().is_none());
+
+            let since = Frame::fetch_last_updated_at(&db.pool)
+                .await
+                .unwrap()
+                .unwrap();
+            let updated = Frame::fetch_updated_since(&db.pool, &since).await.unwrap();
+            assert_eq!(ids(&updated), [1, 2, 3, 4]);
+            let updated = Frame::fetch_updated_since(&db.pool, "9999-01-01 00:00:00").await;
+            assert!(updated.unwrap().is_empty());
+        });
+    }
+}
@@ -0,0 +1,501 @@
+use crate::container_runtime::ContainerRuntime;
+use crate::error::{CommanderError, ErrorCode};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// A minimal Docker Engine API client over the daemon's unix socket, which podman serves too. Only
+/// covers the read-only calls the local VM needs; compose itself still goes through the cli.
+pub struct DockerApi {
+    socket_path: PathBuf,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerPort {
+    pub private_port: u16,
+    pub public_port: Option<u16>,
+    #[serde(rename = "Type")]
+    pub protocol: String,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerSummary {
+    pub id: String,
+    /// Prefixed with a slash, ie, "/testnet-default-vm-1"
+    #[cfg(any(feature = "gui", test))]
+    #[serde(default)]
+    pub names: Vec<String>,
+    /// Human readable, ie, "Up 2 minutes (healthy)"
+    pub status: String,
+    #[serde(default)]
+    pub ports: Vec<ContainerPort>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerHealth {
+    /// starting, healthy or unhealthy
+    pub status: String,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerState {
+    pub status: String,
+    pub running: bool,
+    pub exit_code: i64,
+    #[serde(default)]
+    pub error: String,
+    /// RFC 3339
+    #[cfg(feature = "gui")]
+    #[serde(default)]
+    pub started_at: String,
+    pub health: Option<ContainerHealth>,
+}
+
+#[cfg(feature = "gui")]
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerConfig {
+    /// The image name the container was created from
+    pub image: String,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerInspect {
+    pub state: ContainerState,
+    /// The image id
+    #[cfg(feature = "gui")]
+    pub image: String,
+    #[cfg(feature = "gui")]
+    pub config: ContainerConfig,
+}
+
+#[cfg(feature = "gui")]
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+struct CpuUsage {
+    total_usage: u64,
+}
+
+#[cfg(feature = "gui")]
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+struct CpuStats {
+    cpu_usage: CpuUsage,
+    system_cpu_usage: Option<u64>,
+    online_cpus: Option<u64>,
+}
+
+#[cfg(feature = "gui")]
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+struct MemoryStats {
+    usage: Option<u64>,
+    limit: Option<u64>,
+}
+
+#[cfg(feature = "gui")]
+#[derive(serde::Deserialize, Debug, Clone)]
+struct RawStats {
+    #[serde(default)]
+    cpu_stats: CpuStats,
+    #[serde(default)]
+    precpu_stats: CpuStats,
+    #[serde(default)]
+    memory_stats: MemoryStats,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone)]
+pub struct ContainerStats {
+    /// Percent of a single cpu, so a container saturating two cores reports 200
+    pub cpu_percent: f64,
+    pub memory_usage_bytes: u64,
+    pub memory_limit_bytes: u64,
+}
+
+/// The engine's view of the machine. On Docker Desktop this is the desktop VM, not the host.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct EngineInfo {
+    #[serde(rename = "NCPU")]
+    pub cpus: u32,
+    #[serde(rename = "MemTotal")]
+    pub memory_bytes: u64,
+}
+
+#[derive(serde::Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+impl ContainerSummary {
+    pub fn service_name(&self) -> Option<&str> {
+        self.labels
+            .get("com.docker.compose.service")
+            .map(String::as_str)
+    }
+
+    #[cfg(any(feature = "gui", test))]
+    pub fn project_name(&self) -> Option<&str> {
+        self.labels
+            .get("com.docker.compose.project")
+            .map(String::as_str)
+    }
+
+    #[cfg(any(feature = "gui", test))]
+    pub fn name(&self) -> &str {
+        self.names
+            .first()
+            .map(|name| name.trim_start_matches('/'))
+            .unwrap_or(&self.id)
+    }
+
+    pub fn public_port(&self, private_port: u16) -> Option<u16> {
+        self.ports
+            .iter()
+            .find(|p| p.private_port == private_port && p.protocol == "tcp")
+            .and_then(|p| p.public_port)
+    }
+}
+
+impl DockerApi {
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self { socket_path }
+    }
+
+    /// The API socket of the detected container runtime. Returns None when it doesn't serve one,
+    /// ie, containerd, or Docker Desktop on Windows.
+    pub fn detect() -> Option<Self> {
+        ContainerRuntime::get()?.socket_path.map(Self::new)
+    }
+
+    pub fn ping(&self) -> bool {
+        matches!(self.request("GET", "/_ping"), Ok((200, _)))
+    }
+
+    pub fn get_info(&self) -> Result<EngineInfo, CommanderError> {
+        self.get_json("/info")
+    }
+
+    #[cfg(any(feature = "gui", test))]
+    pub fn list_running_containers(&self) -> Result<Vec<ContainerSummary>, CommanderError> {
+        self.get_json("/containers/json")
+    }
+
+    /// Containers belonging to a compose project, including stopped ones.
+    pub fn list_project_containers(
+        &self,
+        project_name: &str,
+    ) -> Result<Vec<ContainerSummary>, CommanderError> {
+        let filters = serde_json::json!({
+            "label": [format!("com.docker.compose.project={}", project_name)]
+        });
+        let path = format!(
+            "/containers/json?all=true&filters={}",
+            percent_encode(&filters.to_string())
+        );
+        self.get_json(&path)
+    }
+
+    pub fn get_project_service(
+        &self,
+        project_name: &str,
+        service_name: &str,
+    ) -> Result<Option<ContainerSummary>, CommanderError> {
+        Ok(self
+            .list_project_containers(project_name)?
+            .into_iter()
+            .find(|c| c.service_name() == Some(service_name)))
+    }
+
+    pub fn get_container_state(&self, id: &str) -> Result<ContainerState, CommanderError> {
+        Ok(self.inspect_container(id)?.state)
+    }
+
+    pub fn inspect_container(&self, id: &str) -> Result<ContainerInspect, CommanderError> {
+        self.get_json(&format!("/containers/{}/json", id))
+    }
+
+    /// A single stats sample. The daemon takes two readings a second apart so cpu usage can be
+    /// computed, so this blocks for about a second.
+    #[cfg(feature = "gui")]
+    pub fn get_container_stats(&self, id: &str) -> Result<ContainerStats, CommanderError> {
+        let stats: RawStats = self.get_json(&format!("/containers/{}/stats?stream=false", id))?;
+        let cpu_delta = stats
+            .cpu_stats
+            .cpu_usage
+            .total_usage
+            .saturating_sub(stats.precpu_stats.cpu_usage.total_usage);
+        let system_delta = stats
+            .cpu_stats
+            .system_cpu_usage
+            .unwrap_or_default()
+            .saturating_sub(stats.precpu_stats.system_cpu_usage.unwrap_or_default());
+        let cpu_percent = if system_delta > 0 {
+            let cpus = stats.cpu_stats.online_cpus.unwrap_or(1) as f64;
+            (cpu_delta as f64 / system_delta as f64) * cpus * 100.0
+        } else {
+            0.0
+        };
+        Ok(ContainerStats {
+            cpu_percent,
+            memory_usage_bytes: stats.memory_stats.usage.unwrap_or_default(),
+            memory_limit_bytes: stats.memory_stats.limit.unwrap_or_default(),
+        })
+    }
+
+    fn get_json<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, CommanderError> {
+        let (status, body) = self.request("GET", path)?;
+        if !(200..300).contains(&status) {
+            let message = serde_json::from_slice::<ApiError>(&body)
+                .map(|e| e.message)
+                .unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string());
+            return Err(CommanderError::new(
+                ErrorCode::DockerCommandFailed,
+                format!("Docker API {} returned {}: {}", path, status, message),
+            ));
+        }
+        serde_json::from_slice(&body).map_err(|e| {
+            CommanderError::new(
+                ErrorCode::DockerCommandFailed,
+                format!("Unexpected Docker API response for {}: {}", path, e),
+            )
+        })
+    }
+
+    #[cfg(unix)]
+    fn request(&self, method: &str, path: &str) -> Result<(u16, Vec<u8>), CommanderError> {
+        use std::io::{Read, Write};
+        use std::os::unix::net::UnixStream;
+        use std::time::Duration;
+
+        let not_running = |e: std::io::Error| {
+            CommanderError::new(
+                ErrorCode::DockerNotRunning,
+                format!(
+                    "Unable to reach the Docker daemon at {}: {}",
+                    self.socket_path.display(),
+                    e
+                ),
+            )
+        };
+        let mut stream = UnixStream::connect(&self.socket_path).map_err(not_running)?;
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .map_err(not_running)?;
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n\r\n",
+            method, path
+        )
+        .map_err(not_running)?;
+        let mut response = vec![];
+        stream.read_to_end(&mut response).map_err(not_running)?;
+        parse_http_response(&response)
+    }
+
+    #[cfg(not(unix))]
+    fn request(&self, _method: &str, _path: &str) -> Result<(u16, Vec<u8>), CommanderError> {
+        Err(CommanderError::new(
+            ErrorCode::DockerNotRunning,
+            format!(
+                "Docker socket {} is not supported on this platform",
+                self.socket_path.display()
+            ),
+        ))
+    }
+}
+
+fn parse_http_response(response: &[u8]) -> Result<(u16, Vec<u8>), CommanderError> {
+    let invalid = |reason: &str| {
+        CommanderError::new(
+            ErrorCode::DockerCommandFailed,
+            format!("Invalid Docker API response: {}", reason),
+        )
+    };
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| invalid("no header terminator"))?;
+    let head = String::from_utf8_lossy(&response[..header_end]);
+    let body = &response[header_end + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| invalid("no status code"))?;
+    let is_chunked = lines.any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+    if !is_chunked {
+        return Ok((status, body.to_vec()));
+    }
+
+    let mut decoded = vec![];
+    let mut rest = body;
+    loop {
+        let line_end = rest
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(|| invalid("truncated chunk"))?;
+        let size_text = String::from_utf8_lossy(&rest[..line_end]);
+        let size = usize::from_str_radix(size_text.split(';').next().unwrap_or("").trim(), 16)
+            .map_err(|_| invalid("bad chunk size"))?;
+        rest = &rest[line_end + 2..];
+        if size == 0 {
+            break;
+        }
+        if rest.len() < size {
+            return Err(invalid("truncated chunk"));
+        }
+        decoded.extend_from_slice(&rest[..size]);
+        rest = rest.get(size + 2..).unwrap_or_default();
+    }
+    Ok((status, decoded))
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+
+    const CONTAINERS_JSON: &str = r#"[{"Id":"abc123","Names":["/testnet-default-vm-1"],"State":"running","Status":"Up 2 minutes (healthy)","Ports":[{"PrivatePort":22,"PublicPort":32768,"Type":"tcp"}],"Labels":{"com.docker.compose.project":"testnet-default","com.docker.compose.service":"vm"}}]"#;
+
+    /// Serves canned responses on a unix socket in `dir`, one connection per request like the
+    /// daemon does with `Connection: close`.
+    fn serve(dir: &tempfile::TempDir) -> DockerApi {
+        let socket_path = dir.path().join("docker.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    return;
+                };
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+                let response = match path {
+                    "/_ping" => "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nOK".to_string(),
+                    "/containers/json" => format!(
+                        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                        CONTAINERS_JSON.len(),
+                        CONTAINERS_JSON
+                    ),
+                    "/info" => "HTTP/1.1 200 OK\r\n\r\nnot json".to_string(),
+                    _ => {
+                        "HTTP/1.1 404 Not Found\r\n\r\n{\"message\":\"No such container: missing\"}"
+                            .to_string()
+                    }
+                };
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        DockerApi::new(socket_path)
+    }
+
+    #[test]
+    fn pings_and_lists_containers_over_the_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = serve(&dir);
+        assert!(api.ping());
+
+        let containers = api.list_running_containers().unwrap();
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[0].name(), "testnet-default-vm-1");
+        assert_eq!(containers[0].service_name(), Some("vm"));
+        assert_eq!(containers[0].project_name(), Some("testnet-default"));
+        assert_eq!(containers[0].public_port(22), Some(32768));
+        assert_eq!(containers[0].public_port(80), None);
+    }
+
+    #[test]
+    fn maps_api_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = serve(&dir);
+
+        let error = api.inspect_container("missing").unwrap_err();
+        assert_eq!(error.code, ErrorCode::DockerCommandFailed);
+        assert!(error.message.contains("404"));
+        assert!(error.message.contains("No such container: missing"));
+
+        let error = api.get_info().unwrap_err();
+        assert_eq!(error.code, ErrorCode::DockerCommandFailed);
+        assert!(
+            error
+                .message
+                .starts_with("Unexpected Docker API response for /info")
+        );
+    }
+
+    #[test]
+    fn reports_an_unreachable_socket_as_not_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = DockerApi::new(dir.path().join("missing.sock"));
+        assert!(!api.ping());
+        let error = api.get_info().unwrap_err();
+        assert_eq!(error.code, ErrorCode::DockerNotRunning);
+    }
+
+    #[test]
+    fn parses_plain_and_chunked_responses() {
+        let (status, body) =
+            parse_http_response(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n{\"a\"").unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(body, b"{\"a\"");
+
+        let chunked = b"HTTP/1.1 201 Created\r\nTRANSFER-ENCODING: chunked\r\n\r\n\
+            3;ext=1\r\nabc\r\nA\r\n0123456789\r\n0\r\n\r\n";
+        let (status, body) = parse_http_response(chunked).unwrap();
+        assert_eq!(status, 201);
+        assert_eq!(body, b"abc0123456789");
+    }
+
+    #[test]
+    fn rejects_malformed_responses() {
+        for response in [
+            &b"HTTP/1.1 200 OK\r\n"[..],
+            b"garbage\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n10\r\nshort",
+        ] {
+            let error = parse_http_response(response).unwrap_err();
+            assert_eq!(error.code, ErrorCode::DockerCommandFailed);
+            assert!(error.message.starts_with("Invalid Docker API response"));
+        }
+    }
+
+    #[test]
+    fn percent_encodes_everything_but_unreserved_characters() {
+        assert_eq!(percent_encode("aZ09-_.~"), "aZ09-_.~");
+        assert_eq!(
+            percent_encode(r#"{"label":["a=b c"]}"#),
+            "%7B%22label%22%3A%5B%22a%3Db%20c%22%5D%7D"
+        );
+        assert_eq!(percent_encode("é"), "%C3%A9");
+    }
+}
@@ -0,0 +1,518 @@
+use crate::accounting::{average, microgons_to_usd, micronots_to_microgons};
+#[cfg(feature = "gui")]
+use crate::db::DbPool;
+use crate::db::{Cohort, CohortFrame, Frame, FrameRange};
+use crate::error::CommanderError;
+use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+
+const FRAMES_PER_YEAR: f64 = 365.0;
+
+/// Usd values are None when a frame they draw on has no price samples, and argonots are only
+/// valued in argons when the frame has an argonot price, rather than assuming par.
+#[derive(serde::Serialize, specta::Type, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EarningsTotals {
+    pub frame_count: u32,
+    pub blocks_mined: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgons_mined: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgons_minted: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub micronots_mined: i64,
+    #[serde(with = "crate::db::bigint::option")]
+    #[specta(type = Option<String>)]
+    pub micronots_mined_as_microgons: Option<i64>,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub microgon_fees_collected: i64,
+    #[serde(with = "crate::db::bigint")]
+    #[specta(type = String)]
+    pub seat_cost_microgons: i64,
+    #[serde(with = "crate::db::bigint::option")]
+    #[specta(type = Option<String>)]
+    pub earnings_microgons: Option<i64>,
+    #[serde(with = "crate::db::bigint::option")]
+    #[specta(type = Option<String>)]
+    pub profit_microgons: Option<i64>,
+    pub earnings_usd: Option<f64>,
+    pub seat_cost_usd: Option<f64>,
+    pub profit_usd: Option<f64>,
+    pub roi_pct: Option<f64>,
+    pub apy_pct: Option<f64>,
+}
+
+impl Default for EarningsTotals {
+    fn default() -> Self {
+        Self {
+            frame_count: 0,
+            blocks_mined: 0,
+            microgons_mined: 0,
+            microgons_minted: 0,
+            micronots_mined: 0,
+            micronots_mined_as_microgons: Some(0),
+            microgon_fees_collected: 0,
+            seat_cost_microgons: 0,
+            earnings_microgons: Some(0),
+            profit_microgons: Some(0),
+            earnings_usd: Some(0.0),
+            seat_cost_usd: Some(0.0),
+            profit_usd: Some(0.0),
+            roi_pct: Some(0.0),
+            apy_pct: Some(0.0),
+        }
+    }
+}
+
+fn add<T: std::ops::Add<Output = T>>(a: Option<T>, b: Option<T>) -> Option<T> {
+    Some(a? + b?)
+}
+
+impl EarningsTotals {
+    fn add(&mut self, other: &EarningsTotals) {
+        self.frame_count += other.frame_count;
+        self.blocks_mined += other.blocks_mined;
+        self.microgons_mined += other.microgons_mined;
+        self.microgons_minted += other.microgons_minted;
+        self.micronots_mined += other.micronots_mined;
+        self.micronots_mined_as_microgons = add(
+            self.micronots_mined_as_microgons,
+            other.micronots_mined_as_microgons,
+        );
+        self.microgon_fees_collected += other.microgon_fees_collected;
+        self.seat_cost_microgons += other.seat_cost_microgons;
+        self.earnings_usd = add(self.earnings_usd, other.earnings_usd);
+        self.seat_cost_usd = add(self.seat_cost_usd, other.seat_cost_usd);
+        self.finalize();
+    }
+
+    /// Recomputes the derived fields. ROI is compounded over a year of daily frames for the APY,
+    /// matching the BiddingCalculator.
+    fn finalize(&mut self) {
+        self.earnings_microgons = self.micronots_mined_as_microgons.map(|argonots| {
+            self.microgons_mined + self.microgons_minted + argonots + self.microgon_fees_collected
+        });
+        self.profit_microgons = self
+            .earnings_microgons
+            .map(|earnings| earnings - self.seat_cost_microgons);
+        self.profit_usd = add(self.earnings_usd, self.seat_cost_usd.map(|x| -x));
+        let seat_cost = self.seat_cost_microgons;
+        self.roi_pct = self.profit_microgons.map(|profit| {
+            if seat_cost > 0 {
+                profit as f64 / seat_cost as f64 * 100.0
+            } else {
+                0.0
+            }
+        });
+        let frame_count = self.frame_count;
+        self.apy_pct = self.roi_pct.map(|roi_pct| {
+            if frame_count > 0 && seat_cost > 0 {
+                let periods = FRAMES_PER_YEAR / frame_count as f64;
+                ((1.0 + roi_pct / 100.0).max(0.0).powf(periods) * 100.0 - 100.0).max(-100.0)
+            } else {
+                0.0
+            }
+        });
+    }
+
+    /// Totals for one frame's (or one cohort's share of a frame's) earnings at the frame's prices.
+    fn for_frame(
+        mut self,
+        microgons_per_usd: Option<f64>,
+        microgons_per_argonot: Option<f64>,
+    ) -> Self {
+        self.frame_count = 1;
+        self.micronots_mined_as_microgons =
+            micronots_to_microgons(self.micronots_mined, microgons_per_argonot);
+        self.finalize();
+        self.earnings_usd = self
+            .earnings_microgons
+            .and_then(|x| microgons_to_usd(x, microgons_per_usd));
+        self.seat_cost_usd = microgons_to_usd(self.seat_cost_microgons, microgons_per_usd);
+        self.finalize();
+        self
+    }
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameEarnings {
+    pub frame_id: i64,
+    pub first_tick: i64,
+    pub is_processed: bool,
+    /// Average of the frame's price samples, None until one is recorded
+    pub microgons_per_usd: Option<f64>,
+    pub microgons_per_argonot: Option<f64>,
+    pub totals: EarningsTotals,
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CohortEarnings {
+    pub cohort_id: i64,
+    pub seat_count_won: i64,
+    pub progress: f64,
+    pub totals: EarningsTotals,
+}
+
+impl FrameEarnings {
+    fn from_frame(frame: &Frame) -> Self {
+        let microgons_per_usd = average(&frame.microgon_to_usd);
+        let microgons_per_argonot = average(&frame.microgon_to_argonot);
+        let totals = EarningsTotals {
+            blocks_mined: frame.blocks_mined_total,
+            microgons_mined: frame.microgons_mined_total,
+            microgons_minted: frame.microgons_minted_total,
+            micronots_mined: frame.micronots_mined_total,
+            microgon_fees_collected: frame.microgon_fees_collected_total,
+            seat_cost_microgons: frame.seat_cost_total_framed,
+            ..Default::default()
+        };
+
+        Self {
+            frame_id: frame.id,
+            first_tick: frame.first_tick,
+            is_processed: frame.is_processed,
+            microgons_per_usd,
+            microgons_per_argonot,
+            totals: totals.for_frame(microgons_per_usd, microgons_per_argonot),
+        }
+    }
+}
+
+/// Per-frame earnings, cached by frame id. Each query recomputes only the frames whose
+/// `updatedAt` has moved since the last one, so rewrites of processed frames are picked up too.
+#[derive(Default)]
+pub struct EarningsCache {
+    frames: Mutex<CachedFrames>,
+}
+
+#[derive(Default)]
+struct CachedFrames {
+    by_id: BTreeMap<i64, FrameEarnings>,
+    /// The latest frame `updatedAt` already reflected in `by_id`
+    updated_at: Option<String>,
+}
+
+impl EarningsCache {
+    pub async fn clear(&self) {
+        *self.frames.lock().await = CachedFrames::default();
+    }
+
+    async fn refresh(&self, pool: &SqlitePool) -> Result<(), CommanderError> {
+        let mut frames = self.frames.lock().await;
+        // read the mark first, so a write landing mid-refresh is still newer than it
+        let last_updated_at = Frame::fetch_last_updated_at(pool).await?;
+        let since = frames.updated_at.clone().unwrap_or_default();
+        for frame in Frame::fetch_updated_since(pool, &since).await? {
+            frames
+                .by_id
+                .insert(frame.id, FrameEarnings::from_frame(&frame));
+        }
+        frames.updated_at = last_updated_at;
+        Ok(())
+    }
+
+    pub async fn get_frames(
+        &self,
+        pool: &SqlitePool,
+        range: FrameRange,
+    ) -> Result<Vec<FrameEarnings>, CommanderError> {
+        self.refresh(pool).await?;
+        let frames = self.frames.lock().await;
+        let start = range.start_frame_id.unwrap_or(0);
+        let end = range.end_frame_id.unwrap_or(i64::MAX);
+        let limit = range.limit.map_or(usize::MAX, |x| x.max(0) as usize);
+        if start > end {
+            return Ok(vec![]);
+        }
+        Ok(frames
+            .by_id
+            .range(start..=end)
+            .take(limit)
+            .map(|(_, x)| x.clone())
+            .collect())
+    }
+
+    /// Totals over the latest `window_frames` frames.
+    pub async fn get_rolling(
+        &self,
+        pool: &SqlitePool,
+        window_frames: usize,
+    ) -> Result<EarningsTotals, CommanderError> {
+        self.refresh(pool).await?;
+        let frames = self.frames.lock().await;
+        let mut totals = EarningsTotals::default();
+        for frame in frames.by_id.values().rev().take(window_frames) {
+            totals.add(&frame.totals);
+        }
+        Ok(totals)
+    }
+
+    pub async fn get_cohort(
+        &self,
+        pool: &SqlitePool,
+        cohort_id: i64,
+    ) -> Result<Option<CohortEarnings>, CommanderError> {
+        self.refresh(pool).await?;
+        let range = FrameRange {
+            start_frame_id: Some(cohort_id),
+            end_frame_id: Some(cohort_id),
+            limit: Some(1),
+        };
+        let Some(cohort) = Cohort::fetch_range(pool, range).await?.pop() else {
+            return Ok(None);
+        };
+        let cohort_frames = CohortFrame::fetch_for_cohort(pool, cohort_id).await?;
+
+        let frames = self.frames.lock().await;
+        let prices = |frame_id: i64| {
+            frames.by_id.get(&frame_id).map_or((None, None), |x| {
+                (x.microgons_per_usd, x.microgons_per_argonot)
+            })
+        };
+        let cost_microgons =
+            cohort.microgons_bid_per_seat * cohort.seat_count_won + cohort.transaction_fees_total;
+        let mut totals = EarningsTotals {
+            seat_cost_microgons: cost_microgons,
+            seat_cost_usd: microgons_to_usd(cost_microgons, prices(cohort_id).0),
+            ..Default::default()
+        };
+        for cohort_frame in &cohort_frames {
+            let (per_usd, per_argonot) = prices(cohort_frame.frame_id);
+            let frame_totals = EarningsTotals {
+                blocks_mined: cohort_frame.blocks_mined_total,
+                microgons_mined: cohort_frame.microgons_mined_total,
+                microgons_minted: cohort_frame.microgons_minted_total,
+                micronots_mined: cohort_frame.micronots_mined_total,
+                microgon_fees_collected: cohort_frame.microgon_fees_collected_total,
+                ..Default::default()
+            };
+            totals.add(&frame_totals.for_frame(per_usd, per_argonot));
+        }
+        totals.finalize();
+
+        Ok(Some(CohortEarnings {
+            cohort_id,
+            seat_count_won: cohort.seat_count_won,
+            progress: cohort.progress,
+            totals,
+        }))
+    }
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn get_frame_earnings(
+    app: AppHandle,
+    cache: State<'_, EarningsCache>,
+    range: FrameRange,
+) -> Result<Vec<FrameEarnings>, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    cache.get_frames(&pool, range).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn get_cohort_earnings(
+    app: AppHandle,
+    cache: State<'_, EarningsCache>,
+    cohort_id: i64,
+) -> Result<Option<CohortEarnings>, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    cache.get_cohort(&pool, cohort_id).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn get_rolling_earnings(
+    app: AppHandle,
+    cache: State<'_, EarningsCache>,
+    window_frames: usize,
+) -> Result<EarningsTotals, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    cache.get_rolling(&pool, window_frames).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounting::MICROGONS_PER_ARGON;
+    use crate::db::BigIntJson;
+    use crate::test_utils::{self, TestDb, block_on};
+
+    /// A frame that mined `argons` for a seat cost of `cost_argons` while an argon was worth $2.
+    fn frame(id: i64, argons: i64, cost_argons: i64) -> Frame {
+        Frame {
+            microgons_mined_total: argons * MICROGONS_PER_ARGON,
+            seat_cost_total_framed: cost_argons * MICROGONS_PER_ARGON,
+            microgon_to_usd: BigIntJson(vec![MICROGONS_PER_ARGON / 2]),
+            ..test_utils::frame(id)
+        }
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn computes_roi_and_compounds_it_into_an_apy() {
+        let mut totals = FrameEarnings::from_frame(&frame(1, 0, 0)).totals;
+        for id in 2..=11 {
+            totals.add(&FrameEarnings::from_frame(&frame(id, 11, 10)).totals);
+        }
+        assert_eq!(totals.frame_count, 11);
+        assert_eq!(totals.earnings_microgons, Some(110 * MICROGONS_PER_ARGON));
+        assert_eq!(totals.profit_microgons, Some(10 * MICROGONS_PER_ARGON));
+        assert_close(totals.earnings_usd, 220.0);
+        assert_close(totals.profit_usd, 20.0);
+        assert_close(totals.roi_pct, 10.0);
+        assert_close(totals.apy_pct, (1.1f64.powf(365.0 / 11.0) - 1.0) * 100.0);
+
+        let total_loss = FrameEarnings::from_frame(&frame(1, 0, 10)).totals;
+        assert_close(total_loss.roi_pct, -100.0);
+        assert_close(total_loss.apy_pct, -100.0);
+        let no_cost = FrameEarnings::from_frame(&frame(1, 5, 0)).totals;
+        assert_eq!((no_cost.roi_pct, no_cost.apy_pct), (Some(0.0), Some(0.0)));
+    }
+
+    #[test]
+    fn leaves_values_without_a_price_missing() {
+        let unpriced = Frame {
+            microgon_to_usd: BigIntJson(vec![]),
+            micronots_mined_total: MICROGONS_PER_ARGON,
+            ..frame(1, 10, 5)
+        };
+        let earnings = FrameEarnings::from_frame(&unpriced);
+        assert_eq!(earnings.microgons_per_usd, None);
+        assert_eq!(earnings.totals.micronots_mined_as_microgons, None);
+        assert_eq!(earnings.totals.earnings_microgons, None);
+        assert_eq!(earnings.totals.seat_cost_usd, None);
+        assert_eq!(earnings.totals.roi_pct, None);
+
+        // argonots only need a price when some were mined
+        let earnings = FrameEarnings::from_frame(&frame(2, 10, 5));
+        assert_eq!(earnings.totals.micronots_mined_as_microgons, Some(0));
+        assert_close(earnings.totals.earnings_usd, 20.0);
+
+        let priced = Frame {
+            microgon_to_argonot: BigIntJson(vec![3 * MICROGONS_PER_ARGON]),
+            micronots_mined_total: 2 * MICROGONS_PER_ARGON,
+            ..frame(3, 0, 0)
+        };
+        let earnings = FrameEarnings::from_frame(&priced);
+        assert_eq!(
+            earnings.totals.micronots_mined_as_microgons,
+            Some(6 * MICROGONS_PER_ARGON)
+        );
+
+        let mut totals = earnings.totals;
+        totals.add(&FrameEarnings::from_frame(&unpriced).totals);
+        assert_eq!(totals.earnings_usd, None);
+        assert_eq!(totals.frame_count, 2);
+    }
+
+    #[test]
+    fn refreshes_only_frames_written_since_the_last_query() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            for id in 1..=3 {
+                frame(id, id, 0).upsert(&db.pool).await.unwrap();
+            }
+            sqlx::query("UPDATE Frames SET updatedAt = '2000-01-01 00:00:00'")
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            let cache = EarningsCache::default();
+            let all = FrameRange::default();
+            assert_eq!(cache.get_frames(&db.pool, all).await.unwrap().len(), 3);
+
+            // a deleted frame is only dropped by a full reload, so it shows what was re-read
+            sqlx::query("DELETE FROM Frames WHERE id = 1")
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            frame(2, 20, 0).upsert(&db.pool).await.unwrap();
+            frame(4, 4, 0).upsert(&db.pool).await.unwrap();
+            let mined = |frames: Vec<FrameEarnings>| {
+                frames
+                    .iter()
+                    .map(|x| (x.frame_id, x.totals.microgons_mined / MICROGONS_PER_ARGON))
+                    .collect::<Vec<_>>()
+            };
+            let frames = cache.get_frames(&db.pool, all).await.unwrap();
+            assert_eq!(mined(frames), [(1, 1), (2, 20), (3, 3), (4, 4)]);
+
+            let rolling = cache.get_rolling(&db.pool, 2).await.unwrap();
+            assert_eq!(rolling.frame_count, 2);
+            assert_eq!(rolling.microgons_mined, 7 * MICROGONS_PER_ARGON);
+
+            cache.clear().await;
+            let frames = cache.get_frames(&db.pool, all).await.unwrap();
+            assert_eq!(mined(frames), [(2, 20), (3, 3), (4, 4)]);
+        });
+    }
+
+    #[test]
+    fn totals_a_cohort_across_its_frames() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            let cohort = Cohort {
+                id: 2,
+                progress: 50.0,
+                seat_count_won: 2,
+                transaction_fees_total: 0,
+                micronots_staked_per_seat: 0,
+                microgons_bid_per_seat: 5 * MICROGONS_PER_ARGON,
+                microgons_to_be_mined_per_seat: 0,
+                micronots_to_be_mined_per_seat: 0,
+            };
+            for frame_id in [2, 3] {
+                frame(frame_id, 0, 0).upsert(&db.pool).await.unwrap();
+            }
+            cohort.upsert(&db.pool).await.unwrap();
+            for frame_id in [2, 3] {
+                CohortFrame {
+                    frame_id,
+                    cohort_id: 2,
+                    blocks_mined_total: 1,
+                    microgon_fees_collected_total: 0,
+                    micronots_mined_total: 0,
+                    microgons_mined_total: 6 * MICROGONS_PER_ARGON,
+                    microgons_minted_total: 0,
+                }
+                .upsert(&db.pool)
+                .await
+                .unwrap();
+            }
+
+            let cache = EarningsCache::default();
+            let earnings = cache.get_cohort(&db.pool, 2).await.unwrap().unwrap();
+            assert_eq!(earnings.totals.frame_count, 2);
+            assert_eq!(earnings.totals.blocks_mined, 2);
+            assert_eq!(
+                earnings.totals.profit_microgons,
+                Some(2 * MICROGONS_PER_ARGON)
+            );
+            assert_close(earnings.totals.seat_cost_usd, 20.0);
+            assert_close(earnings.totals.roi_pct, 20.0);
+            assert!(cache.get_cohort(&db.pool, 3).await.unwrap().is_none());
+        });
+    }
+}
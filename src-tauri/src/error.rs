@@ -0,0 +1,269 @@
+use std::fmt::{self, Display};
+
+/// Stable identifiers the UI can branch on instead of matching on message text.
+#[derive(serde::Serialize, specta::Type, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    SshAuthFailed,
+    SshTimeout,
+    SshConnectionRefused,
+    SshHostUnreachable,
+    SshMissingExitStatus,
+    NoConnection,
+    DockerNotRunning,
+    DockerCommandFailed,
+    QemuCommandFailed,
+    VmNotFound,
+    PortInUse,
+    MigrationFailed,
+    InstanceInUse,
+    DatabaseError,
+    KeychainUnavailable,
+    FileSystemError,
+    InvalidInput,
+    Unknown,
+}
+
+/// The error returned by every command. Serialized as `{ code, message, context }`, where
+/// `context` lists what was being attempted from the outermost operation inwards.
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CommanderError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub context: Vec<String>,
+}
+
+impl CommanderError {
+    pub fn new(code: ErrorCode, message: impl Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            context: vec![],
+        }
+    }
+
+    pub fn context(mut self, context: impl Display) -> Self {
+        self.context.insert(0, context.to_string());
+        self
+    }
+
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = code;
+        self
+    }
+}
+
+impl Display for CommanderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for context in &self.context {
+            write!(f, "{}: ", context)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CommanderError {}
+
+/// Only filesystem failures get a code here; callers that know what the io was for (a socket, a
+/// child process) set their own with [`ResultExt::code`].
+fn code_for_io_error(e: &std::io::Error) -> ErrorCode {
+    use std::io::ErrorKind;
+    match e.kind() {
+        ErrorKind::NotFound
+        | ErrorKind::PermissionDenied
+        | ErrorKind::AlreadyExists
+        | ErrorKind::NotADirectory
+        | ErrorKind::IsADirectory
+        | ErrorKind::DirectoryNotEmpty
+        | ErrorKind::ReadOnlyFilesystem
+        | ErrorKind::StorageFull => ErrorCode::FileSystemError,
+        _ => ErrorCode::Unknown,
+    }
+}
+
+/// io errors russh hit on the ssh connection itself.
+fn code_for_ssh_io_error(e: &std::io::Error) -> ErrorCode {
+    use std::io::ErrorKind;
+    match e.kind() {
+        ErrorKind::ConnectionRefused => ErrorCode::SshConnectionRefused,
+        ErrorKind::HostUnreachable | ErrorKind::NetworkUnreachable => ErrorCode::SshHostUnreachable,
+        ErrorKind::TimedOut => ErrorCode::SshTimeout,
+        ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::NotConnected => {
+            ErrorCode::NoConnection
+        }
+        _ => ErrorCode::Unknown,
+    }
+}
+
+impl From<String> for CommanderError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorCode::Unknown, message)
+    }
+}
+
+impl From<&str> for CommanderError {
+    fn from(message: &str) -> Self {
+        Self::new(ErrorCode::Unknown, message)
+    }
+}
+
+impl From<std::io::Error> for CommanderError {
+    fn from(e: std::io::Error) -> Self {
+        Self::new(code_for_io_error(&e), e)
+    }
+}
+
+impl From<sqlx::Error> for CommanderError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::new(ErrorCode::DatabaseError, e)
+    }
+}
+
+impl From<keyring::Error> for CommanderError {
+    fn from(e: keyring::Error) -> Self {
+        Self::new(ErrorCode::KeychainUnavailable, e)
+    }
+}
+
+#[cfg(feature = "gui")]
+impl From<tauri::Error> for CommanderError {
+    fn from(e: tauri::Error) -> Self {
+        Self::new(ErrorCode::Unknown, e)
+    }
+}
+
+impl From<bip39::Error> for CommanderError {
+    fn from(e: bip39::Error) -> Self {
+        Self::new(ErrorCode::Unknown, e)
+    }
+}
+
+fn code_for_russh_error(e: &russh::Error) -> ErrorCode {
+    match e {
+        russh::Error::IO(io) => code_for_ssh_io_error(io),
+        russh::Error::ConnectionTimeout | russh::Error::KeepaliveTimeout => ErrorCode::SshTimeout,
+        russh::Error::NotAuthenticated => ErrorCode::SshAuthFailed,
+        russh::Error::Disconnect | russh::Error::SendError | russh::Error::HUP => {
+            ErrorCode::NoConnection
+        }
+        _ => ErrorCode::Unknown,
+    }
+}
+
+impl From<russh::Error> for CommanderError {
+    fn from(e: russh::Error) -> Self {
+        Self::new(code_for_russh_error(&e), e)
+    }
+}
+
+/// Keeps the most specific error found in the chain and records the outer messages as context.
+impl From<anyhow::Error> for CommanderError {
+    fn from(e: anyhow::Error) -> Self {
+        let mut context = vec![];
+        for cause in e.chain() {
+            let root = if let Some(err) = cause.downcast_ref::<CommanderError>() {
+                Some(err.clone())
+            } else if let Some(err) = cause.downcast_ref::<russh::Error>() {
+                Some(Self::new(code_for_russh_error(err), err))
+            } else {
+                cause
+                    .downcast_ref::<std::io::Error>()
+                    .map(|err| Self::new(code_for_io_error(err), err))
+            };
+            if let Some(mut root) = root {
+                context.append(&mut root.context);
+                root.context = context;
+                return root;
+            }
+            context.push(cause.to_string());
+        }
+        let message = context.pop().unwrap_or_default();
+        Self {
+            code: ErrorCode::Unknown,
+            message,
+            context,
+        }
+    }
+}
+
+/// Adds a code or context to any error on its way out of a command.
+pub trait ResultExt<T> {
+    fn context(self, context: impl Display) -> Result<T, CommanderError>;
+    fn code(self, code: ErrorCode) -> Result<T, CommanderError>;
+}
+
+impl<T, E: Into<CommanderError>> ResultExt<T> for Result<T, E> {
+    fn context(self, context: impl Display) -> Result<T, CommanderError> {
+        self.map_err(|e| e.into().context(context))
+    }
+
+    fn code(self, code: ErrorCode) -> Result<T, CommanderError> {
+        self.map_err(|e| e.into().with_code(code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn maps_io_errors_to_ssh_codes_only_through_russh() {
+        let refused = || io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert_eq!(CommanderError::from(refused()).code, ErrorCode::Unknown);
+        assert_eq!(
+            CommanderError::from(russh::Error::IO(refused())).code,
+            ErrorCode::SshConnectionRefused
+        );
+        assert_eq!(
+            CommanderError::from(io::Error::from(io::ErrorKind::PermissionDenied)).code,
+            ErrorCode::FileSystemError
+        );
+        assert_eq!(
+            CommanderError::from(russh::Error::NotAuthenticated).code,
+            ErrorCode::SshAuthFailed
+        );
+    }
+
+    #[test]
+    fn keeps_the_innermost_known_error_from_an_anyhow_chain() {
+        let error = anyhow::Error::new(russh::Error::IO(io::ErrorKind::TimedOut.into()))
+            .context("opening channel")
+            .context("running command");
+        let error = CommanderError::from(error);
+        assert_eq!(error.code, ErrorCode::SshTimeout);
+        assert_eq!(error.context, ["running command", "opening channel"]);
+
+        let inner =
+            CommanderError::new(ErrorCode::SshAuthFailed, "denied").context("authenticating");
+        let error = CommanderError::from(anyhow::Error::new(inner).context("connecting"));
+        assert_eq!(error.code, ErrorCode::SshAuthFailed);
+        assert_eq!(error.context, ["connecting", "authenticating"]);
+        assert_eq!(error.to_string(), "connecting: authenticating: denied");
+
+        let error = anyhow::Error::new(io::Error::from(io::ErrorKind::ConnectionRefused))
+            .context("reading key");
+        let error = CommanderError::from(error);
+        assert_eq!(error.code, ErrorCode::Unknown);
+    }
+
+    #[test]
+    fn falls_back_to_unknown_with_the_root_cause_as_message() {
+        let error = anyhow::anyhow!("root cause").context("outer");
+        let error = CommanderError::from(error);
+        assert_eq!(error.code, ErrorCode::Unknown);
+        assert_eq!(error.message, "root cause");
+        assert_eq!(error.context, ["outer"]);
+    }
+
+    #[test]
+    fn lets_callers_override_the_code() {
+        let result: Result<(), io::Error> = Err(io::ErrorKind::ConnectionRefused.into());
+        let error = result
+            .context("Error reaching the daemon")
+            .code(ErrorCode::DockerNotRunning)
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::DockerNotRunning);
+        assert_eq!(error.context, ["Error reaching the daemon"]);
+    }
+}
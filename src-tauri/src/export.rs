@@ -0,0 +1,548 @@
+use crate::accounting::{
+    MICROGONS_PER_ARGON, average, format_micro_units, microgons_to_usd, micronots_to_microgons,
+    millis_to_date, serialize_micro_units, write_csv,
+};
+#[cfg(feature = "gui")]
+use crate::db::DbPool;
+use crate::db::{Cohort, Frame, FrameBid, FrameRange};
+use crate::error::CommanderError;
+#[cfg(feature = "gui")]
+use crate::error::ResultExt;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "gui")]
+use tauri::AppHandle;
+
+#[derive(serde::Deserialize, specta::Type, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// Either a frame range or a date range (unix millis) can be given. Dates are mapped onto frames
+/// with the network's tick duration.
+#[derive(serde::Deserialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportHistoryRequest {
+    pub format: ExportFormat,
+    pub output_dir: PathBuf,
+    pub start_frame_id: Option<i64>,
+    pub end_frame_id: Option<i64>,
+    pub start_date_millis: Option<i64>,
+    pub end_date_millis: Option<i64>,
+    pub tick_millis: i64,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct FrameIncomeRow {
+    frame_id: i64,
+    date: String,
+    is_processed: bool,
+    blocks_mined: i64,
+    argons_mined: String,
+    argons_minted: String,
+    argonots_mined: String,
+    argon_fees_collected: String,
+    seat_cost_argons: String,
+    /// USD values are left empty for frames without a price.
+    usd_per_argon: Option<f64>,
+    income_usd: Option<f64>,
+    seat_cost_usd: Option<f64>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct CohortCostRow {
+    cohort_id: i64,
+    date: String,
+    seats_won: i64,
+    argons_bid_per_seat: String,
+    argonots_staked_per_seat: String,
+    transaction_fees_argons: String,
+    total_cost_argons: String,
+    total_cost_usd: Option<f64>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct FrameBidRow {
+    frame_id: i64,
+    address: String,
+    sub_account_index: Option<i64>,
+    bid_position: i64,
+    argons_per_seat: String,
+    confirmed_at_block_number: i64,
+}
+
+#[derive(serde::Serialize, sqlx::FromRow, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(rename_all = "camelCase")]
+struct BitcoinLockEventRow {
+    utxo_id: i64,
+    vault_id: i64,
+    from_status: Option<String>,
+    to_status: String,
+    at: String,
+    satoshis: i64,
+    #[serde(rename = "peggedPriceArgons", serialize_with = "serialize_micro_units")]
+    pegged_price: i64,
+    txid: Option<String>,
+    released_txid: Option<String>,
+}
+
+#[derive(sqlx::FromRow, Debug, Clone)]
+#[sqlx(rename_all = "camelCase")]
+struct VaultRecord {
+    id: i64,
+    hd_path: String,
+    created_at_block_height: i64,
+    /// bigints are stored as text
+    operational_fee_microgons: Option<String>,
+    prebonded_microgons: Option<String>,
+    is_closed: bool,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct VaultRow {
+    id: i64,
+    hd_path: String,
+    created_at_block_height: i64,
+    operational_fee_argons: Option<String>,
+    prebonded_argons: Option<String>,
+    is_closed: bool,
+}
+
+impl From<VaultRecord> for VaultRow {
+    fn from(record: VaultRecord) -> Self {
+        let format_text = |x: Option<String>| {
+            x.and_then(|x| x.parse::<i64>().ok())
+                .map(format_micro_units)
+        };
+        VaultRow {
+            id: record.id,
+            hd_path: record.hd_path,
+            created_at_block_height: record.created_at_block_height,
+            operational_fee_argons: format_text(record.operational_fee_microgons),
+            prebonded_argons: format_text(record.prebonded_microgons),
+            is_closed: record.is_closed,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct HistoryExport {
+    frames: Vec<FrameIncomeRow>,
+    cohorts: Vec<CohortCostRow>,
+    frame_bids: Vec<FrameBidRow>,
+    bitcoin_lock_events: Vec<BitcoinLockEventRow>,
+    vaults: Vec<VaultRow>,
+}
+
+impl ExportHistoryRequest {
+    /// Resolves the requested range to frame ids and the matching unix-millis window.
+    async fn resolve_range(
+        &self,
+        pool: &SqlitePool,
+    ) -> Result<(FrameRange, i64, i64), CommanderError> {
+        let tick_millis = self.tick_millis.max(1);
+        let mut range = FrameRange {
+            start_frame_id: self.start_frame_id,
+            end_frame_id: self.end_frame_id,
+            limit: None,
+        };
+        if self.start_date_millis.is_some() || self.end_date_millis.is_some() {
+            let start_tick = self.start_date_millis.unwrap_or(0) / tick_millis;
+            let end_tick = self.end_date_millis.unwrap_or(i64::MAX) / tick_millis;
+            let (start_id, end_id): (Option<i64>, Option<i64>) = sqlx::query_as(
+                "SELECT MIN(id), MAX(id) FROM Frames WHERE lastTick >= ? AND firstTick <= ?",
+            )
+            .bind(start_tick)
+            .bind(end_tick)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| format!("Failed to resolve date range: {}", e))?;
+            range.start_frame_id = Some(start_id.unwrap_or(i64::MAX));
+            range.end_frame_id = Some(end_id.unwrap_or(-1));
+        }
+
+        let (first_tick, last_tick): (Option<i64>, Option<i64>) = sqlx::query_as(
+            "SELECT MIN(firstTick), MAX(lastTick) FROM Frames WHERE id >= ? AND id <= ?",
+        )
+        .bind(range.start_frame_id.unwrap_or(0))
+        .bind(range.end_frame_id.unwrap_or(i64::MAX))
+        .fetch_one(pool)
+        .await
+        .map_err(|e| format!("Failed to resolve frame range: {}", e))?;
+        let start_millis = self
+            .start_date_millis
+            .or(first_tick.map(|x| x * tick_millis))
+            .unwrap_or(0);
+        let end_millis = self
+            .end_date_millis
+            .or(last_tick.map(|x| (x + 1) * tick_millis))
+            .unwrap_or(i64::MAX);
+        Ok((range, start_millis, end_millis))
+    }
+}
+
+async fn build_export(
+    pool: &SqlitePool,
+    request: &ExportHistoryRequest,
+) -> Result<HistoryExport, CommanderError> {
+    let (range, start_millis, end_millis) = request.resolve_range(pool).await?;
+    let tick_millis = request.tick_millis.max(1);
+    let mut export = HistoryExport::default();
+
+    let frames = Frame::fetch_range(pool, range).await?;
+    let mut usd_by_frame = HashMap::new();
+    for frame in &frames {
+        let microgons_per_usd = average(&frame.microgon_to_usd);
+        usd_by_frame.insert(frame.id, (microgons_per_usd, frame.first_tick));
+        let income = micronots_to_microgons(
+            frame.micronots_mined_total,
+            average(&frame.microgon_to_argonot),
+        )
+        .map(|argonots_as_microgons| {
+            frame.microgons_mined_total
+                + frame.microgons_minted_total
+                + frame.microgon_fees_collected_total
+                + argonots_as_microgons
+        });
+        export.frames.push(FrameIncomeRow {
+            frame_id: frame.id,
+            date: millis_to_date(frame.first_tick * tick_millis),
+            is_processed: frame.is_processed,
+            blocks_mined: frame.blocks_mined_total,
+            argons_mined: format_micro_units(frame.microgons_mined_total),
+            argons_minted: format_micro_units(frame.microgons_minted_total),
+            argonots_mined: format_micro_units(frame.micronots_mined_total),
+            argon_fees_collected: format_micro_units(frame.microgon_fees_collected_total),
+            seat_cost_argons: format_micro_units(frame.seat_cost_total_framed),
+            usd_per_argon: microgons_per_usd.map(|x| MICROGONS_PER_ARGON as f64 / x),
+            income_usd: income.and_then(|x| microgons_to_usd(x, microgons_per_usd)),
+            seat_cost_usd: microgons_to_usd(frame.seat_cost_total_framed, microgons_per_usd),
+        });
+    }
+
+    for cohort in Cohort::fetch_range(pool, range).await? {
+        let (microgons_per_usd, first_tick) =
+            usd_by_frame.get(&cohort.id).copied().unwrap_or((None, 0));
+        let total_cost =
+            cohort.microgons_bid_per_seat * cohort.seat_count_won + cohort.transaction_fees_total;
+        export.cohorts.push(CohortCostRow {
+            cohort_id: cohort.id,
+            date: millis_to_date(first_tick * tick_millis),
+            seats_won: cohort.seat_count_won,
+            argons_bid_per_seat: format_micro_units(cohort.microgons_bid_per_seat),
+            argonots_staked_per_seat: format_micro_units(cohort.micronots_staked_per_seat),
+            transaction_fees_argons: format_micro_units(cohort.transaction_fees_total),
+            total_cost_argons: format_micro_units(total_cost),
+            total_cost_usd: microgons_to_usd(total_cost, microgons_per_usd),
+        });
+    }
+
+    for bid in FrameBid::fetch_range(pool, range).await? {
+        export.frame_bids.push(FrameBidRow {
+            frame_id: bid.frame_id,
+            address: bid.address,
+            sub_account_index: bid.sub_account_index,
+            bid_position: bid.bid_position,
+            argons_per_seat: format_micro_units(bid.microgons_per_seat),
+            confirmed_at_block_number: bid.confirmed_at_block_number,
+        });
+    }
+
+    export.bitcoin_lock_events = sqlx::query_as::<_, BitcoinLockEventRow>(
+        "SELECT t.utxoId, l.vaultId, t.fromStatus, t.toStatus, CAST(t.createdAt AS TEXT) AS at,
+            l.satoshis, l.peggedPrice, l.txid, l.releasedTxid
+        FROM BitcoinLockTransitions t
+        INNER JOIN BitcoinLocks l ON l.utxoId = t.utxoId
+        WHERE CAST(strftime('%s', t.createdAt) AS INTEGER) * 1000 >= ?
+            AND CAST(strftime('%s', t.createdAt) AS INTEGER) * 1000 < ?
+        ORDER BY t.id ASC",
+    )
+    .bind(start_millis)
+    .bind(end_millis)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to export bitcoin locks: {}", e))?;
+
+    export.vaults = sqlx::query_as::<_, VaultRecord>(
+        "SELECT id, hdPath, createdAtBlockHeight, operationalFeeMicrogons, prebondedMicrogons,
+            isClosed
+        FROM Vaults ORDER BY id ASC",
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to export vaults: {}", e))?
+    .into_iter()
+    .map(VaultRow::from)
+    .collect();
+
+    Ok(export)
+}
+
+pub async fn write_history(
+    pool: &SqlitePool,
+    request: ExportHistoryRequest,
+) -> Result<Vec<PathBuf>, CommanderError> {
+    let export = build_export(pool, &request).await?;
+    let dir = &request.output_dir;
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Error creating directory {}: {}", dir.display(), e))?;
+
+    if request.format == ExportFormat::Json {
+        let path = dir.join("history.json");
+        let json = serde_json::to_string_pretty(&export).map_err(|e| e.to_string())?;
+        fs::write(&path, json)
+            .map_err(|e| format!("Error writing file {}: {}", path.display(), e))?;
+        return Ok(vec![path]);
+    }
+
+    Ok(vec![
+        write_csv(
+            dir.join("frames.csv"),
+            &[
+                "frameId",
+                "date",
+                "isProcessed",
+                "blocksMined",
+                "argonsMined",
+                "argonsMinted",
+                "argonotsMined",
+                "argonFeesCollected",
+                "seatCostArgons",
+                "usdPerArgon",
+                "incomeUsd",
+                "seatCostUsd",
+            ],
+            &export.frames,
+        )?,
+        write_csv(
+            dir.join("cohorts.csv"),
+            &[
+                "cohortId",
+                "date",
+                "seatsWon",
+                "argonsBidPerSeat",
+                "argonotsStakedPerSeat",
+                "transactionFeesArgons",
+                "totalCostArgons",
+                "totalCostUsd",
+            ],
+            &export.cohorts,
+        )?,
+        write_csv(
+            dir.join("frame-bids.csv"),
+            &[
+                "frameId",
+                "address",
+                "subAccountIndex",
+                "bidPosition",
+                "argonsPerSeat",
+                "confirmedAtBlockNumber",
+            ],
+            &export.frame_bids,
+        )?,
+        write_csv(
+            dir.join("bitcoin-lock-events.csv"),
+            &[
+                "utxoId",
+                "vaultId",
+                "fromStatus",
+                "toStatus",
+                "at",
+                "satoshis",
+                "peggedPriceArgons",
+                "txid",
+                "releasedTxid",
+            ],
+            &export.bitcoin_lock_events,
+        )?,
+        write_csv(
+            dir.join("vaults.csv"),
+            &[
+                "id",
+                "hdPath",
+                "createdAtBlockHeight",
+                "operationalFeeArgons",
+                "prebondedArgons",
+                "isClosed",
+            ],
+            &export.vaults,
+        )?,
+    ])
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn export_history(
+    app: AppHandle,
+    request: ExportHistoryRequest,
+) -> Result<Vec<PathBuf>, CommanderError> {
+    log::info!("export_history: {:?}", request);
+    let pool = DbPool::get(&app).await?;
+    write_history(&pool, request)
+        .await
+        .context("Failed to export history")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::BigIntJson;
+    use crate::test_utils::{self, TestDb, block_on};
+
+    fn request(output_dir: &Path) -> ExportHistoryRequest {
+        ExportHistoryRequest {
+            format: ExportFormat::Csv,
+            output_dir: output_dir.to_path_buf(),
+            start_frame_id: None,
+            end_frame_id: None,
+            start_date_millis: None,
+            end_date_millis: None,
+            tick_millis: 60_000,
+        }
+    }
+
+    async fn insert_frames(pool: &SqlitePool) {
+        // an argon is worth $2 and an argonot 3 argons
+        let priced = Frame {
+            microgons_mined_total: 4 * MICROGONS_PER_ARGON,
+            micronots_mined_total: MICROGONS_PER_ARGON,
+            seat_cost_total_framed: MICROGONS_PER_ARGON,
+            microgon_to_usd: BigIntJson(vec![MICROGONS_PER_ARGON / 2]),
+            microgon_to_argonot: BigIntJson(vec![3 * MICROGONS_PER_ARGON]),
+            ..test_utils::frame(1)
+        };
+        priced.upsert(pool).await.unwrap();
+        let unpriced = Frame {
+            microgons_mined_total: 4 * MICROGONS_PER_ARGON,
+            micronots_mined_total: MICROGONS_PER_ARGON,
+            microgon_to_usd: BigIntJson(vec![MICROGONS_PER_ARGON / 2]),
+            ..test_utils::frame(2)
+        };
+        unpriced.upsert(pool).await.unwrap();
+        test_utils::frame(3).upsert(pool).await.unwrap();
+    }
+
+    #[test]
+    fn leaves_usd_empty_without_a_price() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            insert_frames(&db.pool).await;
+            let export = build_export(&db.pool, &request(db.dir.path()))
+                .await
+                .unwrap();
+
+            let usd = export
+                .frames
+                .iter()
+                .map(|x| (x.usd_per_argon, x.income_usd, x.seat_cost_usd))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                usd,
+                [
+                    (Some(2.0), Some(14.0), Some(2.0)),
+                    // argonots were mined but can't be valued
+                    (Some(2.0), None, Some(0.0)),
+                    (None, None, None),
+                ]
+            );
+            assert_eq!(export.frames[0].argonots_mined, "1.000000");
+        });
+    }
+
+    #[test]
+    fn maps_a_date_range_onto_frames() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            insert_frames(&db.pool).await;
+            let tick_millis = 60_000;
+            let request = ExportHistoryRequest {
+                start_date_millis: Some(2 * 1440 * tick_millis),
+                end_date_millis: Some(2 * 1440 * tick_millis + 1),
+                ..request(db.dir.path())
+            };
+            let (range, start_millis, end_millis) = request.resolve_range(&db.pool).await.unwrap();
+            assert_eq!(
+                (range.start_frame_id, range.end_frame_id),
+                (Some(2), Some(2))
+            );
+            assert_eq!(
+                (start_millis, end_millis),
+                (2 * 1440 * tick_millis, 2 * 1440 * tick_millis + 1)
+            );
+
+            let request = ExportHistoryRequest {
+                start_frame_id: Some(3),
+                start_date_millis: None,
+                end_date_millis: None,
+                ..request
+            };
+            let (_, start_millis, end_millis) = request.resolve_range(&db.pool).await.unwrap();
+            assert_eq!(
+                (start_millis, end_millis),
+                (3 * 1440 * tick_millis, 4 * 1440 * tick_millis)
+            );
+
+            let request = ExportHistoryRequest {
+                start_date_millis: Some(10 * 1440 * tick_millis),
+                ..request
+            };
+            let export = build_export(&db.pool, &request).await.unwrap();
+            assert!(export.frames.is_empty());
+        });
+    }
+
+    #[test]
+    fn writes_one_csv_per_table_or_a_single_json() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            insert_frames(&db.pool).await;
+            let out = db.dir.path().join("export");
+            let files = write_history(&db.pool, request(&out)).await.unwrap();
+            let names = files
+                .iter()
+                .map(|x| x.file_name().unwrap().to_str().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                names,
+                [
+                    "frames.csv",
+                    "cohorts.csv",
+                    "frame-bids.csv",
+                    "bitcoin-lock-events.csv",
+                    "vaults.csv"
+                ]
+            );
+            let frames = fs::read_to_string(&files[0]).unwrap();
+            let rows = frames.lines().collect::<Vec<_>>();
+            assert_eq!(rows.len(), 4);
+            assert!(rows[0].ends_with("usdPerArgon,incomeUsd,seatCostUsd"));
+            assert!(
+                rows[2].ends_with(",1.000000,0.000000,0.000000,2.0,,0.0"),
+                "{}",
+                rows[2]
+            );
+            assert!(rows[3].ends_with(",,,"), "{}", rows[3]);
+
+            let request = ExportHistoryRequest {
+                format: ExportFormat::Json,
+                ..request(&out)
+            };
+            let files = write_history(&db.pool, request).await.unwrap();
+            let json: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(&files[0]).unwrap()).unwrap();
+            assert_eq!(json["frames"][2]["incomeUsd"], serde_json::Value::Null);
+            assert_eq!(json["frames"][0]["incomeUsd"], 14.0);
+        });
+    }
+}
@@ -0,0 +1,329 @@
+#[cfg(feature = "gui")]
+use crate::db::DbPool;
+use crate::error::CommanderError;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+#[cfg(feature = "gui")]
+use tauri::AppHandle;
+
+pub fn get_hd_path(vault_id: i64, index: i64) -> String {
+    format!("m/1018'/0'/{}'/0/{}", vault_id, index)
+}
+
+fn parse_hd_index(hd_path: &str) -> Option<i64> {
+    hd_path.rsplit('/').next()?.parse::<i64>().ok()
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HdReservation {
+    pub vault_id: i64,
+    pub hd_index: i64,
+    pub hd_path: String,
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum HdIndexState {
+    /// A bitcoin lock exists with this path
+    Used,
+    /// Handed out and not yet used or released. A stale one may still have been locked on-chain
+    /// if the app stopped before saving the lock, so check before releasing it.
+    Reserved,
+    /// Released, so the next reservation will reuse it
+    Abandoned,
+    /// Below the high-water mark, but never reserved or used
+    Unused,
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HdIndexScan {
+    pub hd_index: i64,
+    pub hd_path: String,
+    pub state: HdIndexState,
+    pub utxo_id: Option<i64>,
+}
+
+/// Hands out the next hd path for a vault. Runs in a single transaction, so concurrent callers
+/// can't receive the same index. Released reservations are reused before the sequence grows; a
+/// reservation is never reclaimed on age alone, since its lock may exist on-chain without a record.
+pub async fn reserve(pool: &SqlitePool, vault_id: i64) -> Result<HdReservation, CommanderError> {
+    let map_err = |e: sqlx::Error| {
+        CommanderError::from(e).context(format!("Failed to reserve hd path for vault {}", vault_id))
+    };
+    let mut tx = pool.begin().await.map_err(map_err)?;
+
+    // take the write lock up front so the reads below can't race another reservation
+    sqlx::query(
+        "INSERT OR IGNORE INTO BitcoinLockVaultHdSeq (vaultId, latestIndex) VALUES (?, -1)",
+    )
+    .bind(vault_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(map_err)?;
+
+    let reclaimed: Option<(i64,)> = sqlx::query_as(
+        "SELECT hdIndex FROM BitcoinLockHdReservations
+        WHERE vaultId = ? AND status = 'abandoned'
+            AND hdPath NOT IN (SELECT hdPath FROM BitcoinLocks)
+        ORDER BY hdIndex ASC LIMIT 1",
+    )
+    .bind(vault_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(map_err)?;
+
+    let hd_index = if let Some((hd_index,)) = reclaimed {
+        log::info!(
+            "Reclaiming released hd index {} for vault {}",
+            hd_index,
+            vault_id
+        );
+        sqlx::query(
+            "UPDATE BitcoinLockHdReservations SET status = 'reserved', reservedAt = CURRENT_TIMESTAMP
+            WHERE vaultId = ? AND hdIndex = ?",
+        )
+        .bind(vault_id)
+        .bind(hd_index)
+        .execute(&mut *tx)
+        .await
+        .map_err(map_err)?;
+        hd_index
+    } else {
+        let (hd_index,): (i64,) = sqlx::query_as(
+            "UPDATE BitcoinLockVaultHdSeq SET latestIndex = MAX(
+                latestIndex,
+                COALESCE((SELECT MAX(hdIndex) FROM BitcoinLockHdReservations WHERE vaultId = ?), -1)
+            ) + 1
+            WHERE vaultId = ?
+            RETURNING latestIndex",
+        )
+        .bind(vault_id)
+        .bind(vault_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(map_err)?;
+        sqlx::query(
+            "INSERT INTO BitcoinLockHdReservations (vaultId, hdIndex, hdPath, status)
+            VALUES (?, ?, ?, 'reserved')",
+        )
+        .bind(vault_id)
+        .bind(hd_index)
+        .bind(get_hd_path(vault_id, hd_index))
+        .execute(&mut *tx)
+        .await
+        .map_err(map_err)?;
+        hd_index
+    };
+
+    tx.commit().await.map_err(map_err)?;
+    Ok(HdReservation {
+        vault_id,
+        hd_index,
+        hd_path: get_hd_path(vault_id, hd_index),
+    })
+}
+
+/// Releases a reservation that will not be used, so the next lock can take its index. Only call
+/// this when creating the lock failed, or the index was checked on-chain and found unused.
+pub async fn release(
+    pool: &SqlitePool,
+    vault_id: i64,
+    hd_index: i64,
+) -> Result<(), CommanderError> {
+    sqlx::query(
+        "UPDATE BitcoinLockHdReservations SET status = 'abandoned'
+        WHERE vaultId = ? AND hdIndex = ? AND status = 'reserved'",
+    )
+    .bind(vault_id)
+    .bind(hd_index)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        CommanderError::from(e).context(format!("Failed to release hd index {}", hd_index))
+    })?;
+    Ok(())
+}
+
+/// Lists every index up to the vault's high-water mark with how it is used, to support recovering
+/// locks whose records were lost.
+pub async fn scan(pool: &SqlitePool, vault_id: i64) -> Result<Vec<HdIndexScan>, CommanderError> {
+    let map_err = |e: sqlx::Error| {
+        CommanderError::from(e).context(format!("Failed to scan hd paths for vault {}", vault_id))
+    };
+    let mut conn = pool.acquire().await.map_err(map_err)?;
+
+    let locks: Vec<(i64, String)> =
+        sqlx::query_as("SELECT utxoId, hdPath FROM BitcoinLocks WHERE vaultId = ?")
+            .bind(vault_id)
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(map_err)?;
+    let reservations: Vec<(i64, String)> =
+        sqlx::query_as("SELECT hdIndex, status FROM BitcoinLockHdReservations WHERE vaultId = ?")
+            .bind(vault_id)
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(map_err)?;
+    let latest_index: Option<(i64,)> =
+        sqlx::query_as("SELECT latestIndex FROM BitcoinLockVaultHdSeq WHERE vaultId = ?")
+            .bind(vault_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(map_err)?;
+
+    let locks_by_index: HashMap<i64, i64> = locks
+        .iter()
+        .filter_map(|(utxo_id, hd_path)| Some((parse_hd_index(hd_path)?, *utxo_id)))
+        .collect();
+    let reservations: HashMap<i64, String> = reservations.into_iter().collect();
+    let max_index = [
+        latest_index.map(|x| x.0),
+        locks_by_index.keys().max().copied(),
+        reservations.keys().max().copied(),
+    ]
+    .into_iter()
+    .flatten()
+    .max()
+    .unwrap_or(-1);
+
+    Ok((0..=max_index)
+        .map(|hd_index| {
+            let utxo_id = locks_by_index.get(&hd_index).copied();
+            let state = match (utxo_id, reservations.get(&hd_index).map(String::as_str)) {
+                (Some(_), _) | (None, Some("used")) => HdIndexState::Used,
+                (None, Some("reserved")) => HdIndexState::Reserved,
+                (None, Some("abandoned")) => HdIndexState::Abandoned,
+                _ => HdIndexState::Unused,
+            };
+            HdIndexScan {
+                hd_index,
+                hd_path: get_hd_path(vault_id, hd_index),
+                state,
+                utxo_id,
+            }
+        })
+        .collect())
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn reserve_bitcoin_lock_hd_path(
+    app: AppHandle,
+    vault_id: i64,
+) -> Result<HdReservation, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    reserve(&pool, vault_id).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn release_bitcoin_lock_hd_path(
+    app: AppHandle,
+    vault_id: i64,
+    hd_index: i64,
+) -> Result<(), CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    release(&pool, vault_id, hd_index).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn scan_bitcoin_lock_hd_paths(
+    app: AppHandle,
+    vault_id: i64,
+) -> Result<Vec<HdIndexScan>, CommanderError> {
+    let pool = DbPool::get(&app).await?;
+    scan(&pool, vault_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestDb, block_on, insert_bitcoin_lock};
+
+    async fn insert_lock(pool: &SqlitePool, utxo_id: i64, vault_id: i64, hd_index: i64) {
+        insert_bitcoin_lock(pool, utxo_id, vault_id, &get_hd_path(vault_id, hd_index)).await;
+    }
+
+    async fn states(pool: &SqlitePool, vault_id: i64) -> Vec<HdIndexState> {
+        scan(pool, vault_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|x| x.state)
+            .collect()
+    }
+
+    #[test]
+    fn parses_the_index_from_a_path() {
+        assert_eq!(get_hd_path(3, 12), "m/1018'/0'/3'/0/12");
+        assert_eq!(parse_hd_index(&get_hd_path(3, 12)), Some(12));
+        assert_eq!(parse_hd_index("m/1018'/0'/3'"), None);
+    }
+
+    #[test]
+    fn hands_out_indexes_in_order_per_vault() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            let pool = db.pool;
+            for expected in 0..3 {
+                assert_eq!(reserve(&pool, 1).await.unwrap().hd_index, expected);
+            }
+            let other = reserve(&pool, 2).await.unwrap();
+            assert_eq!(other.hd_index, 0);
+            assert_eq!(other.hd_path, "m/1018'/0'/2'/0/0");
+        });
+    }
+
+    #[test]
+    fn reuses_only_released_reservations() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            let pool = db.pool;
+            for _ in 0..3 {
+                reserve(&pool, 1).await.unwrap();
+            }
+            insert_lock(&pool, 100, 1, 0).await;
+            release(&pool, 1, 1).await.unwrap();
+            // a used path is never released
+            release(&pool, 1, 0).await.unwrap();
+            assert_eq!(
+                states(&pool, 1).await,
+                [
+                    HdIndexState::Used,
+                    HdIndexState::Abandoned,
+                    HdIndexState::Reserved
+                ]
+            );
+
+            assert_eq!(reserve(&pool, 1).await.unwrap().hd_index, 1);
+            assert_eq!(reserve(&pool, 1).await.unwrap().hd_index, 3);
+        });
+    }
+
+    #[test]
+    fn skips_released_paths_a_lock_was_saved_with() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            let pool = db.pool;
+            reserve(&pool, 1).await.unwrap();
+            release(&pool, 1, 0).await.unwrap();
+            insert_lock(&pool, 100, 1, 0).await;
+            // as if the lock was saved before its reservation was marked used
+            sqlx::query("UPDATE BitcoinLockHdReservations SET status = 'abandoned'")
+                .execute(&pool)
+                .await
+                .unwrap();
+
+            assert_eq!(reserve(&pool, 1).await.unwrap().hd_index, 1);
+            let scanned = scan(&pool, 1).await.unwrap();
+            assert_eq!(scanned[0].state, HdIndexState::Used);
+            assert_eq!(scanned[0].utxo_id, Some(100));
+        });
+    }
+}
@@ -0,0 +1,626 @@
+#[cfg(any(feature = "gui", test))]
+use crate::error::{CommanderError, ErrorCode, ResultExt};
+#[cfg(any(feature = "gui", test))]
+use crate::migrations;
+#[cfg(feature = "gui")]
+use crate::utils::{Network, Utils};
+#[cfg(feature = "gui")]
+use crate::vm::{self, Vm};
+#[cfg(feature = "gui")]
+use fs2::FileExt;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "gui")]
+use std::path::PathBuf;
+#[cfg(feature = "gui")]
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, Manager};
+
+const METADATA_FILE_NAME: &str = "instance.json";
+/// Held by the app while the instance is open, and by the cli, see [`OpenInstance`].
+#[cfg(any(feature = "gui", test))]
+const LOCK_FILE_NAME: &str = "instance.lock";
+
+/// Files that belong to a running process or a single machine and are never copied into a clone.
+#[cfg(any(feature = "gui", test))]
+const CLONE_SKIP: &[&str] = &[
+    LOCK_FILE_NAME,
+    "database.sqlite",
+    "database.sqlite-wal",
+    "database.sqlite-shm",
+    "virtual-machine",
+    "vm-snapshots",
+    "database-backups",
+    METADATA_FILE_NAME,
+];
+
+/// The instance's wallet and server keys. Only copied into a clone when the caller opts in.
+#[cfg(any(feature = "gui", test))]
+const KEY_FILES: &[&str] = &["mnemonic", "serverkey.pub", "serverkey.pem"];
+
+/// Config keys describing the source's server. A clone starts without one so it can't drive the
+/// same machine as the instance it was copied from.
+#[cfg(any(feature = "gui", test))]
+const CLONE_CLEARED_CONFIG: &[&str] = &[
+    "serverCreation",
+    "serverDetails",
+    "installDetails",
+    "isMinerReadyToInstall",
+    "isMiningMachineCreated",
+    "isMinerInstalled",
+    "isMinerUpToDate",
+    "isMinerWaitingForUpgradeApproval",
+];
+
+/// Mirrors `ServerType.LocalComputer` in src-vue/interfaces/IConfig.ts
+#[cfg(feature = "gui")]
+const LOCAL_COMPUTER_SERVER_TYPE: &str = "LocalComputer";
+
+#[derive(serde::Serialize, serde::Deserialize, specta::Type, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceMetadata {
+    pub last_opened_at_millis: Option<u64>,
+    pub server_type: Option<String>,
+    pub mining_account_address: Option<String>,
+    /// Host ports moved off the network defaults to avoid conflicts, keyed by env name
+    #[serde(default)]
+    pub port_overrides: BTreeMap<String, u16>,
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Instance {
+    pub network_name: String,
+    pub instance_name: String,
+    /// This process is running the instance
+    pub is_current: bool,
+    /// Another Commander process has the instance open
+    pub is_in_use: bool,
+    pub metadata: InstanceMetadata,
+}
+
+impl InstanceMetadata {
+    pub fn load(config_instance_dir: &Path) -> Self {
+        let path = config_instance_dir.join(METADATA_FILE_NAME);
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&text).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    #[cfg(any(feature = "gui", test))]
+    pub fn save(&self, config_instance_dir: &Path) -> Result<(), CommanderError> {
+        let path = config_instance_dir.join(METADATA_FILE_NAME);
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))?;
+        fs::write(&path, text).context(format!("Writing {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Holds `instance.lock` for the lifetime of the app. Other processes check it to tell the
+/// instance is open, and the cli takes the same lock, so it can't migrate the database underneath
+/// the app.
+#[cfg(feature = "gui")]
+pub struct OpenInstance {
+    config_instance_dir: PathBuf,
+    file: std::sync::Mutex<Option<fs::File>>,
+}
+
+#[cfg(feature = "gui")]
+impl OpenInstance {
+    /// Takes the lock and records when the instance was opened. Called once at startup. When
+    /// another process has the instance, the app still starts and [`OpenInstance::lock`] is left
+    /// to fail the migrations.
+    pub fn open(config_instance_dir: &Path) -> Result<Self, CommanderError> {
+        let instance = Self {
+            config_instance_dir: config_instance_dir.to_path_buf(),
+            file: std::sync::Mutex::new(None),
+        };
+        match instance.lock() {
+            Err(e) if e.code == ErrorCode::InstanceInUse => {
+                log::warn!("Instance is already open in another process: {}", e);
+            }
+            result => result?,
+        }
+
+        let mut metadata = InstanceMetadata::load(config_instance_dir);
+        metadata.last_opened_at_millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .ok();
+        metadata.save(config_instance_dir)?;
+        Ok(instance)
+    }
+
+    /// Takes the lock unless this process already holds it, ie, once the process that had the
+    /// instance open at startup has exited.
+    pub fn lock(&self) -> Result<(), CommanderError> {
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))?;
+        if file.is_none() {
+            *file = Some(Utils::lock_instance_dir(&self.config_instance_dir)?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "gui")]
+fn get_app_config_dir(app: &AppHandle) -> Result<PathBuf, CommanderError> {
+    Ok(app.path().app_config_dir()?)
+}
+
+/// Instance names end up in paths, compose project names and `COMMANDER_INSTANCE` (which uses
+/// `:` to separate the dev port), so only allow a conservative set of characters.
+#[cfg(feature = "gui")]
+fn validate_name(kind: &str, name: &str) -> Result<(), CommanderError> {
+    let is_valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !is_valid {
+        return Err(CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Invalid {} name '{}'. Use letters, numbers, '-' and '_'",
+                kind, name
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "gui")]
+fn get_instance_dir(
+    app: &AppHandle,
+    network_name: &str,
+    instance_name: &str,
+) -> Result<PathBuf, CommanderError> {
+    validate_name("network", network_name)?;
+    validate_name("instance", instance_name)?;
+    Ok(get_app_config_dir(app)?
+        .join(network_name)
+        .join(instance_name))
+}
+
+#[cfg(feature = "gui")]
+fn is_current(network_name: &str, instance_name: &str) -> bool {
+    network_name == Utils::get_network_name() && instance_name == Utils::get_instance_name()
+}
+
+/// Checks whether another process holds the [`OpenInstance`] lock, without waiting for it.
+#[cfg(feature = "gui")]
+fn is_open_elsewhere(config_instance_dir: &Path) -> bool {
+    let Ok(file) = fs::File::open(config_instance_dir.join(LOCK_FILE_NAME)) else {
+        return false;
+    };
+    match file.try_lock_exclusive() {
+        Ok(_) => {
+            let _ = FileExt::unlock(&file);
+            false
+        }
+        Err(_) => true,
+    }
+}
+
+#[cfg(any(feature = "gui", test))]
+fn copy_dir(from: &Path, to: &Path, skip: &[&str]) -> Result<(), CommanderError> {
+    fs::create_dir_all(to).context(format!("Creating {}", to.display()))?;
+    for entry in fs::read_dir(from).context(format!("Reading {}", from.display()))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if skip.iter().any(|skip| name == *skip) {
+            continue;
+        }
+        let target = to.join(&name);
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target, skip)?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .context(format!("Copying {}", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(any(feature = "gui", test))]
+async fn clear_server_config(absolute_db_path: &Path) -> Result<(), CommanderError> {
+    let opts = sqlx::sqlite::SqliteConnectOptions::new().filename(absolute_db_path);
+    let pool = sqlx::SqlitePool::connect_with(opts).await?;
+    let placeholders = vec!["?"; CLONE_CLEARED_CONFIG.len()].join(", ");
+    let sql = format!("DELETE FROM Config WHERE key IN ({})", placeholders);
+    let mut query = sqlx::query(&sql);
+    for key in CLONE_CLEARED_CONFIG {
+        query = query.bind(*key);
+    }
+    let result = query.execute(&pool).await;
+    pool.close().await;
+    result.context("Clearing the cloned server settings")?;
+    Ok(())
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn list_instances(app: AppHandle) -> Result<Vec<Instance>, CommanderError> {
+    let config_dir = get_app_config_dir(&app)?;
+    let mut instances = vec![];
+    if !config_dir.exists() {
+        return Ok(instances);
+    }
+    for network in fs::read_dir(&config_dir).context("Reading the config directory")? {
+        let network = network?;
+        if !network.file_type()?.is_dir() {
+            continue;
+        }
+        let network_name = network.file_name().to_string_lossy().to_string();
+        for instance in fs::read_dir(network.path())? {
+            let instance = instance?;
+            let path = instance.path();
+            // instances get their keys on first launch, or metadata when created from the app
+            let is_instance =
+                path.join("mnemonic").exists() || path.join(METADATA_FILE_NAME).exists();
+            if !instance.file_type()?.is_dir() || !is_instance {
+                continue;
+            }
+            let instance_name = instance.file_name().to_string_lossy().to_string();
+            let is_current = is_current(&network_name, &instance_name);
+            instances.push(Instance {
+                is_in_use: !is_current && is_open_elsewhere(&path),
+                is_current,
+                metadata: InstanceMetadata::load(&path),
+                network_name: network_name.clone(),
+                instance_name,
+            });
+        }
+    }
+    instances.sort_by(|a, b| {
+        (&a.network_name, &a.instance_name).cmp(&(&b.network_name, &b.instance_name))
+    });
+    Ok(instances)
+}
+
+/// Networks an instance can be created on, including custom ones from the app config dir.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub fn list_networks() -> Vec<Network> {
+    Utils::list_networks()
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn create_instance(
+    app: AppHandle,
+    network_name: String,
+    instance_name: String,
+) -> Result<(), CommanderError> {
+    log::info!("create_instance: {}/{}", network_name, instance_name);
+    let instance_dir = get_instance_dir(&app, &network_name, &instance_name)?;
+    Utils::get_network_env(&network_name).code(ErrorCode::InvalidInput)?;
+    if instance_dir.exists() {
+        return Err(CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!("Instance {}/{} already exists", network_name, instance_name),
+        ));
+    }
+    fs::create_dir_all(&instance_dir).context(format!("Creating {}", instance_dir.display()))?;
+    InstanceMetadata::default().save(&instance_dir)
+}
+
+/// Copies an instance's settings into a new instance on the same network. The mnemonic, server
+/// keys and database (whose accounts belong to that mnemonic) only come along with
+/// `include_keys`, and the copied database has its server settings cleared. The local VM is not
+/// copied.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn clone_instance(
+    app: AppHandle,
+    network_name: String,
+    source_instance_name: String,
+    target_instance_name: String,
+    include_keys: bool,
+) -> Result<(), CommanderError> {
+    log::info!(
+        "clone_instance: {}/{} -> {} (include_keys = {})",
+        network_name,
+        source_instance_name,
+        target_instance_name,
+        include_keys
+    );
+    let source_dir = get_instance_dir(&app, &network_name, &source_instance_name)?;
+    let target_dir = get_instance_dir(&app, &network_name, &target_instance_name)?;
+    if !source_dir.exists() {
+        return Err(CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Instance {}/{} does not exist",
+                network_name, source_instance_name
+            ),
+        ));
+    }
+    if target_dir.exists() {
+        return Err(CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Instance {}/{} already exists",
+                network_name, target_instance_name
+            ),
+        ));
+    }
+
+    copy_instance_dir(&source_dir, &target_dir, include_keys)
+        .await
+        .context(format!("Cloning instance {}", source_instance_name))
+}
+
+/// Does the copying for [`clone_instance`], removing the partly written `target_dir` on failure.
+/// The mining account comes from the mnemonic, so it's only kept when the keys are copied.
+#[cfg(any(feature = "gui", test))]
+async fn copy_instance_dir(
+    source_dir: &Path,
+    target_dir: &Path,
+    include_keys: bool,
+) -> Result<(), CommanderError> {
+    let result = async {
+        let mut skip = CLONE_SKIP.to_vec();
+        if !include_keys {
+            skip.extend_from_slice(KEY_FILES);
+        }
+        copy_dir(source_dir, target_dir, &skip)?;
+        let source_db = source_dir.join("database.sqlite");
+        if include_keys && source_db.exists() {
+            let target_db = target_dir.join("database.sqlite");
+            migrations::copy_instance_db(source_db, target_db.clone())
+                .await
+                .code(ErrorCode::DatabaseError)?;
+            clear_server_config(&target_db).await?;
+        }
+        let source_metadata = InstanceMetadata::load(source_dir);
+        let metadata = InstanceMetadata {
+            mining_account_address: source_metadata
+                .mining_account_address
+                .filter(|_| include_keys),
+            ..InstanceMetadata::default()
+        };
+        metadata.save(target_dir)
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_dir_all(target_dir);
+    }
+    result
+}
+
+/// Removes an instance directory, tearing down its local VM first. Refuses to delete the running
+/// instance or one that another Commander process has open.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_instance(
+    app: AppHandle,
+    network_name: String,
+    instance_name: String,
+) -> Result<(), CommanderError> {
+    log::info!("delete_instance: {}/{}", network_name, instance_name);
+    let instance_dir = get_instance_dir(&app, &network_name, &instance_name)?;
+    if !instance_dir.exists() {
+        return Ok(());
+    }
+    if is_current(&network_name, &instance_name) || is_open_elsewhere(&instance_dir) {
+        return Err(CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Instance {}/{} is open and can't be deleted",
+                network_name, instance_name
+            ),
+        ));
+    }
+    Vm::destroy(&vm::get_vm_path_in(&instance_dir))?;
+    fs::remove_dir_all(&instance_dir).context(format!("Removing {}", instance_dir.display()))?;
+    Ok(())
+}
+
+/// Starts a new Commander process for the given instance and exits this one. The dev server port
+/// suffix of `COMMANDER_INSTANCE` is kept so dev builds keep loading from the same url.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn relaunch_into_instance(
+    app: AppHandle,
+    network_name: String,
+    instance_name: String,
+) -> Result<(), CommanderError> {
+    log::info!("relaunch_into_instance: {}/{}", network_name, instance_name);
+    let instance_dir = get_instance_dir(&app, &network_name, &instance_name)?;
+    if !is_current(&network_name, &instance_name) && is_open_elsewhere(&instance_dir) {
+        return Err(CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Instance {}/{} is already open in another window",
+                network_name, instance_name
+            ),
+        ));
+    }
+
+    let mut commander_instance = instance_name;
+    if let Ok(current) = std::env::var("COMMANDER_INSTANCE") {
+        if let Some((_, port)) = current.split_once(':') {
+            commander_instance = format!("{}:{}", commander_instance, port);
+        }
+    }
+
+    let exe = tauri::utils::platform::current_exe()?;
+    std::process::Command::new(exe)
+        .args(std::env::args_os().skip(1))
+        .env("ARGON_NETWORK_NAME", network_name)
+        .env("COMMANDER_INSTANCE", commander_instance)
+        .spawn()
+        .context("Relaunching Commander")?;
+    app.exit(0);
+    Ok(())
+}
+
+/// Lets the webview keep the metadata shown in the instance list current as its config changes.
+/// Port overrides only apply to a local computer, so they're dropped when the server moves off
+/// one.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn update_instance_metadata(
+    app: AppHandle,
+    server_type: Option<String>,
+    mining_account_address: Option<String>,
+) -> Result<(), CommanderError> {
+    let instance_dir = Utils::get_absolute_config_instance_dir(&app);
+    let mut metadata = InstanceMetadata::load(&instance_dir);
+    if metadata.server_type.as_deref() == Some(LOCAL_COMPUTER_SERVER_TYPE)
+        && server_type.as_deref() != Some(LOCAL_COMPUTER_SERVER_TYPE)
+    {
+        metadata.port_overrides.clear();
+    }
+    metadata.server_type = server_type;
+    metadata.mining_account_address = mining_account_address;
+    metadata.save(&instance_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestDb, block_on};
+
+    /// An instance with keys, a database with a server configured, a VM and metadata for every
+    /// field.
+    async fn source_instance() -> TestDb {
+        let db = TestDb::migrated().await;
+        for (key, value) in [
+            ("serverDetails", "{}"),
+            ("isMinerInstalled", "true"),
+            ("theme", "dark"),
+        ] {
+            sqlx::query("INSERT INTO Config (key, value) VALUES (?, ?)")
+                .bind(key)
+                .bind(value)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        let dir = db.dir.path();
+        for file in KEY_FILES.iter().chain(&[LOCK_FILE_NAME]) {
+            fs::write(dir.join(file), file).unwrap();
+        }
+        fs::create_dir_all(dir.join("virtual-machine")).unwrap();
+        fs::write(dir.join("virtual-machine").join(".env"), "").unwrap();
+        fs::create_dir_all(dir.join("custom").join("nested")).unwrap();
+        fs::write(
+            dir.join("custom").join("nested").join("settings.json"),
+            "{}",
+        )
+        .unwrap();
+        InstanceMetadata {
+            last_opened_at_millis: Some(1),
+            server_type: Some("LocalComputer".to_string()),
+            mining_account_address: Some("5Fminer".to_string()),
+            port_overrides: BTreeMap::from([("BITCOIN_P2P_PORT".to_string(), 18444)]),
+        }
+        .save(dir)
+        .unwrap();
+        db
+    }
+
+    async fn config_keys(db_path: &Path) -> Vec<String> {
+        let pool = TestDb::connect(db_path).await;
+        let keys = sqlx::query_scalar("SELECT key FROM Config ORDER BY key")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        keys
+    }
+
+    #[test]
+    fn clones_settings_without_keys_database_or_vm() {
+        block_on(async {
+            let source = source_instance().await;
+            let target = tempfile::tempdir().unwrap();
+            let target_dir = target.path().join("clone");
+
+            copy_instance_dir(source.dir.path(), &target_dir, false)
+                .await
+                .unwrap();
+
+            let settings = target_dir
+                .join("custom")
+                .join("nested")
+                .join("settings.json");
+            assert!(settings.exists());
+            for skipped in KEY_FILES.iter().chain(CLONE_SKIP) {
+                if *skipped != METADATA_FILE_NAME {
+                    assert!(!target_dir.join(skipped).exists(), "{} was copied", skipped);
+                }
+            }
+            let metadata = InstanceMetadata::load(&target_dir);
+            assert_eq!(metadata.last_opened_at_millis, None);
+            assert_eq!(metadata.server_type, None);
+            assert_eq!(metadata.mining_account_address, None);
+            assert!(metadata.port_overrides.is_empty());
+        });
+    }
+
+    #[test]
+    fn clones_keys_and_database_with_the_server_settings_cleared() {
+        block_on(async {
+            let source = source_instance().await;
+            let target = tempfile::tempdir().unwrap();
+            let target_dir = target.path().join("clone");
+
+            copy_instance_dir(source.dir.path(), &target_dir, true)
+                .await
+                .unwrap();
+
+            for file in KEY_FILES {
+                assert_eq!(fs::read_to_string(target_dir.join(file)).unwrap(), *file);
+            }
+            assert!(!target_dir.join(LOCK_FILE_NAME).exists());
+            assert!(!target_dir.join("virtual-machine").exists());
+            assert_eq!(
+                config_keys(&target_dir.join("database.sqlite")).await,
+                ["theme"]
+            );
+            assert_eq!(config_keys(&source.path()).await.len(), 3);
+
+            let metadata = InstanceMetadata::load(&target_dir);
+            assert_eq!(metadata.mining_account_address.as_deref(), Some("5Fminer"));
+            assert_eq!(metadata.server_type, None);
+            assert!(metadata.port_overrides.is_empty());
+        });
+    }
+
+    #[test]
+    fn removes_a_partial_clone_on_failure() {
+        block_on(async {
+            let source = tempfile::tempdir().unwrap();
+            fs::write(source.path().join("mnemonic"), "mnemonic").unwrap();
+            fs::write(source.path().join("database.sqlite"), "not a database").unwrap();
+            let target = tempfile::tempdir().unwrap();
+            let target_dir = target.path().join("clone");
+
+            let error = copy_instance_dir(source.path(), &target_dir, true)
+                .await
+                .unwrap_err();
+            assert_eq!(error.code, ErrorCode::DatabaseError);
+            assert!(!target_dir.exists());
+        });
+    }
+}
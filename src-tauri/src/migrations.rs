@@ -1,15 +1,39 @@
 use include_dir::{Dir, include_dir};
+use sqlx::SqlitePool;
 use sqlx::{
     error::BoxDynError,
-    migrate::{Migration as SqlxMigration, MigrationSource, Migrator},
+    migrate::{Migrate, Migration as SqlxMigration, MigrationSource, MigrationType, Migrator},
 };
+use std::collections::HashMap;
+use std::fs;
 use std::future::Future;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use tauri_plugin_sql::{Migration, MigrationKind};
+#[cfg(feature = "gui")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, Manager};
+#[cfg(feature = "gui")]
+use tauri_specta::Event;
+
+use crate::error::{CommanderError, ErrorCode, ResultExt};
+#[cfg(feature = "gui")]
+use crate::instances::OpenInstance;
+use crate::utils::Utils;
 
 static MIGRATIONS_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/migrations");
 
+const BACKUP_DIR_NAME: &str = "database-backups";
+const BACKUP_KEEP_COUNT: usize = 5;
+
+#[derive(Debug)]
+struct Migration {
+    version: i64,
+    description: &'static str,
+    sql: &'static str,
+}
+
 #[derive(Debug)]
 struct MigrationList(Vec<Migration>);
 
@@ -24,24 +48,24 @@ impl MigrationSource<'static> for MigrationList {
         >,
     > {
         Box::pin(async move {
-            let mut migrations = Vec::new();
-            for migration in self.0 {
-                if matches!(migration.kind, MigrationKind::Up) {
-                    migrations.push(SqlxMigration::new(
+            Ok(self
+                .0
+                .into_iter()
+                .map(|migration| {
+                    SqlxMigration::new(
                         migration.version,
                         migration.description.into(),
-                        migration.kind.into(),
+                        MigrationType::ReversibleUp,
                         migration.sql.into(),
                         false,
-                    ));
-                }
-            }
-            Ok(migrations)
+                    )
+                })
+                .collect())
         })
     }
 }
 
-pub fn get_migrations() -> Vec<Migration> {
+fn get_migrations() -> Vec<Migration> {
     let mut out: Vec<Migration> = MIGRATIONS_DIR
         .dirs()
         .filter_map(|dir| {
@@ -58,7 +82,6 @@ pub fn get_migrations() -> Vec<Migration> {
                 version,
                 description,
                 sql,
-                kind: MigrationKind::Up,
             })
         })
         .collect();
@@ -66,24 +89,812 @@ pub fn get_migrations() -> Vec<Migration> {
     out
 }
 
-pub async fn run_db_migrations(absolute_db_path: PathBuf) -> Result<(), String> {
+#[derive(serde::Serialize, specta::Type, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the checksum stored in `_sqlx_migrations` differs from the embedded sql
+    ChecksumMismatch,
+    /// Recorded as applied, but no longer part of the embedded migrations
+    Unknown,
+    /// Recorded in `_sqlx_migrations` with success = false
+    Failed,
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+    pub checksum: String,
+    pub applied_checksum: Option<String>,
+    pub applied_at: Option<String>,
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationDryRun {
+    pub version: i64,
+    pub description: String,
+    pub error: Option<String>,
+}
+
+async fn connect(absolute_db_path: &Path, create_if_missing: bool) -> Result<SqlitePool, String> {
     let opts = sqlx::sqlite::SqliteConnectOptions::new()
-        .filename(&absolute_db_path)
-        .create_if_missing(true);
+        .filename(absolute_db_path)
+        .create_if_missing(create_if_missing);
 
-    let pool = sqlx::SqlitePool::connect_with(opts)
+    SqlitePool::connect_with(opts)
         .await
-        .map_err(|e| format!("Failed to connect to database: {}", e))?;
+        .map_err(|e| format!("Failed to connect to database: {}", e))
+}
 
+async fn get_migrator() -> Result<Migrator, String> {
     let migrations = MigrationList(get_migrations());
-    let migrator = Migrator::new(migrations)
+    Migrator::new(migrations)
+        .await
+        .map_err(|e| format!("Failed to create migrator: {}", e))
+}
+
+async fn get_applied_migrations(
+    pool: &SqlitePool,
+) -> Result<Vec<(i64, String, Vec<u8>, String, bool)>, String> {
+    let has_table: Option<(String,)> = sqlx::query_as(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to read migrations table: {}", e))?;
+    if has_table.is_none() {
+        return Ok(vec![]);
+    }
+
+    sqlx::query_as(
+        "SELECT version, description, checksum, CAST(installed_on AS TEXT), success FROM _sqlx_migrations ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to read applied migrations: {}", e))
+}
+
+pub async fn get_db_migration_status(
+    absolute_db_path: PathBuf,
+) -> Result<Vec<MigrationStatus>, String> {
+    let migrator = get_migrator().await?;
+    let mut applied = HashMap::new();
+    if absolute_db_path.exists() {
+        let pool = connect(&absolute_db_path, false).await?;
+        let rows = get_applied_migrations(&pool).await;
+        pool.close().await;
+        for row in rows? {
+            applied.insert(row.0, row);
+        }
+    }
+
+    let mut out = Vec::new();
+    for migration in migrator.iter() {
+        let checksum = hex::encode(&migration.checksum);
+        let mut status = MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            state: MigrationState::Pending,
+            checksum,
+            applied_checksum: None,
+            applied_at: None,
+        };
+        if let Some((_, _, applied_checksum, applied_at, success)) =
+            applied.remove(&migration.version)
+        {
+            status.state = if !success {
+                MigrationState::Failed
+            } else if applied_checksum.as_slice() != migration.checksum.as_ref() {
+                MigrationState::ChecksumMismatch
+            } else {
+                MigrationState::Applied
+            };
+            status.applied_checksum = Some(hex::encode(applied_checksum));
+            status.applied_at = Some(applied_at);
+        }
+        out.push(status);
+    }
+
+    for (version, description, applied_checksum, applied_at, success) in applied.into_values() {
+        out.push(MigrationStatus {
+            version,
+            description,
+            state: if success {
+                MigrationState::Unknown
+            } else {
+                MigrationState::Failed
+            },
+            checksum: String::new(),
+            applied_checksum: Some(hex::encode(applied_checksum)),
+            applied_at: Some(applied_at),
+        });
+    }
+    out.sort_by_key(|m| m.version);
+    Ok(out)
+}
+
+/// Runs every pending migration inside a single transaction that is always rolled back, so the
+/// outcome of an upgrade can be reported without touching the database.
+pub async fn dry_run_db_migrations(
+    absolute_db_path: PathBuf,
+) -> Result<Vec<MigrationDryRun>, String> {
+    let pending = get_db_migration_status(absolute_db_path.clone())
+        .await?
+        .into_iter()
+        .filter(|m| m.state == MigrationState::Pending)
+        .map(|m| m.version)
+        .collect::<Vec<_>>();
+    if pending.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let migrator = get_migrator().await?;
+    let pool = if absolute_db_path.exists() {
+        connect(&absolute_db_path, false).await?
+    } else {
+        // nothing on disk yet, so validate against a throwaway in-memory database
+        SqlitePool::connect("sqlite::memory:")
+            .await
+            .map_err(|e| format!("Failed to connect to database: {}", e))?
+    };
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    let mut out = Vec::new();
+    for migration in migrator.iter().filter(|m| pending.contains(&m.version)) {
+        let result = sqlx::raw_sql(&migration.sql).execute(&mut *tx).await;
+        let error = result.err().map(|e| e.to_string());
+        let failed = error.is_some();
+        out.push(MigrationDryRun {
+            version: migration.version,
+            description: migration.description.to_string(),
+            error,
+        });
+        if failed {
+            break;
+        }
+    }
+
+    tx.rollback()
+        .await
+        .map_err(|e| format!("Failed to roll back dry run: {}", e))?;
+    pool.close().await;
+    Ok(out)
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum MigrationPhase {
+    Checking,
+    BackingUp,
+    Applying,
+    RestoringBackup,
+    Succeeded,
+    Failed,
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[cfg_attr(feature = "gui", derive(tauri_specta::Event))]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationProgress {
+    pub phase: MigrationPhase,
+    pub version: Option<i64>,
+    pub description: Option<String>,
+    pub completed: usize,
+    pub total: usize,
+    pub error: Option<String>,
+}
+
+impl MigrationProgress {
+    fn new(phase: MigrationPhase) -> Self {
+        Self {
+            phase,
+            version: None,
+            description: None,
+            completed: 0,
+            total: 0,
+            error: None,
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone)]
+enum MigrationRunStatus {
+    Running,
+    Succeeded,
+    Failed(CommanderError),
+}
+
+/// Tracks the migration run for this instance so the webview only opens its database handle
+/// once the Rust side has finished migrating.
+#[cfg(feature = "gui")]
+pub struct DbMigrations {
+    status: tokio::sync::watch::Sender<MigrationRunStatus>,
+    /// Set once the webview has been told it can open its own handle to the database
+    is_opened: AtomicBool,
+}
+
+#[cfg(feature = "gui")]
+impl Default for DbMigrations {
+    fn default() -> Self {
+        let (status, _) = tokio::sync::watch::channel(MigrationRunStatus::Running);
+        Self {
+            status,
+            is_opened: AtomicBool::new(false),
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+impl DbMigrations {
+    /// Runs the migrations for this instance in the background, emitting
+    /// [`MigrationProgress`] events as it goes.
+    pub fn start(app: &AppHandle) {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let _ = migrate_instance_db(&app).await;
+        });
+    }
+
+    /// Records that the webview is about to open the database, after which a failed migration
+    /// can no longer restore the file underneath it.
+    pub fn mark_opened(&self) {
+        self.is_opened.store(true, Ordering::SeqCst);
+    }
+
+    pub async fn wait(&self) -> Result<(), CommanderError> {
+        let mut rx = self.status.subscribe();
+        let status = rx
+            .wait_for(|s| !matches!(s, MigrationRunStatus::Running))
+            .await
+            .map_err(|e| CommanderError::new(ErrorCode::MigrationFailed, e))?
+            .clone();
+        match status {
+            MigrationRunStatus::Failed(e) => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The single entrypoint for migrating the app's instance database. Only runs while this process
+/// holds the [`OpenInstance`] lock, so a second Commander process on the same instance can't
+/// migrate concurrently.
+#[cfg(feature = "gui")]
+pub async fn migrate_instance_db(app: &AppHandle) -> Result<(), CommanderError> {
+    let state = app.state::<DbMigrations>();
+    state.status.send_replace(MigrationRunStatus::Running);
+
+    let emit = |progress: MigrationProgress| {
+        if let Err(e) = progress.emit(app) {
+            log::warn!("Unable to emit migration progress: {}", e);
+        }
+    };
+
+    let config_instance_dir = Utils::get_absolute_config_instance_dir(app);
+    let result = match app.state::<OpenInstance>().lock() {
+        Ok(_) => {
+            let can_restore = !state.is_opened.load(Ordering::SeqCst);
+            let absolute_db_path = config_instance_dir.join("database.sqlite");
+            run_db_migrations(absolute_db_path, can_restore, emit)
+                .await
+                .code(ErrorCode::MigrationFailed)
+        }
+        Err(e) => Err(e),
+    };
+
+    report_outcome(&result, emit);
+    state.status.send_replace(match &result {
+        Ok(_) => MigrationRunStatus::Succeeded,
+        Err(e) => MigrationRunStatus::Failed(e.clone()),
+    });
+    result
+}
+
+/// Migrates the database in `config_instance_dir` while holding the instance lock. Used directly by
+/// the cli, which has no app to track state on, and fails if the app has the instance open.
+pub async fn migrate_instance_dir(
+    config_instance_dir: PathBuf,
+    on_progress: impl Fn(MigrationProgress),
+) -> Result<(), CommanderError> {
+    let _instance_lock = Utils::lock_instance_dir(&config_instance_dir)?;
+    let result = run_db_migrations(
+        config_instance_dir.join("database.sqlite"),
+        true,
+        &on_progress,
+    )
+    .await
+    .code(ErrorCode::MigrationFailed);
+    report_outcome(&result, on_progress);
+    result
+}
+
+/// Emits the final [`MigrationPhase::Succeeded`] or [`MigrationPhase::Failed`] progress for a run.
+fn report_outcome(result: &Result<(), CommanderError>, on_progress: impl Fn(MigrationProgress)) {
+    match result {
+        Ok(_) => on_progress(MigrationProgress::new(MigrationPhase::Succeeded)),
+        Err(e) => {
+            log::error!("Database migrations failed: {}", e);
+            let mut progress = MigrationProgress::new(MigrationPhase::Failed);
+            progress.error = Some(e.to_string());
+            on_progress(progress);
+        }
+    }
+}
+
+/// Applies the pending migrations. When one fails, the pre-migration backup is copied back only if
+/// `can_restore`, since nothing else may have the file open while it's replaced.
+pub async fn run_db_migrations(
+    absolute_db_path: PathBuf,
+    can_restore: bool,
+    on_progress: impl Fn(MigrationProgress),
+) -> Result<(), String> {
+    on_progress(MigrationProgress::new(MigrationPhase::Checking));
+    let statuses = get_db_migration_status(absolute_db_path.clone()).await?;
+    if let Some(bad) = statuses.iter().find(|m| {
+        matches!(
+            m.state,
+            MigrationState::ChecksumMismatch | MigrationState::Failed
+        )
+    }) {
+        return Err(format!(
+            "Migration {} ({}) is {:?}",
+            bad.version, bad.description, bad.state
+        ));
+    }
+    let pending = statuses
+        .iter()
+        .filter(|m| m.state == MigrationState::Pending)
+        .map(|m| m.version)
+        .collect::<Vec<_>>();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    // a brand-new database has nothing worth snapshotting
+    let needs_backup = absolute_db_path.exists();
+    let pool = connect(&absolute_db_path, true).await?;
+    let backup_path = if needs_backup {
+        on_progress(MigrationProgress::new(MigrationPhase::BackingUp));
+        Some(backup_db(&pool, &absolute_db_path).await?)
+    } else {
+        None
+    };
+
+    let migrator = get_migrator().await?;
+    let result = apply_migrations(&pool, &migrator, &pending, &on_progress).await;
+    pool.close().await;
+
+    if let Err(e) = result {
+        match backup_path {
+            Some(backup_path) if can_restore => {
+                log::error!("{}. Restoring database from {}", e, backup_path.display());
+                on_progress(MigrationProgress::new(MigrationPhase::RestoringBackup));
+                restore_db(&backup_path, &absolute_db_path)?;
+            }
+            Some(backup_path) => {
+                log::error!(
+                    "{}. The database is open, so it was left as is. Backup: {}",
+                    e,
+                    backup_path.display()
+                );
+            }
+            None => {}
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+async fn apply_migrations(
+    pool: &SqlitePool,
+    migrator: &Migrator,
+    pending: &[i64],
+    on_progress: &impl Fn(MigrationProgress),
+) -> Result<(), String> {
+    let mut conn = pool
+        .acquire()
         .await
-        .map_err(|e| format!("Failed to create migrator: {}", e))?;
-    migrator
-        .run(&pool)
+        .map_err(|e| format!("Failed to connect to database: {}", e))?;
+    conn.lock()
+        .await
+        .map_err(|e| format!("Failed to lock database: {}", e))?;
+    conn.ensure_migrations_table()
+        .await
+        .map_err(|e| format!("Failed to create migrations table: {}", e))?;
+
+    let total = pending.len();
+    let to_apply = migrator.iter().filter(|m| pending.contains(&m.version));
+    for (completed, migration) in to_apply.enumerate() {
+        on_progress(MigrationProgress {
+            phase: MigrationPhase::Applying,
+            version: Some(migration.version),
+            description: Some(migration.description.to_string()),
+            completed,
+            total,
+            error: None,
+        });
+        log::info!(
+            "Applying migration {} ({})",
+            migration.version,
+            migration.description
+        );
+        conn.apply(migration)
+            .await
+            .map_err(|e| format!("Failed to run migrations: {}", e))?;
+    }
+
+    conn.unlock()
         .await
-        .map_err(|e| format!("Failed to run migrations: {}", e))?;
+        .map_err(|e| format!("Failed to unlock database: {}", e))?;
+    Ok(())
+}
+
+fn get_backup_dir(absolute_db_path: &Path) -> PathBuf {
+    absolute_db_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(BACKUP_DIR_NAME)
+}
 
+/// Takes an on-demand snapshot into the same backup directory used before migrations.
+pub async fn backup_instance_db(absolute_db_path: PathBuf) -> Result<PathBuf, String> {
+    if !absolute_db_path.exists() {
+        return Err(format!(
+            "No database exists at {}",
+            absolute_db_path.display()
+        ));
+    }
+    let pool = connect(&absolute_db_path, false).await?;
+    let result = backup_db(&pool, &absolute_db_path).await;
+    pool.close().await;
+    result
+}
+
+/// Writes a consistent copy of the database to `destination`, ie, when cloning an instance.
+#[cfg(any(feature = "gui", test))]
+pub async fn copy_instance_db(
+    absolute_db_path: PathBuf,
+    destination: PathBuf,
+) -> Result<(), String> {
+    let pool = connect(&absolute_db_path, false).await?;
+    let result = sqlx::query("VACUUM INTO ?")
+        .bind(destination.to_string_lossy().to_string())
+        .execute(&pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("Failed to copy database: {}", e));
     pool.close().await;
+    result
+}
+
+/// Snapshots the database with `VACUUM INTO` (which is consistent even with an open WAL) and
+/// prunes all but the newest [`BACKUP_KEEP_COUNT`] snapshots.
+async fn backup_db(pool: &SqlitePool, absolute_db_path: &Path) -> Result<PathBuf, String> {
+    let backup_dir = get_backup_dir(absolute_db_path);
+    fs::create_dir_all(&backup_dir).map_err(|e| {
+        format!(
+            "Error creating backup directory {}: {}",
+            backup_dir.display(),
+            e
+        )
+    })?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let backup_path = backup_dir.join(format!("database-{:013}.sqlite", timestamp));
+    log::info!("Backing up database to {}", backup_path.display());
+    sqlx::query("VACUUM INTO ?")
+        .bind(backup_path.to_string_lossy().to_string())
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to back up database: {}", e))?;
+
+    let mut backups = list_db_backups(absolute_db_path);
+    if backups.len() > BACKUP_KEEP_COUNT {
+        let excess = backups.len() - BACKUP_KEEP_COUNT;
+        for old in backups.drain(..excess) {
+            if let Err(e) = fs::remove_file(&old) {
+                log::warn!("Unable to remove old backup {}: {}", old.display(), e);
+            }
+        }
+    }
+    Ok(backup_path)
+}
+
+/// Lists the database snapshots for this instance, oldest first.
+fn list_db_backups(absolute_db_path: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(get_backup_dir(absolute_db_path)) else {
+        return vec![];
+    };
+    let mut backups = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("database-") && n.ends_with(".sqlite"))
+        })
+        .collect::<Vec<_>>();
+    backups.sort();
+    backups
+}
+
+fn restore_db(backup_path: &Path, absolute_db_path: &Path) -> Result<(), String> {
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = absolute_db_path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        let _ = fs::remove_file(PathBuf::from(sidecar));
+    }
+    fs::copy(backup_path, absolute_db_path).map_err(|e| {
+        format!(
+            "Failed to restore database from {}: {}",
+            backup_path.display(),
+            e
+        )
+    })?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestDb, block_on};
+
+    async fn table_names(pool: &SqlitePool) -> Vec<String> {
+        sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    fn states(statuses: &[MigrationStatus]) -> Vec<(i64, MigrationState)> {
+        statuses
+            .iter()
+            .map(|x| (x.version, x.state.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn reports_every_migration_as_pending_without_a_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("database.sqlite");
+        let statuses = block_on(get_db_migration_status(db_path.clone())).unwrap();
+        assert_eq!(statuses.len(), get_migrations().len());
+        assert!(statuses.iter().all(|x| x.state == MigrationState::Pending));
+        assert!(!db_path.exists());
+    }
+
+    #[test]
+    fn flags_changed_and_unknown_migrations() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            let statuses = get_db_migration_status(db.path()).await.unwrap();
+            assert!(statuses.iter().all(|x| x.state == MigrationState::Applied));
+
+            sqlx::query("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = 1")
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            sqlx::query(
+                "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+                VALUES (99, 'removed', 1, x'00', 0), (98, 'broken', 0, x'00', 0)",
+            )
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+            let statuses = get_db_migration_status(db.path()).await.unwrap();
+            assert_eq!(statuses[0].state, MigrationState::ChecksumMismatch);
+            assert_eq!(statuses[0].applied_checksum.as_deref(), Some("00"));
+            assert_eq!(
+                states(&statuses[statuses.len() - 2..]),
+                [(98, MigrationState::Failed), (99, MigrationState::Unknown)]
+            );
+        });
+    }
+
+    #[test]
+    fn dry_run_leaves_the_database_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("database.sqlite");
+        block_on(async {
+            connect(&db_path, true).await.unwrap().close().await;
+
+            let results = dry_run_db_migrations(db_path.clone()).await.unwrap();
+            assert_eq!(results.len(), get_migrations().len());
+            assert!(results.iter().all(|x| x.error.is_none()));
+
+            let pool = connect(&db_path, false).await.unwrap();
+            assert!(table_names(&pool).await.is_empty());
+            pool.close().await;
+            let statuses = get_db_migration_status(db_path.clone()).await.unwrap();
+            assert!(statuses.iter().all(|x| x.state == MigrationState::Pending));
+        });
+    }
+
+    #[test]
+    fn dry_run_stops_at_the_first_failing_migration() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("database.sqlite");
+        block_on(async {
+            let pool = connect(&db_path, true).await.unwrap();
+            // migration 1 creates this table, so it fails when the table already exists
+            sqlx::query("CREATE TABLE Config (key TEXT)")
+                .execute(&pool)
+                .await
+                .unwrap();
+            pool.close().await;
+
+            let results = dry_run_db_migrations(db_path.clone()).await.unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].version, 1);
+            assert!(results[0].error.is_some());
+
+            let pool = connect(&db_path, false).await.unwrap();
+            assert_eq!(table_names(&pool).await, ["Config"]);
+            pool.close().await;
+        });
+    }
+
+    #[test]
+    fn dry_run_has_nothing_to_do_once_migrated() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            assert!(dry_run_db_migrations(db.path()).await.unwrap().is_empty());
+        });
+    }
+
+    /// Marks the last migration as never applied while leaving its tables in place, so applying it
+    /// again fails.
+    async fn break_last_migration(db: &TestDb) {
+        let version = get_migrations().last().unwrap().version;
+        sqlx::query("DELETE FROM _sqlx_migrations WHERE version = ?")
+            .bind(version)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO Config (key, value) VALUES ('marker', '1')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        db.pool.close().await;
+    }
+
+    fn run_collecting_phases(db: &TestDb, can_restore: bool) -> Vec<MigrationPhase> {
+        let phases = std::sync::Mutex::new(vec![]);
+        let result = block_on(run_db_migrations(db.path(), can_restore, |x| {
+            phases.lock().unwrap().push(x.phase)
+        }));
+        assert!(result.is_err());
+        phases.into_inner().unwrap()
+    }
+
+    #[test]
+    fn keeps_only_the_newest_backups() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            let mut created = vec![];
+            for _ in 0..BACKUP_KEEP_COUNT + 2 {
+                created.push(backup_instance_db(db.path()).await.unwrap());
+                // backups are named by the millisecond they're taken in
+                std::thread::sleep(std::time::Duration::from_millis(2));
+            }
+            assert_eq!(list_db_backups(&db.path()), created[2..]);
+            assert!(!created[0].exists());
+        });
+    }
+
+    #[test]
+    fn backs_up_existing_databases_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("database.sqlite");
+        block_on(run_db_migrations(db_path.clone(), true, |_| {})).unwrap();
+        assert!(list_db_backups(&db_path).is_empty());
+
+        block_on(async {
+            let db = TestDb::migrated().await;
+            break_last_migration(&db).await;
+            let _ = run_db_migrations(db.path(), true, |_| {}).await;
+            assert_eq!(list_db_backups(&db.path()).len(), 1);
+        });
+    }
+
+    #[test]
+    fn restores_the_backup_when_a_migration_fails() {
+        let db = block_on(async {
+            let db = TestDb::migrated().await;
+            break_last_migration(&db).await;
+            db
+        });
+        let phases = run_collecting_phases(&db, true);
+        assert_eq!(
+            phases,
+            [
+                MigrationPhase::Checking,
+                MigrationPhase::BackingUp,
+                MigrationPhase::Applying,
+                MigrationPhase::RestoringBackup
+            ]
+        );
+
+        block_on(async {
+            let pool = TestDb::connect(&db.path()).await;
+            let marker: Option<String> =
+                sqlx::query_scalar("SELECT value FROM Config WHERE key = 'marker'")
+                    .fetch_optional(&pool)
+                    .await
+                    .unwrap();
+            assert_eq!(marker.as_deref(), Some("1"));
+            pool.close().await;
+        });
+    }
+
+    #[test]
+    fn leaves_an_open_database_in_place_when_a_migration_fails() {
+        let db = block_on(async {
+            let db = TestDb::migrated().await;
+            break_last_migration(&db).await;
+            db
+        });
+        let phases = run_collecting_phases(&db, false);
+        assert!(!phases.contains(&MigrationPhase::RestoringBackup));
+        assert_eq!(list_db_backups(&db.path()).len(), 1);
+    }
+
+    #[test]
+    fn migrates_an_instance_dir_only_while_no_one_else_holds_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let instance_lock = Utils::lock_instance_dir(dir.path()).unwrap();
+        let result = block_on(migrate_instance_dir(dir.path().to_path_buf(), |_| {}));
+        assert_eq!(result.unwrap_err().code, ErrorCode::InstanceInUse);
+        assert!(!dir.path().join("database.sqlite").exists());
+
+        drop(instance_lock);
+        block_on(migrate_instance_dir(dir.path().to_path_buf(), |_| {})).unwrap();
+        let statuses = block_on(get_db_migration_status(dir.path().join("database.sqlite")));
+        assert!(
+            statuses
+                .unwrap()
+                .iter()
+                .all(|x| x.state == MigrationState::Applied)
+        );
+    }
+
+    #[test]
+    fn skips_up_to_date_databases() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            let phases = std::sync::Mutex::new(vec![]);
+            run_db_migrations(db.path(), true, |x| phases.lock().unwrap().push(x.phase))
+                .await
+                .unwrap();
+            assert_eq!(phases.into_inner().unwrap(), [MigrationPhase::Checking]);
+            assert!(list_db_backups(&db.path()).is_empty());
+        });
+    }
+
+    #[test]
+    fn refuses_to_migrate_past_a_changed_migration() {
+        block_on(async {
+            let db = TestDb::migrated().await;
+            sqlx::query("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = 1")
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            let error = run_db_migrations(db.path(), true, |_| {})
+                .await
+                .unwrap_err();
+            assert!(error.contains("ChecksumMismatch"), "{}", error);
+        });
+    }
+}
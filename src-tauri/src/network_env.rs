@@ -0,0 +1,370 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::io::Cursor;
+use std::str::FromStr;
+use url::Url;
+
+/// A version as written in the env files: `dev` for locally built images, otherwise dot separated
+/// numbers with an optional leading `v` (`28.1`, `v1.3.8`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version(String);
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let numbers = value.strip_prefix('v').unwrap_or(value);
+        let is_valid = value == "dev"
+            || (!numbers.is_empty()
+                && numbers
+                    .split('.')
+                    .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())));
+        if !is_valid {
+            return Err(format!("'{}' is not a version", value));
+        }
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl serde::Serialize for Version {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+/// A url from the env files. Validated on parse, but kept as written so it serializes back without
+/// the normalization (ie, a trailing slash) `Url` would add.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvUrl(String);
+
+impl FromStr for EnvUrl {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Url::parse(value).map_err(|e| format!("'{}' is not a url: {}", value, e))?;
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl Display for EnvUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl serde::Serialize for EnvUrl {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BitcoinChain {
+    Mainnet,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl FromStr for BitcoinChain {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "mainnet" => Ok(Self::Mainnet),
+            "testnet" => Ok(Self::Testnet),
+            "signet" => Ok(Self::Signet),
+            "regtest" => Ok(Self::Regtest),
+            _ => Err(format!("'{}' is not a bitcoin chain", value)),
+        }
+    }
+}
+
+/// The settings in a network's `.env.<network>` file. Serialized with the env key names, since
+/// that's how the webview and the server scripts refer to them.
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct NetworkEnv {
+    pub data_folder: String,
+    pub config_folder: String,
+    pub bitcoin_config: String,
+    pub bitcoin_chain: BitcoinChain,
+    #[specta(type = String)]
+    pub bitcoin_version: Version,
+    pub bitcoin_p2p_port: u16,
+    pub bitcoin_data_slug: String,
+    pub bitcoin_tar_paths: String,
+    #[specta(type = String)]
+    pub argon_version: Version,
+    pub argon_chain: String,
+    #[specta(type = String)]
+    pub argon_archive_node: EnvUrl,
+    /// Comma separated in the env file
+    #[specta(type = Vec<String>)]
+    pub notebook_archive_hosts: Vec<EnvUrl>,
+    pub argon_p2p_port: u16,
+    pub argon_rpc_port: u16,
+    pub argon_bootnodes: Option<String>,
+    pub bot_port: u16,
+    pub status_port: u16,
+    /// Keys Commander doesn't read itself (ie, ROOT or ARGON_DATA_FOLDER), passed through as is
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, String>,
+}
+
+/// Pulls typed values out of the parsed env file, collecting every problem instead of stopping at
+/// the first one.
+struct EnvReader {
+    vars: HashMap<String, String>,
+    read_keys: HashSet<String>,
+    errors: Vec<String>,
+}
+
+impl EnvReader {
+    fn optional<T: FromStr>(&mut self, key: &str) -> Option<T>
+    where
+        T::Err: Display,
+    {
+        self.read_keys.insert(key.to_string());
+        let value = self.vars.get(key).filter(|value| !value.is_empty())?;
+        match value.parse::<T>() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.errors.push(format!("{}: {}", key, e));
+                None
+            }
+        }
+    }
+
+    fn required<T: FromStr>(&mut self, key: &str) -> Option<T>
+    where
+        T::Err: Display,
+    {
+        self.read_keys.insert(key.to_string());
+        if self.vars.get(key).is_none_or(|value| value.is_empty()) {
+            self.errors.push(format!("{} is missing", key));
+            return None;
+        }
+        self.optional(key)
+    }
+
+    fn required_list<T: FromStr>(&mut self, key: &str) -> Option<Vec<T>>
+    where
+        T::Err: Display,
+    {
+        let value: String = self.required(key)?;
+        let mut items = vec![];
+        for item in value.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            match item.parse::<T>() {
+                Ok(item) => items.push(item),
+                Err(e) => self.errors.push(format!("{}: {}", key, e)),
+            }
+        }
+        Some(items)
+    }
+
+    /// Every variable that hasn't been read by one of the typed getters.
+    fn unread(&self) -> BTreeMap<String, String> {
+        self.vars
+            .iter()
+            .filter(|(key, _)| !self.read_keys.contains(*key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+}
+
+impl NetworkEnv {
+    /// Parses an env file, reporting malformed lines, missing keys and bad values together.
+    pub fn parse(env_text: &str) -> Result<Self, String> {
+        let mut reader = EnvReader {
+            vars: HashMap::new(),
+            read_keys: HashSet::new(),
+            errors: vec![],
+        };
+        for item in dotenvy::from_read_iter(Cursor::new(env_text)) {
+            match item {
+                Ok((key, value)) => {
+                    reader.vars.insert(key, value);
+                }
+                Err(e) => reader.errors.push(e.to_string()),
+            }
+        }
+
+        let env = Self::read(&mut reader);
+        match env {
+            Some(env) if reader.errors.is_empty() => Ok(env),
+            _ => Err(reader.errors.join("; ")),
+        }
+    }
+
+    fn read(reader: &mut EnvReader) -> Option<Self> {
+        // read every key before bailing so all the errors get collected
+        let data_folder = reader.required("DATA_FOLDER");
+        let config_folder = reader.required("CONFIG_FOLDER");
+        let bitcoin_config = reader.required("BITCOIN_CONFIG");
+        let bitcoin_chain = reader.required("BITCOIN_CHAIN");
+        let bitcoin_version = reader.required("BITCOIN_VERSION");
+        let bitcoin_p2p_port = reader.required("BITCOIN_P2P_PORT");
+        let bitcoin_data_slug = reader.required("BITCOIN_DATA_SLUG");
+        let bitcoin_tar_paths = reader.required("BITCOIN_TAR_PATHS");
+        let argon_version = reader.required("ARGON_VERSION");
+        let argon_chain = reader.required("ARGON_CHAIN");
+        let argon_archive_node = reader.required("ARGON_ARCHIVE_NODE");
+        let notebook_archive_hosts = reader.required_list("NOTEBOOK_ARCHIVE_HOSTS");
+        let argon_p2p_port = reader.required("ARGON_P2P_PORT");
+        let argon_rpc_port = reader.required("ARGON_RPC_PORT");
+        let argon_bootnodes = reader.optional("ARGON_BOOTNODES");
+        let bot_port = reader.required("BOT_PORT");
+        let status_port = reader.required("STATUS_PORT");
+
+        Some(Self {
+            data_folder: data_folder?,
+            config_folder: config_folder?,
+            bitcoin_config: bitcoin_config?,
+            bitcoin_chain: bitcoin_chain?,
+            bitcoin_version: bitcoin_version?,
+            bitcoin_p2p_port: bitcoin_p2p_port?,
+            bitcoin_data_slug: bitcoin_data_slug?,
+            bitcoin_tar_paths: bitcoin_tar_paths?,
+            argon_version: argon_version?,
+            argon_chain: argon_chain?,
+            argon_archive_node: argon_archive_node?,
+            notebook_archive_hosts: notebook_archive_hosts?,
+            argon_p2p_port: argon_p2p_port?,
+            argon_rpc_port: argon_rpc_port?,
+            argon_bootnodes,
+            bot_port: bot_port?,
+            status_port: status_port?,
+            extra: reader.unread(),
+        })
+    }
+
+    /// Moves ports off the network defaults, ie, when this instance remapped a port conflict.
+    pub fn apply_port_overrides(
+        &mut self,
+        overrides: &BTreeMap<String, u16>,
+    ) -> Result<(), String> {
+        for (key, port) in overrides {
+            let target = self
+                .port_mut(key)
+                .ok_or_else(|| format!("{} is not a port setting", key))?;
+            *target = *port;
+        }
+        Ok(())
+    }
+
+    fn port_mut(&mut self, key: &str) -> Option<&mut u16> {
+        match key {
+            "BITCOIN_P2P_PORT" => Some(&mut self.bitcoin_p2p_port),
+            "ARGON_P2P_PORT" => Some(&mut self.argon_p2p_port),
+            "ARGON_RPC_PORT" => Some(&mut self.argon_rpc_port),
+            "BOT_PORT" => Some(&mut self.bot_port),
+            "STATUS_PORT" => Some(&mut self.status_port),
+            _ => None,
+        }
+    }
+
+    /// Every port the server binds on the host, keyed by its env name.
+    pub fn ports(&self) -> Vec<(&'static str, u16)> {
+        vec![
+            ("BITCOIN_P2P_PORT", self.bitcoin_p2p_port),
+            ("ARGON_P2P_PORT", self.argon_p2p_port),
+            ("ARGON_RPC_PORT", self.argon_rpc_port),
+            ("BOT_PORT", self.bot_port),
+            ("STATUS_PORT", self.status_port),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TESTNET: &str = include_str!("../../server/.env.testnet");
+
+    #[test]
+    fn parses_the_built_in_networks() {
+        for env_text in [
+            include_str!("../../server/.env.dev-docker"),
+            include_str!("../../server/.env.localnet"),
+            include_str!("../../server/.env.mainnet"),
+            TESTNET,
+        ] {
+            NetworkEnv::parse(env_text).unwrap();
+        }
+    }
+
+    #[test]
+    fn keeps_urls_as_written_and_passes_unknown_keys_through() {
+        let env = NetworkEnv::parse(TESTNET).unwrap();
+        assert_eq!(env.bitcoin_chain, BitcoinChain::Signet);
+        assert_eq!(env.argon_version.to_string(), "v1.3.8");
+        assert_eq!(env.extra.get("ROOT").map(String::as_str), Some(".."));
+        assert_eq!(
+            env.extra.get("ARGON_DATA_FOLDER").map(String::as_str),
+            Some("../data/argon")
+        );
+        assert!(!env.extra.contains_key("DATA_FOLDER"));
+
+        let json = serde_json::to_value(&env).unwrap();
+        assert_eq!(
+            json["ARGON_ARCHIVE_NODE"],
+            "wss://rpc.testnet.argonprotocol.org"
+        );
+        assert_eq!(
+            json["NOTEBOOK_ARCHIVE_HOSTS"],
+            serde_json::json!(["https://testnet-argon-notary-archive.argonprotocol.org/"])
+        );
+        assert_eq!(json["ROOT"], "..");
+        assert_eq!(json["STATUS_PORT"], 3261);
+    }
+
+    #[test]
+    fn reports_every_problem_together() {
+        let env_text = TESTNET
+            .replace("STATUS_PORT=3261", "")
+            .replace("BOT_PORT=3260", "BOT_PORT=not-a-port")
+            .replace("ARGON_VERSION=v1.3.8", "ARGON_VERSION=latest")
+            .replace(
+                "ARGON_ARCHIVE_NODE=wss://rpc.testnet.argonprotocol.org",
+                "ARGON_ARCHIVE_NODE=rpc.testnet",
+            )
+            .replace("BITCOIN_CHAIN=signet", "BITCOIN_CHAIN=litecoin");
+        let error = NetworkEnv::parse(&env_text).unwrap_err();
+        for expected in [
+            "STATUS_PORT is missing",
+            "BOT_PORT:",
+            "ARGON_VERSION: 'latest' is not a version",
+            "ARGON_ARCHIVE_NODE: 'rpc.testnet' is not a url",
+            "BITCOIN_CHAIN: 'litecoin' is not a bitcoin chain",
+        ] {
+            assert!(
+                error.contains(expected),
+                "{} is missing from {}",
+                expected,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn applies_port_overrides() {
+        let mut env = NetworkEnv::parse(TESTNET).unwrap();
+        env.apply_port_overrides(&BTreeMap::from([("ARGON_RPC_PORT".to_string(), 19944)]))
+            .unwrap();
+        assert!(env.ports().contains(&("ARGON_RPC_PORT", 19944)));
+        assert!(env.ports().contains(&("BOT_PORT", 3260)));
+
+        let error = env
+            .apply_port_overrides(&BTreeMap::from([("ROOT".to_string(), 1)]))
+            .unwrap_err();
+        assert_eq!(error, "ROOT is not a port setting");
+    }
+}
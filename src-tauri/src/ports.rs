@@ -0,0 +1,280 @@
+use crate::docker_api::DockerApi;
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, Ipv6Addr, TcpListener, UdpSocket};
+
+/// How far past a blocked port to look for a free one.
+const SUGGESTION_RANGE: u16 = 1000;
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PortBinding {
+    TcpV4,
+    TcpV6,
+    UdpV4,
+    UdpV6,
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PortOwner {
+    /// None when the port is held by a container rather than a local process
+    pub pid: Option<u32>,
+    /// The process or container name
+    pub name: String,
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PortConflict {
+    /// The env setting the port comes from, ie, BITCOIN_P2P_PORT
+    pub env_key: String,
+    pub port: u16,
+    /// Bindings that failed. Empty when only a container engine reports holding the port
+    pub blocked: Vec<PortBinding>,
+    /// Who holds the port, when the OS or the container engine will say
+    pub owner: Option<PortOwner>,
+    /// The nearest higher port that's free on every binding
+    pub suggested_port: Option<u16>,
+}
+
+/// Checks each port over tcp and udp on both address families, plus the ports other containers
+/// publish, which names the container as the owner. Ports published by `project_name`'s own
+/// containers aren't conflicts.
+pub fn find_conflicts(ports: &[(&str, u16)], project_name: &str) -> Vec<PortConflict> {
+    let (container_ports, own_ports) = get_container_ports(project_name);
+    let needed_ports = ports.iter().map(|(_, port)| *port).collect::<HashSet<_>>();
+    let mut conflicts: Vec<PortConflict> = vec![];
+    for (env_key, port) in ports {
+        if own_ports.contains(port) {
+            continue;
+        }
+        let blocked = get_blocked_bindings(*port);
+        let container_owner = container_ports.get(port);
+        if blocked.is_empty() && container_owner.is_none() {
+            continue;
+        }
+        let owner = match container_owner {
+            Some(name) => Some(PortOwner {
+                pid: None,
+                name: name.clone(),
+            }),
+            None => find_port_owner(*port),
+        };
+        let mut taken = needed_ports.clone();
+        taken.extend(container_ports.keys());
+        taken.extend(conflicts.iter().filter_map(|x| x.suggested_port));
+        conflicts.push(PortConflict {
+            env_key: env_key.to_string(),
+            port: *port,
+            blocked,
+            owner,
+            suggested_port: find_free_port(*port, &taken),
+        });
+    }
+    conflicts
+}
+
+/// Whether a port can be bound everywhere, ignoring ports `project_name` already publishes.
+pub fn is_port_free(port: u16, project_name: &str) -> bool {
+    let (container_ports, own_ports) = get_container_ports(project_name);
+    own_ports.contains(&port)
+        || (!container_ports.contains_key(&port) && get_blocked_bindings(port).is_empty())
+}
+
+fn get_blocked_bindings(port: u16) -> Vec<PortBinding> {
+    // a host without ipv6 fails with "address not available", which isn't a conflict
+    let is_blocked = |result: std::io::Result<()>| match result {
+        Ok(()) => false,
+        Err(e) => matches!(e.kind(), ErrorKind::AddrInUse | ErrorKind::PermissionDenied),
+    };
+    let mut blocked = vec![];
+    if is_blocked(TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).map(drop)) {
+        blocked.push(PortBinding::TcpV4);
+    }
+    if is_blocked(TcpListener::bind((Ipv6Addr::UNSPECIFIED, port)).map(drop)) {
+        blocked.push(PortBinding::TcpV6);
+    }
+    if is_blocked(UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)).map(drop)) {
+        blocked.push(PortBinding::UdpV4);
+    }
+    if is_blocked(UdpSocket::bind((Ipv6Addr::UNSPECIFIED, port)).map(drop)) {
+        blocked.push(PortBinding::UdpV6);
+    }
+    blocked
+}
+
+/// Public ports of running containers, split into other projects' (with the container name) and
+/// the given project's own.
+fn get_container_ports(project_name: &str) -> (HashMap<u16, String>, HashSet<u16>) {
+    let mut container_ports = HashMap::new();
+    let mut own_ports = HashSet::new();
+    let containers = DockerApi::detect()
+        .map(|api| api.list_running_containers())
+        .transpose()
+        .unwrap_or_else(|e| {
+            log::warn!("Unable to list containers for a port check: {}", e);
+            None
+        })
+        .unwrap_or_default();
+    for container in containers {
+        let is_own = container.project_name() == Some(project_name);
+        for port in container.ports.iter().filter_map(|p| p.public_port) {
+            if is_own {
+                own_ports.insert(port);
+            } else {
+                container_ports.insert(port, container.name().to_string());
+            }
+        }
+    }
+    (container_ports, own_ports)
+}
+
+fn find_free_port(blocked_port: u16, taken: &HashSet<u16>) -> Option<u16> {
+    let end = blocked_port.saturating_add(SUGGESTION_RANGE);
+    (blocked_port.checked_add(1)?..=end)
+        .find(|port| !taken.contains(port) && get_blocked_bindings(*port).is_empty())
+}
+
+/// Finds the listening process through /proc. Sockets of other users' processes can't be read
+/// without root, so those come back as None.
+#[cfg(target_os = "linux")]
+fn find_port_owner(port: u16) -> Option<PortOwner> {
+    // tcp sockets only count while listening (state 0A); udp ones have no listen state
+    let mut inodes = HashSet::new();
+    for (table, listen_state) in [
+        ("tcp", Some("0A")),
+        ("tcp6", Some("0A")),
+        ("udp", None),
+        ("udp6", None),
+    ] {
+        let Ok(text) = std::fs::read_to_string(format!("/proc/net/{}", table)) else {
+            continue;
+        };
+        for line in text.lines().skip(1) {
+            let columns = line.split_whitespace().collect::<Vec<_>>();
+            let (Some(local_address), Some(state), Some(inode)) =
+                (columns.get(1), columns.get(3), columns.get(9))
+            else {
+                continue;
+            };
+            let local_port = local_address
+                .rsplit(':')
+                .next()
+                .and_then(|x| u16::from_str_radix(x, 16).ok());
+            if local_port == Some(port) && listen_state.is_none_or(|x| x == *state) {
+                inodes.insert(format!("socket:[{}]", inode));
+            }
+        }
+    }
+    if inodes.is_empty() {
+        return None;
+    }
+
+    for process in std::fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = process
+            .file_name()
+            .to_str()
+            .and_then(|x| x.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        let holds_socket = fds.flatten().any(|fd| {
+            std::fs::read_link(fd.path())
+                .is_ok_and(|target| inodes.contains(target.to_string_lossy().as_ref()))
+        });
+        if holds_socket {
+            let name = std::fs::read_to_string(process.path().join("comm")).unwrap_or_default();
+            return Some(PortOwner {
+                pid: Some(pid),
+                name: name.trim().to_string(),
+            });
+        }
+    }
+    None
+}
+
+/// Asks lsof, which ships with macOS, in its field output format (`p<pid>` and `c<command>` lines).
+#[cfg(target_os = "macos")]
+fn find_port_owner(port: u16) -> Option<PortOwner> {
+    let output = std::process::Command::new("lsof")
+        .args(["-nP", &format!("-i:{}", port), "-Fpc"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let pid = text
+        .lines()
+        .find_map(|line| line.strip_prefix('p'))
+        .and_then(|x| x.parse::<u32>().ok())?;
+    let name = text
+        .lines()
+        .find_map(|line| line.strip_prefix('c'))
+        .unwrap_or_default();
+    Some(PortOwner {
+        pid: Some(pid),
+        name: name.to_string(),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn find_port_owner(_port: u16) -> Option<PortOwner> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_a_bound_port_as_blocked() {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(get_blocked_bindings(port).contains(&PortBinding::TcpV4));
+        drop(listener);
+        assert!(!get_blocked_bindings(port).contains(&PortBinding::TcpV4));
+    }
+
+    #[test]
+    fn reports_conflicts_with_their_owner_and_a_suggestion() {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let ports = [("BITCOIN_P2P_PORT", port)];
+
+        let conflicts = find_conflicts(&ports, "commander-test");
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].env_key, "BITCOIN_P2P_PORT");
+        assert!(conflicts[0].blocked.contains(&PortBinding::TcpV4));
+        assert!(conflicts[0].suggested_port.is_some_and(|x| x > port));
+        #[cfg(target_os = "linux")]
+        assert_eq!(
+            conflicts[0].owner.as_ref().and_then(|x| x.pid),
+            Some(std::process::id())
+        );
+        assert!(!is_port_free(port, "commander-test"));
+
+        drop(listener);
+        assert!(find_conflicts(&ports, "commander-test").is_empty());
+        assert!(is_port_free(port, "commander-test"));
+    }
+
+    #[test]
+    fn suggests_the_next_port_that_is_free_and_not_taken() {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let taken = HashSet::from([port.wrapping_add(1)]);
+        let suggested = find_free_port(port - 1, &taken).unwrap();
+        assert!(suggested > port + 1);
+        assert!(suggested <= port - 1 + SUGGESTION_RANGE);
+        assert!(get_blocked_bindings(suggested).is_empty());
+    }
+
+    #[test]
+    fn gives_up_past_the_suggestion_range() {
+        assert_eq!(find_free_port(u16::MAX, &HashSet::new()), None);
+        let taken = (40_001..=40_000 + SUGGESTION_RANGE).collect::<HashSet<_>>();
+        assert_eq!(find_free_port(40_000, &taken), None);
+    }
+}
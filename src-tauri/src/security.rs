@@ -1,13 +1,19 @@
+#[cfg(feature = "gui")]
 use bip39::{Language, Mnemonic};
+#[cfg(feature = "gui")]
 use rand::RngCore;
 use std::fs;
-#[cfg(not(target_os = "windows"))]
+#[cfg(all(feature = "gui", not(target_os = "windows")))]
 use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+#[cfg(feature = "gui")]
 use tauri::AppHandle;
 
+use crate::error::CommanderError;
+#[cfg(feature = "gui")]
 use crate::{ssh::SSH, utils::Utils};
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, specta::Type, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Security {
     pub master_mnemonic: String,
@@ -16,31 +22,48 @@ pub struct Security {
 }
 
 impl Security {
-    pub fn load(app: &AppHandle) -> Result<Self, Box<dyn std::error::Error>> {
-        let absolute_config_dir = Utils::get_absolute_config_instance_dir(app);
+    #[cfg(feature = "gui")]
+    pub fn load(app: &AppHandle) -> Result<Self, CommanderError> {
+        Self::load_from_dir(&Utils::get_absolute_config_instance_dir(app))
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn load_from_dir(absolute_config_dir: &Path) -> Result<Self, CommanderError> {
+        match Self::read_from_dir(absolute_config_dir)? {
+            Some(security) => Ok(security),
+            None => Security::create_in_dir(absolute_config_dir),
+        }
+    }
+
+    /// Reads the keys without creating them, for callers that must not initialize an instance.
+    pub fn read_from_dir(absolute_config_dir: &Path) -> Result<Option<Self>, CommanderError> {
         let mnemonic_file_path = absolute_config_dir.join("mnemonic");
         let public_key_path = absolute_config_dir.join("serverkey.pub");
         let private_key_path = absolute_config_dir.join("serverkey.pem");
 
-        if mnemonic_file_path.exists() && public_key_path.exists() && private_key_path.exists() {
-            // Load mnemonics
-            let master_mnemonic = fs::read_to_string(&mnemonic_file_path)?;
+        if !(mnemonic_file_path.exists() && public_key_path.exists() && private_key_path.exists()) {
+            return Ok(None);
+        }
+        // Load mnemonics
+        let master_mnemonic = fs::read_to_string(&mnemonic_file_path)?;
 
-            // Load SSH keys
-            let ssh_public_key = fs::read_to_string(&public_key_path)?;
+        // Load SSH keys
+        let ssh_public_key = fs::read_to_string(&public_key_path)?;
 
-            Ok(Self {
-                ssh_public_key,
-                ssh_private_key_path: private_key_path.to_string_lossy().to_string(),
-                master_mnemonic,
-            })
-        } else {
-            Security::create(app)
-        }
+        Ok(Some(Self {
+            ssh_public_key,
+            ssh_private_key_path: private_key_path.to_string_lossy().to_string(),
+            master_mnemonic,
+        }))
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn save(&self, app: &AppHandle) -> Result<(), CommanderError> {
+        self.save_to_dir(&Utils::get_absolute_config_instance_dir(app))
     }
 
-    pub fn save(&self, app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
-        let absolute_config_dir = Utils::get_absolute_config_instance_dir(app);
+    #[cfg(feature = "gui")]
+    pub fn save_to_dir(&self, absolute_config_dir: &Path) -> Result<(), CommanderError> {
         // Save mnemonics
         fs::write(absolute_config_dir.join("mnemonic"), &self.master_mnemonic)?;
 
@@ -61,23 +84,24 @@ impl Security {
         Ok(())
     }
 
-    pub fn create(app: &AppHandle) -> Result<Self, Box<dyn std::error::Error>> {
+    #[cfg(feature = "gui")]
+    pub fn create_in_dir(config_dir: &Path) -> Result<Self, CommanderError> {
         let (private_key, public_key) = SSH::generate_keys()?;
         let master_mnemonic = Self::generate_mnemonic()?;
-        let config_dir = Utils::get_absolute_config_instance_dir(app);
         let ssh_private_key_path = config_dir.join("serverkey.pem");
-        fs::create_dir_all(&config_dir)?;
+        fs::create_dir_all(config_dir)?;
         fs::write(&ssh_private_key_path, private_key)?;
         let instance = Self {
             master_mnemonic,
             ssh_public_key: public_key,
             ssh_private_key_path: ssh_private_key_path.to_string_lossy().to_string(),
         };
-        instance.save(app)?;
+        instance.save_to_dir(config_dir)?;
         Ok(instance)
     }
 
-    fn generate_mnemonic() -> Result<String, Box<dyn std::error::Error>> {
+    #[cfg(feature = "gui")]
+    fn generate_mnemonic() -> Result<String, CommanderError> {
         let mut entropy = [0u8; 16];
         rand::thread_rng().fill_bytes(&mut entropy);
 
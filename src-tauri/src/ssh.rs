@@ -1,16 +1,23 @@
+use crate::error::{CommanderError, ErrorCode};
+#[cfg(feature = "gui")]
 use crate::utils::Utils;
 use anyhow::Result;
 use log::info;
+#[cfg(feature = "gui")]
 use rand::rngs::OsRng;
 use russh::client::{AuthResult, Msg};
+#[cfg(feature = "gui")]
 use russh::keys::ssh_key::LineEnding;
 use russh::keys::*;
 use russh::*;
 use std::fmt::Display;
 use std::sync::Arc;
 use std::time::Duration;
+#[cfg(feature = "gui")]
 use tauri::{AppHandle, Emitter};
+#[cfg(feature = "gui")]
 use tokio::fs::File;
+#[cfg(feature = "gui")]
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::sync::Mutex;
 use tokio::time::timeout;
@@ -74,7 +81,10 @@ impl SSH {
         let client = timeout(timeout_duration, Self::authenticate(config))
             .await
             .map_err(|_| {
-                anyhow::anyhow!("SSH connection timed out after {:?}", timeout_duration)
+                CommanderError::new(
+                    ErrorCode::SshTimeout,
+                    format!("SSH connection timed out after {:?}", timeout_duration),
+                )
             })??;
         let ssh = SSH {
             client: Arc::new(Mutex::new(client)),
@@ -107,12 +117,14 @@ impl SSH {
             partial_success,
         } = auth_res
         {
-            anyhow::bail!(
-                "Authentication (with publickey) failed for {}: {:?} (partial success: {})",
-                ssh_config.username,
-                remaining_methods,
-                partial_success
-            );
+            return Err(CommanderError::new(
+                ErrorCode::SshAuthFailed,
+                format!(
+                    "Authentication (with publickey) failed for {}: {:?} (partial success: {})",
+                    ssh_config.username, remaining_methods, partial_success
+                ),
+            )
+            .into());
         }
         Ok(client)
     }
@@ -167,11 +179,17 @@ impl SSH {
             }
         }
         let _ = channel.close().await;
-        let code = code.ok_or_else(|| anyhow::anyhow!("SSHCommandMissingExitStatus"))?;
+        let code = code.ok_or_else(|| {
+            CommanderError::new(
+                ErrorCode::SshMissingExitStatus,
+                "SSHCommandMissingExitStatus",
+            )
+        })?;
 
         Ok((output, code))
     }
 
+    #[cfg(feature = "gui")]
     pub async fn upload_file(&self, contents: &[u8], remote_path: &str) -> Result<()> {
         // First, create the script in the remote server's home directory
         info!("Uploading file {}", remote_path);
@@ -189,6 +207,7 @@ impl SSH {
         Ok(())
     }
 
+    #[cfg(feature = "gui")]
     pub async fn upload_embedded_file(
         &self,
         app: &AppHandle,
@@ -235,6 +254,7 @@ impl SSH {
         Ok(())
     }
 
+    #[cfg(feature = "gui")]
     pub async fn download_remote_file(
         &self,
         app: &AppHandle,
@@ -326,6 +346,7 @@ impl SSH {
         }
     }
 
+    #[cfg(feature = "gui")]
     pub fn generate_keys() -> Result<(String, String), String> {
         // Generate a new key pair using Ed25519
         let private_key =
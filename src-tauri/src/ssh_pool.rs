@@ -19,6 +19,7 @@ pub async fn close_connection(address: &str) -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "gui")]
 pub async fn get_connection(address: &str) -> Result<Option<SSH>> {
     let address = address.to_string();
     Ok(CONNECTIONS_BY_ADDRESS.lock().await.get(&address).cloned())
@@ -0,0 +1,80 @@
+//! Fixtures shared by the unit tests.
+
+use crate::db::{BigIntJson, Frame};
+use sqlx::SqlitePool;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// The `tokio` macros feature isn't enabled, so async tests drive their futures through this.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Runtime::new().unwrap().block_on(future)
+}
+
+/// An instance database in a temporary directory, removed when dropped.
+pub struct TestDb {
+    pub dir: TempDir,
+    pub pool: SqlitePool,
+}
+
+impl TestDb {
+    /// A database with every migration applied.
+    pub async fn migrated() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("database.sqlite");
+        crate::migrations::run_db_migrations(db_path.clone(), false, |_| {})
+            .await
+            .unwrap();
+        let pool = Self::connect(&db_path).await;
+        Self { dir, pool }
+    }
+
+    pub async fn connect(db_path: &Path) -> SqlitePool {
+        let options = sqlx::sqlite::SqliteConnectOptions::new().filename(db_path);
+        SqlitePool::connect_with(options).await.unwrap()
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.path().join("database.sqlite")
+    }
+}
+
+/// A processed frame with nothing mined and no price samples.
+pub fn frame(id: i64) -> Frame {
+    Frame {
+        id,
+        first_tick: id * 1440,
+        last_tick: id * 1440 + 1439,
+        first_block_number: 0,
+        last_block_number: 0,
+        microgon_to_usd: BigIntJson(vec![]),
+        microgon_to_btc: BigIntJson(vec![]),
+        microgon_to_argonot: BigIntJson(vec![]),
+        all_miners_count: 0,
+        seat_count_active: 0,
+        seat_cost_total_framed: 0,
+        blocks_mined_total: 0,
+        microgon_fees_collected_total: 0,
+        micronots_mined_total: 0,
+        microgons_mined_total: 0,
+        microgons_minted_total: 0,
+        accrued_microgon_profits: 0,
+        progress: 100.0,
+        is_processed: true,
+    }
+}
+
+/// Inserts a lock with only the fields every status needs.
+pub async fn insert_bitcoin_lock(pool: &SqlitePool, utxo_id: i64, vault_id: i64, hd_path: &str) {
+    sqlx::query(
+        "INSERT INTO BitcoinLocks (utxoId, status, satoshis, peggedPrice, ratchets, cosignVersion,
+            lockDetails, network, hdPath, vaultId)
+        VALUES (?, 'initialized', 1000, 1000, '[]', 'v1', '{}', 'signet', ?, ?)",
+    )
+    .bind(utxo_id)
+    .bind(hd_path)
+    .bind(vault_id)
+    .execute(pool)
+    .await
+    .unwrap();
+}
@@ -1,8 +1,11 @@
+use crate::error::{CommanderError, ErrorCode, ResultExt};
+use crate::instances::InstanceMetadata;
+use crate::network_env::NetworkEnv;
 use anyhow::Result;
+use fs2::FileExt;
 use rand::RngCore;
-use std::collections::HashMap;
-use std::io::Cursor;
 use std::path::{Path, PathBuf};
+#[cfg(feature = "gui")]
 use tauri::{AppHandle, Manager};
 
 static ENV_DOCKER: &str = include_str!("../../server/.env.dev-docker");
@@ -10,6 +13,20 @@ static ENV_LOCAL: &str = include_str!("../../server/.env.localnet");
 static ENV_MAINNET: &str = include_str!("../../server/.env.mainnet");
 static ENV_TESTNET: &str = include_str!("../../server/.env.testnet");
 
+#[cfg(feature = "gui")]
+pub const BUILT_IN_NETWORKS: [&str; 4] = ["dev-docker", "localnet", "mainnet", "testnet"];
+
+#[cfg(feature = "gui")]
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Network {
+    pub name: String,
+    /// Loaded from an `.env.<name>` file in the app config dir rather than compiled in
+    pub is_custom: bool,
+    /// Why the env file can't be used, if it fails validation
+    pub error: Option<String>,
+}
+
 pub struct Utils;
 
 impl Utils {
@@ -29,21 +46,88 @@ impl Utils {
         option_env!("ARGON_EXPERIMENTAL").map_or(false, |v| v == "true")
     }
 
-    pub fn get_server_env_vars() -> Result<HashMap<String, String>, String> {
-        let env_text = match Self::get_network_name().as_str() {
+    /// The current network's env, with any ports this instance remapped applied.
+    pub fn get_server_env() -> Result<NetworkEnv, String> {
+        let mut network_env = Self::get_network_env(&Self::get_network_name())?;
+        let metadata = InstanceMetadata::load(&Self::get_headless_config_instance_dir()?);
+        network_env.apply_port_overrides(&metadata.port_overrides)?;
+        Ok(network_env)
+    }
+
+    pub fn get_network_env(network_name: &str) -> Result<NetworkEnv, String> {
+        let env_text = Self::get_network_env_text(network_name)?;
+        NetworkEnv::parse(&env_text)
+            .map_err(|e| format!("Invalid env file for network {}: {}", network_name, e))
+    }
+
+    /// The raw env file for a network. Built-in networks are compiled in; any other network is
+    /// read from `.env.<network>` in the app config dir.
+    pub fn get_network_env_text(network_name: &str) -> Result<String, String> {
+        let env_text = match network_name {
             "dev-docker" => ENV_DOCKER,
             "localnet" => ENV_LOCAL,
             "mainnet" => ENV_MAINNET,
             "testnet" => ENV_TESTNET,
-            _ => return Err("Unknown network".to_string()),
+            _ => {
+                let path = Self::get_custom_network_env_path(network_name)?;
+                if !path.exists() {
+                    return Err(format!(
+                        "Unknown network {}. Add it as {}",
+                        network_name,
+                        path.display()
+                    ));
+                }
+                return std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Error reading {}: {}", path.display(), e));
+            }
         };
-        let env_vars = dotenvy::from_read_iter(Cursor::new(env_text));
+        Ok(env_text.to_string())
+    }
+
+    pub fn get_custom_network_env_path(network_name: &str) -> Result<PathBuf, String> {
+        Ok(Self::get_app_config_dir()?.join(format!(".env.{}", network_name)))
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn is_custom_network(network_name: &str) -> bool {
+        !BUILT_IN_NETWORKS.contains(&network_name)
+    }
+
+    /// The built-in networks followed by every `.env.<name>` file in the app config dir.
+    #[cfg(feature = "gui")]
+    pub fn list_networks() -> Vec<Network> {
+        let mut networks = BUILT_IN_NETWORKS
+            .iter()
+            .map(|name| Network {
+                name: name.to_string(),
+                is_custom: false,
+                error: None,
+            })
+            .collect::<Vec<_>>();
 
-        let mut result = HashMap::new();
-        for (key, value) in env_vars.flatten() {
-            result.insert(key, value);
+        let Ok(entries) = Self::get_app_config_dir().and_then(|dir| {
+            std::fs::read_dir(&dir).map_err(|e| format!("Error reading {}: {}", dir.display(), e))
+        }) else {
+            return networks;
+        };
+        let mut custom = vec![];
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(name) = file_name.strip_prefix(".env.") else {
+                continue;
+            };
+            if name.is_empty() || !Self::is_custom_network(name) {
+                continue;
+            }
+            custom.push(Network {
+                name: name.to_string(),
+                is_custom: true,
+                error: Self::get_network_env(name).err(),
+            });
         }
-        Ok(result)
+        custom.sort_by(|a, b| a.name.cmp(&b.name));
+        networks.extend(custom);
+        networks
     }
 
     pub fn get_network_name() -> String {
@@ -70,6 +154,28 @@ impl Utils {
         PathBuf::from(network_name).join(instance_name)
     }
 
+    pub fn get_app_identifier() -> &'static str {
+        // keep in sync with tauri.conf.json and tauri.experimental.conf.json
+        if Self::is_experimental() {
+            "com.argon.commander.experimental"
+        } else {
+            "com.argon.commander"
+        }
+    }
+
+    /// The same directory tauri resolves as `AppConfig`, but available without a running app.
+    pub fn get_app_config_dir() -> Result<PathBuf, String> {
+        let config_dir = dirs::config_dir().ok_or("Unable to resolve the config directory")?;
+        Ok(config_dir.join(Self::get_app_identifier()))
+    }
+
+    /// Resolves the same directory as [`Utils::get_absolute_config_instance_dir`] without a running
+    /// app, ie, for the headless cli.
+    pub fn get_headless_config_instance_dir() -> Result<PathBuf, String> {
+        Ok(Self::get_app_config_dir()?.join(Self::get_relative_config_instance_dir()))
+    }
+
+    #[cfg(feature = "gui")]
     pub fn get_absolute_config_instance_dir(app: &AppHandle) -> PathBuf {
         app.path()
             .resolve(
@@ -79,6 +185,40 @@ impl Utils {
             .expect("Failed to resolve config instance directory")
     }
 
+    /// Takes the exclusive lock for an instance directory, failing straight away if another
+    /// process holds it. The lock is released when the returned file is dropped.
+    pub fn lock_instance_dir(config_dir: &Path) -> Result<std::fs::File, CommanderError> {
+        std::fs::create_dir_all(config_dir)
+            .context(format!("Error creating directory {}", config_dir.display()))?;
+        let lock_path = config_dir.join("instance.lock");
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .context(format!("Error opening {}", lock_path.display()))?;
+        file.try_lock_exclusive().map_err(|e| {
+            if e.kind() == fs2::lock_contended_error().kind() {
+                CommanderError::new(
+                    ErrorCode::InstanceInUse,
+                    format!(
+                        "Instance {} is already open in another Commander process",
+                        config_dir.display()
+                    ),
+                )
+            } else {
+                CommanderError::from(e).context(format!("Error locking {}", lock_path.display()))
+            }
+        })?;
+        Ok(file)
+    }
+
+    /// Space available to the user on the volume holding `path`.
+    pub fn get_free_space(path: &Path) -> std::io::Result<u64> {
+        fs2::available_space(path)
+    }
+
+    #[cfg(feature = "gui")]
     pub fn get_embedded_path(app: &AppHandle, path: impl AsRef<Path>) -> anyhow::Result<PathBuf> {
         let local_base_path = app.path().resolve(
             PathBuf::from("..").join(path),
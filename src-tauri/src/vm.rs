@@ -1,72 +1,872 @@
+use crate::container_runtime::ContainerRuntime;
+use crate::docker_api::DockerApi;
+use crate::error::{CommanderError, ErrorCode, ResultExt};
+#[cfg(feature = "gui")]
+use crate::instances::InstanceMetadata;
+use crate::network_env::NetworkEnv;
+#[cfg(feature = "gui")]
+use crate::ports::{self, PortConflict};
 use crate::utils::Utils;
+use crate::vm_qemu::QemuVm;
 use include_dir::{Dir, include_dir};
+use std::collections::BTreeMap;
 use std::fs;
-use std::net::TcpListener;
-use std::path::PathBuf;
-use std::process::Command;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "gui")]
+use std::process::Child;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+#[cfg(feature = "gui")]
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "gui")]
 use tauri::AppHandle;
+#[cfg(feature = "gui")]
+use tauri_specta::Event;
 
 static VM_FILES: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/../local-machine");
 
+/// `compose logs --follow` processes started by [`Vm::follow_logs`], killed when following stops.
+#[cfg(feature = "gui")]
+static LOG_FOLLOWERS: Mutex<Vec<Child>> = Mutex::new(vec![]);
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Vm {
     pub ssh_port: u16,
 }
 
+/// What runs the local VM. Recorded as `VM_BACKEND` in its `.env`; VMs made before there was a
+/// choice have none and are docker ones.
+#[derive(serde::Serialize, specta::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum VmBackend {
+    /// A systemd container that drives the host's container engine
+    Docker,
+    /// A QEMU machine that runs its own engine, for Linux hosts without a usable container runtime
+    Qemu,
+}
+
+impl VmBackend {
+    /// The backend a new VM gets. `COMMANDER_VM_BACKEND` can ask for either one; otherwise docker
+    /// is used when a container runtime responds, and QEMU is the fallback on Linux. The runtime
+    /// is pinged on each call, so one stopped since it was detected falls through to QEMU.
+    pub fn detect() -> Option<Self> {
+        let has_docker = || ContainerRuntime::get().is_some();
+        let has_qemu = || cfg!(target_os = "linux") && QemuVm::is_available();
+        match std::env::var("COMMANDER_VM_BACKEND")
+            .map(|name| name.to_lowercase())
+            .as_deref()
+        {
+            Ok("docker") => has_docker().then_some(Self::Docker),
+            Ok("qemu") => has_qemu().then_some(Self::Qemu),
+            _ if has_docker() => Some(Self::Docker),
+            _ => has_qemu().then_some(Self::Qemu),
+        }
+    }
+
+    /// The backend an existing VM was created with. VMs from before QEMU was an option are docker.
+    pub fn of(vm_path: &Path) -> Self {
+        match get_env_value(vm_path, "VM_BACKEND").as_deref() {
+            Some("qemu") => Self::Qemu,
+            _ => Self::Docker,
+        }
+    }
+
+    fn env_value(&self) -> &'static str {
+        match self {
+            Self::Docker => "docker",
+            Self::Qemu => "qemu",
+        }
+    }
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct VmStatus {
+    /// The VM dir exists. Everything else is empty when it doesn't
+    pub exists: bool,
+    pub backend: Option<VmBackend>,
+    /// created, running, paused, exited, ..., or None if the container hasn't been created
+    pub state: Option<String>,
+    /// starting, healthy or unhealthy
+    pub health: Option<String>,
+    pub started_at: Option<String>,
+    pub uptime_seconds: Option<u64>,
+    pub ssh_port: Option<u16>,
+    pub image: Option<String>,
+    pub image_id: Option<String>,
+    /// Percent of a single cpu
+    pub cpu_percent: Option<f64>,
+    pub memory_usage_bytes: Option<u64>,
+    pub memory_limit_bytes: Option<u64>,
+    /// Disk space used by the `app` bind mount, which holds the synced chain data. Measured at
+    /// most every few minutes
+    pub app_disk_usage_bytes: u64,
+    /// Embedded `local-machine` files that differ from the ones the VM was built from
+    pub changed_files: Vec<String>,
+    pub limits: VmResourceLimits,
+}
+
+/// Smallest memory limit the VM's bitcoin and argon nodes can sync with.
+const MIN_VM_MEMORY_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Caps on what the local VM can take from the machine. None leaves a resource unlimited.
+#[derive(serde::Serialize, serde::Deserialize, specta::Type, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VmResourceLimits {
+    pub cpus: Option<f64>,
+    pub memory_bytes: Option<u64>,
+    /// Free space the `app` work dir must have room to grow into. Bind mounts can't be capped by
+    /// the engine, so for docker this is only a check against free space whenever limits are set,
+    /// not a cap. A QEMU VM's disk is sized to it.
+    pub reserved_disk_bytes: Option<u64>,
+}
+
+impl VmResourceLimits {
+    fn from_env(vm_path: &Path) -> Self {
+        let read = |key: &str| get_env_value(vm_path, key).filter(|value| value != "0");
+        Self {
+            cpus: read("VM_CPUS").and_then(|value| value.parse().ok()),
+            memory_bytes: read("VM_MEMORY_BYTES").and_then(|value| value.parse().ok()),
+            reserved_disk_bytes: read("VM_RESERVED_DISK_BYTES")
+                .and_then(|value| value.parse().ok()),
+        }
+    }
+
+    /// The compose file reads these, with 0 meaning unlimited.
+    fn to_env(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("VM_CPUS", self.cpus.unwrap_or_default().to_string()),
+            (
+                "VM_MEMORY_BYTES",
+                self.memory_bytes.unwrap_or_default().to_string(),
+            ),
+            (
+                "VM_RESERVED_DISK_BYTES",
+                self.reserved_disk_bytes.unwrap_or_default().to_string(),
+            ),
+        ]
+    }
+
+    /// Checks the limits against what the container engine has available and, for disk, the free
+    /// space on the volume holding the VM, counting what the `app` dir already uses.
+    pub fn validate(&self, vm_path: &Path) -> Result<(), CommanderError> {
+        let invalid = |message: String| Err(CommanderError::new(ErrorCode::InvalidInput, message));
+        let engine_info = DockerApi::detect().and_then(|api| api.get_info().ok());
+        if let Some(cpus) = self.cpus {
+            let available_cpus = match &engine_info {
+                Some(info) => info.cpus as f64,
+                None => std::thread::available_parallelism().map_or(1, |x| x.get()) as f64,
+            };
+            if !cpus.is_finite() || cpus <= 0.0 || cpus > available_cpus {
+                return invalid(format!(
+                    "The VM cpu limit must be between 0 and {}, got {}",
+                    available_cpus, cpus
+                ));
+            }
+        }
+        if let Some(memory_bytes) = self.memory_bytes {
+            if memory_bytes < MIN_VM_MEMORY_BYTES {
+                return invalid(format!(
+                    "The VM needs at least {} bytes of memory, got {}",
+                    MIN_VM_MEMORY_BYTES, memory_bytes
+                ));
+            }
+            if let Some(info) = engine_info
+                .as_ref()
+                .filter(|x| memory_bytes > x.memory_bytes)
+            {
+                return invalid(format!(
+                    "The VM memory limit of {} bytes is more than the {} bytes available",
+                    memory_bytes, info.memory_bytes
+                ));
+            }
+        }
+        if let Some(reserved_disk_bytes) = self.reserved_disk_bytes {
+            let existing_dir = vm_path
+                .ancestors()
+                .find(|path| path.exists())
+                .unwrap_or(vm_path);
+            let free_space = Utils::get_free_space(existing_dir).context(format!(
+                "Error reading free space of {}",
+                existing_dir.display()
+            ))?;
+            let available_bytes = free_space + get_dir_size(&vm_path.join("app"));
+            if reserved_disk_bytes > available_bytes {
+                return invalid(format!(
+                    "The VM disk limit of {} bytes is more than the {} bytes available",
+                    reserved_disk_bytes, available_bytes
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(serde::Serialize, specta::Type, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum VmLogSource {
+    /// The VM container, or a QEMU VM's serial console
+    Vm,
+    /// The server compose stack the VM runs in `app/server`
+    Server,
+}
+
+#[cfg(feature = "gui")]
+impl VmLogSource {
+    fn file_name(&self) -> &'static str {
+        match self {
+            Self::Vm => "vm.log",
+            Self::Server => "server.log",
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(serde::Serialize, specta::Type, Debug, Clone, tauri_specta::Event)]
+#[serde(rename_all = "camelCase")]
+pub struct VmLogLine {
+    pub source: VmLogSource,
+    /// Prefixed by compose with the container name and a timestamp. Console lines from a QEMU VM
+    /// are passed through as they are
+    pub line: String,
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum VmPhase {
+    WritingFiles,
+    BuildingImage,
+    Starting,
+    WaitingForHealth,
+    ResolvingPort,
+    #[cfg(feature = "gui")]
+    Succeeded,
+    #[cfg(feature = "gui")]
+    Failed,
+}
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[cfg_attr(feature = "gui", derive(tauri_specta::Event))]
+#[serde(rename_all = "camelCase")]
+pub struct VmProgress {
+    pub phase: VmPhase,
+    /// A line of compose or build output, if this event carries one
+    pub output: Option<String>,
+    pub error: Option<String>,
+}
+
+impl VmProgress {
+    pub fn new(phase: VmPhase) -> Self {
+        Self {
+            phase,
+            output: None,
+            error: None,
+        }
+    }
+}
+
+/// Runs a VM operation on a blocking thread, emitting [`VmProgress`] events as it goes and a
+/// final succeeded/failed event.
+#[cfg(feature = "gui")]
+async fn run_with_progress(
+    app: &AppHandle,
+    operation: impl FnOnce(&dyn Fn(VmProgress)) -> Result<Vm, CommanderError> + Send + 'static,
+) -> Result<Vm, CommanderError> {
+    let emitter = app.clone();
+    let emit = move |progress: VmProgress| {
+        if let Err(e) = progress.emit(&emitter) {
+            log::warn!("Unable to emit local VM progress: {}", e);
+        }
+    };
+    let on_progress = emit.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || operation(&on_progress))
+        .await
+        .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))
+        .and_then(|result| result);
+
+    match &result {
+        Ok(_) => emit(VmProgress::new(VmPhase::Succeeded)),
+        Err(e) => {
+            let mut progress = VmProgress::new(VmPhase::Failed);
+            progress.error = Some(e.to_string());
+            emit(progress);
+        }
+    }
+    result
+}
+
+#[cfg(feature = "gui")]
 #[tauri::command]
+#[specta::specta]
 pub fn is_docker_running(_app: AppHandle) -> bool {
-    // check if docker is running first
-    let docker_check = Command::new("docker").arg("info").output();
-    docker_check.is_ok() && docker_check.unwrap().status.success()
+    ContainerRuntime::get().is_some()
 }
 
+/// Which engine the local VM runs on, or None if no docker, podman or nerdctl is running.
+#[cfg(feature = "gui")]
 #[tauri::command]
-pub fn check_needed_ports() -> Result<Vec<u16>, String> {
-    let mut blocked_ports = vec![];
-    let env_vars = Utils::get_server_env_vars()?;
-    for (key, value) in env_vars {
-        if key.ends_with("_PORT") {
-            if let Ok(port) = value.parse::<u16>() {
-                let is_in_use = TcpListener::bind(("0.0.0.0", port)).is_err();
-                if is_in_use {
-                    blocked_ports.push(port);
-                }
-            }
-        }
+#[specta::specta]
+pub fn get_container_runtime() -> Option<ContainerRuntime> {
+    ContainerRuntime::get()
+}
+
+/// The backend of the existing VM, or the one a new VM would get. None when there's neither a
+/// running container runtime nor QEMU.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub fn get_local_vm_backend(app: AppHandle) -> Option<VmBackend> {
+    let vm_path = get_vm_path(&app);
+    if vm_path.exists() {
+        Some(VmBackend::of(&vm_path))
+    } else {
+        VmBackend::detect()
     }
-    Ok(blocked_ports)
 }
 
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn check_needed_ports() -> Result<Vec<PortConflict>, CommanderError> {
+    let network_env = Utils::get_server_env().code(ErrorCode::InvalidInput)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        ports::find_conflicts(&network_env.ports(), &get_compose_project_name())
+    })
+    .await
+    .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))
+}
+
+/// Moves server ports for this instance, ie, to the `suggestedPort`s from `check_needed_ports`.
+/// The new ports are saved in the instance metadata and written into the VM and server `.env`s,
+/// and a running server is restarted onto them. Returns the env with every override applied.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn remap_needed_ports(
+    app: AppHandle,
+    ports: BTreeMap<String, u16>,
+) -> Result<NetworkEnv, CommanderError> {
+    let instance_dir = Utils::get_absolute_config_instance_dir(&app);
+    let vm_path = get_vm_path(&app);
+    tauri::async_runtime::spawn_blocking(move || remap_ports(&instance_dir, &vm_path, ports))
+        .await
+        .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))?
+}
+
+/// Rewrites the ports in the VM and server `.env`s from the instance's overrides. The server
+/// installer replaces its `.env` with the network defaults, so this runs after it does.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub fn sync_local_vm_ports(app: AppHandle) -> Result<(), CommanderError> {
+    let network_env = Utils::get_server_env().code(ErrorCode::InvalidInput)?;
+    write_port_env(&get_vm_path(&app), &network_env)
+}
+
+#[cfg(feature = "gui")]
 #[tauri::command]
-pub async fn create_local_vm(app: AppHandle, env_text: String) -> Result<u16, String> {
+#[specta::specta]
+pub async fn create_local_vm(
+    app: AppHandle,
+    env_text: String,
+    limits: VmResourceLimits,
+) -> Result<u16, CommanderError> {
     let vm_path = get_vm_path(&app);
     let work_dir = get_vm_work_dir(&app);
-    let vm = Vm::create(vm_path, work_dir, env_text)?;
+    let vm = run_with_progress(&app, move |on_progress| {
+        Vm::create(vm_path, work_dir, env_text, limits, on_progress)
+    })
+    .await?;
     Ok(vm.ssh_port)
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
-pub async fn activate_local_vm(app: AppHandle) -> Result<u16, String> {
+#[specta::specta]
+pub async fn activate_local_vm(app: AppHandle) -> Result<u16, CommanderError> {
     let vm_path = get_vm_path(&app);
     let work_dir = get_vm_work_dir(&app);
-    let vm = Vm::activate(&vm_path, &work_dir)?;
+    let vm = run_with_progress(&app, move |on_progress| {
+        Vm::activate(&vm_path, &work_dir, on_progress)
+    })
+    .await?;
+    Ok(vm.ssh_port)
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn upgrade_local_vm(app: AppHandle) -> Result<u16, CommanderError> {
+    let vm_path = get_vm_path(&app);
+    let vm = run_with_progress(&app, move |on_progress| Vm::upgrade(&vm_path, on_progress)).await?;
     Ok(vm.ssh_port)
 }
 
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn local_vm_status(app: AppHandle) -> Result<VmStatus, CommanderError> {
+    let vm_path = get_vm_path(&app);
+    tauri::async_runtime::spawn_blocking(move || Vm::get_status(&vm_path))
+        .await
+        .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))?
+}
+
+/// Changes the VM's limits in place. They're saved for the next start and applied to the running
+/// container where the engine allows it.
+#[cfg(feature = "gui")]
 #[tauri::command]
-pub async fn remove_local_vm(app: AppHandle) -> Result<(), String> {
+#[specta::specta]
+pub async fn set_local_vm_limits(
+    app: AppHandle,
+    limits: VmResourceLimits,
+) -> Result<(), CommanderError> {
+    let vm_path = get_vm_path(&app);
+    tauri::async_runtime::spawn_blocking(move || Vm::set_limits(&vm_path, &limits))
+        .await
+        .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))?
+}
+
+/// The last `lines` lines of each log source.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn get_local_vm_logs(
+    app: AppHandle,
+    lines: u32,
+) -> Result<Vec<VmLogLine>, CommanderError> {
+    let vm_path = get_vm_path(&app);
+    tauri::async_runtime::spawn_blocking(move || Vm::get_logs(&vm_path, lines))
+        .await
+        .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))?
+}
+
+/// Emits a [`VmLogLine`] event for every new log line until [`stop_local_vm_logs`] is called.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub fn follow_local_vm_logs(app: AppHandle) -> Result<(), CommanderError> {
+    let vm_path = get_vm_path(&app);
+    Vm::follow_logs(&vm_path, move |line| {
+        if let Err(e) = line.emit(&app) {
+            log::warn!("Unable to emit local VM log: {}", e);
+        }
+    })
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub fn stop_local_vm_logs() {
+    Vm::stop_following_logs();
+}
+
+/// Writes the last `lines` lines of each log source into a new temp dir and returns it, ready to
+/// be passed to `create_zip`. Hand it to `remove_local_vm_logs_export` once zipped.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn export_local_vm_logs(app: AppHandle, lines: u32) -> Result<PathBuf, CommanderError> {
+    let vm_path = get_vm_path(&app);
+    tauri::async_runtime::spawn_blocking(move || Vm::export_logs(&vm_path, lines))
+        .await
+        .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))?
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub fn remove_local_vm_logs_export(export_dir: PathBuf) -> Result<(), CommanderError> {
+    Vm::remove_log_export(&export_dir)
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn remove_local_vm(app: AppHandle) -> Result<(), CommanderError> {
     let vm_path = get_vm_path(&app);
     Vm::destroy(&vm_path)?;
     Ok(())
 }
 
+#[cfg(feature = "gui")]
+fn remap_ports(
+    instance_dir: &Path,
+    vm_path: &Path,
+    ports: BTreeMap<String, u16>,
+) -> Result<NetworkEnv, CommanderError> {
+    let default_env =
+        Utils::get_network_env(&Utils::get_network_name()).code(ErrorCode::InvalidInput)?;
+    let current_env = Utils::get_server_env().code(ErrorCode::InvalidInput)?;
+    let project_name = get_compose_project_name();
+    for (key, port) in &ports {
+        let is_unchanged = current_env
+            .ports()
+            .iter()
+            .any(|(current_key, current_port)| {
+                *current_key == key.as_str() && current_port == port
+            });
+        if !is_unchanged && !ports::is_port_free(*port, &project_name) {
+            return Err(CommanderError::new(
+                ErrorCode::PortInUse,
+                format!("Port {} for {} is already in use", port, key),
+            ));
+        }
+    }
+
+    let mut metadata = InstanceMetadata::load(instance_dir);
+    metadata.port_overrides.extend(ports);
+    // an override back to the network default is dropped so later default changes apply
+    metadata.port_overrides.retain(|key, port| {
+        !default_env
+            .ports()
+            .iter()
+            .any(|(default_key, default_port)| *default_key == key.as_str() && default_port == port)
+    });
+
+    let mut network_env = default_env;
+    network_env
+        .apply_port_overrides(&metadata.port_overrides)
+        .code(ErrorCode::InvalidInput)?;
+    let mut seen_ports = std::collections::HashSet::new();
+    if let Some((key, port)) = network_env
+        .ports()
+        .into_iter()
+        .find(|(_, port)| !seen_ports.insert(*port))
+    {
+        return Err(CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!("{} can't share port {} with another setting", key, port),
+        ));
+    }
+
+    metadata.save(instance_dir)?;
+    write_port_env(vm_path, &network_env)?;
+    restart_for_ports(vm_path)?;
+    Ok(network_env)
+}
+
+/// Puts rewritten ports into effect on a server that's already up. Compose recreates the running
+/// server services whose published ports changed, and a running QEMU VM is rebooted since its
+/// forwards are fixed at boot.
+#[cfg(feature = "gui")]
+fn restart_for_ports(vm_path: &Path) -> Result<(), CommanderError> {
+    if !vm_path.exists() {
+        return Ok(());
+    }
+    if VmBackend::of(vm_path) == VmBackend::Qemu {
+        let qemu = QemuVm::new(vm_path);
+        if qemu.is_running() {
+            qemu.stop()?;
+            qemu.start(
+                &VmResourceLimits::from_env(vm_path),
+                &get_qemu_port_forwards(),
+                &|_| {},
+            )?;
+        }
+        return Ok(());
+    }
+    let services = Vm::get_running_server_services(vm_path)?;
+    Vm::start_server_services(vm_path, &services)
+}
+
+/// Writes every server port into the VM `.env` and, once installed, the server's own `.env`.
+#[cfg(feature = "gui")]
+fn write_port_env(vm_path: &Path, network_env: &NetworkEnv) -> Result<(), CommanderError> {
+    if !vm_path.exists() {
+        return Ok(());
+    }
+    let values = get_port_env(network_env);
+    update_env_file(vm_path, &values)?;
+    let server_dir = vm_path.join("app").join("server");
+    if server_dir.join(".env").exists() {
+        update_env_file(&server_dir, &values)?;
+    }
+    Ok(())
+}
+
+/// (host, guest) pairs for the server ports a QEMU VM forwards. The server inside the guest keeps
+/// the network's default ports, so remapped ones only change the host side.
+fn get_qemu_port_forwards() -> Vec<(u16, u16)> {
+    let Ok(network_env) = Utils::get_server_env() else {
+        return vec![];
+    };
+    let default_ports = Utils::get_network_env(&Utils::get_network_name())
+        .map(|env| env.ports())
+        .unwrap_or_default();
+    network_env
+        .ports()
+        .into_iter()
+        .map(|(key, port)| {
+            let guest_port = default_ports
+                .iter()
+                .find(|(default_key, _)| *default_key == key)
+                .map_or(port, |(_, default_port)| *default_port);
+            (port, guest_port)
+        })
+        .collect()
+}
+
+fn get_port_env(network_env: &NetworkEnv) -> Vec<(&'static str, String)> {
+    network_env
+        .ports()
+        .into_iter()
+        .map(|(key, port)| (key, port.to_string()))
+        .collect()
+}
+
+#[cfg(feature = "gui")]
 fn get_vm_path(app: &AppHandle) -> PathBuf {
-    Utils::get_absolute_config_instance_dir(app).join("virtual-machine")
+    get_vm_path_in(&Utils::get_absolute_config_instance_dir(app))
 }
 
+#[cfg(feature = "gui")]
 fn get_vm_work_dir(app: &AppHandle) -> PathBuf {
     get_vm_path(app).join("app")
 }
 
+pub fn get_vm_path_in(config_instance_dir: &Path) -> PathBuf {
+    config_instance_dir.join("virtual-machine")
+}
+
+/// Mirrors `DOCKER_COMPOSE_PROJECT_NAME` in src-vue/lib/Server.ts
+pub fn get_compose_project_name() -> String {
+    format!(
+        "{}-{}",
+        Utils::get_network_name(),
+        Utils::get_instance_name()
+    )
+    .to_lowercase()
+    .chars()
+    .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+    .collect()
+}
+
+/// The `local-machine` files to install for a runtime, as paths relative to the VM dir. Whichever
+/// compose variant the runtime needs is installed as the default `docker-compose.yml`.
+fn get_embedded_files(runtime: &ContainerRuntime) -> Vec<(PathBuf, &'static [u8])> {
+    let compose_file = runtime.vm_compose_file();
+    let mut files = vec![];
+    for file in VM_FILES.files() {
+        let relative_path = file.path();
+        let Some(file_name) = relative_path.to_str() else {
+            continue;
+        };
+        if file_name.starts_with("docker-compose") && file_name != compose_file {
+            continue;
+        }
+        let target_path = if file_name == compose_file {
+            PathBuf::from("docker-compose.yml")
+        } else {
+            relative_path.to_path_buf()
+        };
+        files.push((target_path, file.contents()));
+    }
+    files
+}
+
+/// `.env` values that depend on the host rather than the instance, rewritten on every upgrade.
+fn get_host_env(runtime: &ContainerRuntime) -> Vec<(&'static str, String)> {
+    let (uid, gid) = get_uid_gid();
+    // the VM drives the host engine through this socket
+    let socket_path = runtime
+        .socket_path
+        .clone()
+        .unwrap_or(PathBuf::from("/var/run/docker.sock"));
+    vec![
+        ("UID", uid.to_string()),
+        ("GID", gid.to_string()),
+        ("CONTAINER_SOCKET", socket_path.display().to_string()),
+    ]
+}
+
+fn format_env(values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .map(|(key, value)| format!("{}={}\n", key, value))
+        .collect()
+}
+
+/// Replaces the given keys in the VM's `.env`, leaving every other line as it was.
+pub fn update_env_file(vm_path: &Path, values: &[(&str, String)]) -> Result<(), CommanderError> {
+    let env_path = vm_path.join(".env");
+    let env_text = fs::read_to_string(&env_path)
+        .context(format!("Error reading file {}", env_path.display()))?;
+    let mut updated_env_text = env_text
+        .lines()
+        .filter(|line| {
+            let key = line.split('=').next().unwrap_or_default().trim();
+            !values.iter().any(|(replaced_key, _)| *replaced_key == key)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    updated_env_text.push('\n');
+    updated_env_text.push_str(&format_env(values));
+    fs::write(&env_path, updated_env_text)
+        .context(format!("Error writing file {}", env_path.display()))?;
+    Ok(())
+}
+
+/// Embedded files whose contents differ from (or are missing in) the VM dir.
+fn get_changed_files(vm_path: &Path, runtime: &ContainerRuntime) -> Vec<PathBuf> {
+    get_embedded_files(runtime)
+        .into_iter()
+        .filter(|(relative_path, contents)| {
+            !fs::read(vm_path.join(relative_path)).is_ok_and(|on_disk| on_disk == *contents)
+        })
+        .map(|(relative_path, _)| relative_path)
+        .collect()
+}
+
+/// Walking the synced chain data takes a while, so a measured size is reused for this long.
+const DIR_SIZE_MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+static DIR_SIZES: Mutex<BTreeMap<PathBuf, (Instant, u64)>> = Mutex::new(BTreeMap::new());
+
+/// Disk space the files under `path` take up, which for sparse database files can be far less
+/// than their length.
+fn get_dir_size(path: &Path) -> u64 {
+    let mut sizes = DIR_SIZES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((measured_at, size)) = sizes.get(path) {
+        if measured_at.elapsed() < DIR_SIZE_MAX_AGE {
+            return *size;
+        }
+    }
+    let size = walkdir::WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| get_allocated_bytes(&metadata))
+        .sum();
+    sizes.insert(path.to_path_buf(), (Instant::now(), size));
+    size
+}
+
+fn forget_dir_size(path: &Path) {
+    let mut sizes = DIR_SIZES.lock().unwrap_or_else(|e| e.into_inner());
+    sizes.retain(|cached, _| !cached.starts_with(path));
+}
+
+#[cfg(unix)]
+fn get_allocated_bytes(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // st_blocks is always in 512 byte units
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn get_allocated_bytes(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(feature = "gui")]
+fn get_uptime_seconds(started_at: &str) -> Option<u64> {
+    let started_at =
+        time::OffsetDateTime::parse(started_at, &time::format_description::well_known::Rfc3339)
+            .ok()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    u64::try_from(now - started_at.unix_timestamp()).ok()
+}
+
+fn output_reporter(phase: VmPhase, on_progress: &dyn Fn(VmProgress)) -> impl Fn(String) + '_ {
+    move |line: String| {
+        on_progress(VmProgress {
+            phase: phase.clone(),
+            output: Some(line),
+            error: None,
+        })
+    }
+}
+
+/// Where each log source's compose project lives, and the services to read from it. The server
+/// stack only exists once the app has installed it into the VM, and a QEMU VM keeps it inside its
+/// own disk.
+#[cfg(feature = "gui")]
+fn get_log_sources(vm_path: &Path) -> Vec<(VmLogSource, PathBuf, Vec<&'static str>)> {
+    let mut sources = vec![(VmLogSource::Vm, vm_path.to_path_buf(), vec!["vm"])];
+    let server_dir = vm_path.join("app").join("server");
+    if server_dir.exists() && VmBackend::of(vm_path) == VmBackend::Docker {
+        sources.push((VmLogSource::Server, server_dir, vec![]));
+    }
+    sources
+}
+
+#[cfg(feature = "gui")]
+fn get_log_export_prefix() -> String {
+    format!("{}-vm-logs-", get_compose_project_name())
+}
+
+/// Log exports still in the temp dir.
+#[cfg(feature = "gui")]
+fn get_log_export_dirs() -> Vec<PathBuf> {
+    let prefix = get_log_export_prefix();
+    let Ok(entries) = fs::read_dir(std::env::temp_dir()) else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect()
+}
+
+fn spawn_line_reader(
+    reader: impl Read + Send + 'static,
+    on_line: impl Fn(String) + Send + 'static,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            on_line(line);
+        }
+    })
+}
+
+fn compose_error(binary: &str, args: &[&str], stderr: &str) -> CommanderError {
+    let code = if stderr.contains("Cannot connect to the Docker daemon")
+        || stderr.contains("Cannot connect to Podman")
+    {
+        ErrorCode::DockerNotRunning
+    } else if stderr.contains("port is already allocated")
+        || stderr.contains("address already in use")
+    {
+        ErrorCode::PortInUse
+    } else {
+        ErrorCode::DockerCommandFailed
+    };
+    CommanderError::new(
+        code,
+        format!("{} compose {:?} failed: {}", binary, args, stderr),
+    )
+}
+
+fn get_runtime() -> Result<ContainerRuntime, CommanderError> {
+    ContainerRuntime::get().ok_or_else(|| {
+        CommanderError::new(
+            ErrorCode::DockerNotRunning,
+            "No container runtime is running. Start docker, podman or nerdctl",
+        )
+    })
+}
+
+/// The project name the VM was created with, which is written to its `.env`.
+fn get_env_project_name(vm_path: &Path) -> Option<String> {
+    get_env_value(vm_path, "COMPOSE_PROJECT_NAME")
+}
+
+pub fn get_env_value(vm_path: &Path, key: &str) -> Option<String> {
+    dotenvy::from_path_iter(vm_path.join(".env"))
+        .ok()?
+        .flatten()
+        .find(|(env_key, _)| env_key == key)
+        .map(|(_, value)| value)
+}
+
 #[cfg(unix)]
 fn get_uid_gid() -> (u32, u32) {
     (unsafe { libc::getuid() }, unsafe { libc::getgid() })
@@ -79,12 +879,30 @@ fn get_uid_gid() -> (u32, u32) {
 }
 
 impl Vm {
-    pub fn activate(vm_path: &PathBuf, work_dir: &PathBuf) -> anyhow::Result<Vm, String> {
+    pub fn activate(
+        vm_path: &PathBuf,
+        work_dir: &PathBuf,
+        on_progress: &dyn Fn(VmProgress),
+    ) -> Result<Vm, CommanderError> {
         if !vm_path.exists() {
-            return Err(format!("VM path {} does not exist", vm_path.display()));
+            return Err(CommanderError::new(
+                ErrorCode::VmNotFound,
+                format!("VM path {} does not exist", vm_path.display()),
+            ));
         }
-        Self::run_compose_command(vm_path, &["up", "-d"])?;
-        Self::run_compose_command(work_dir, &["up", "-d"])?;
+        if VmBackend::of(vm_path) == VmBackend::Qemu {
+            let ssh_port = QemuVm::new(vm_path).start(
+                &VmResourceLimits::from_env(vm_path),
+                &get_qemu_port_forwards(),
+                on_progress,
+            )?;
+            return Ok(Vm { ssh_port });
+        }
+        on_progress(VmProgress::new(VmPhase::Starting));
+        let on_output = output_reporter(VmPhase::Starting, on_progress);
+        Self::run_compose_command_streaming(vm_path, &["up", "-d"], &on_output)?;
+        Self::run_compose_command_streaming(work_dir, &["up", "-d"], &on_output)?;
+        on_progress(VmProgress::new(VmPhase::ResolvingPort));
         Self::get_vm(vm_path)
     }
 
@@ -92,88 +910,683 @@ impl Vm {
         vm_path: PathBuf,
         work_dir: PathBuf,
         mut env_text: String,
-    ) -> anyhow::Result<Vm, String> {
+        limits: VmResourceLimits,
+        on_progress: &dyn Fn(VmProgress),
+    ) -> Result<Vm, CommanderError> {
+        limits.validate(&vm_path)?;
+        let backend = VmBackend::detect().ok_or_else(|| {
+            CommanderError::new(
+                ErrorCode::DockerNotRunning,
+                "No container runtime is running. Start docker, podman or nerdctl, or install QEMU",
+            )
+        })?;
+        let runtime = match backend {
+            VmBackend::Docker => Some(get_runtime()?),
+            VmBackend::Qemu => None,
+        };
+        on_progress(VmProgress::new(VmPhase::WritingFiles));
         if vm_path.exists() {
             std::fs::remove_dir_all(&vm_path)
-                .map_err(|e| format!("Error removing VM directory {}: {}", vm_path.display(), e))?;
+                .context(format!("Error removing VM directory {}", vm_path.display()))?;
         }
 
         if !vm_path.exists() {
             fs::create_dir_all(&vm_path)
-                .map_err(|e| format!("Error creating directory {}: {}", vm_path.display(), e))?;
+                .context(format!("Error creating directory {}", vm_path.display()))?;
         }
 
         let env_path = vm_path.join(".env");
-        let (uid, gid) = get_uid_gid();
-        env_text.push_str(&format!("\nUID={}\nGID={}\n", uid, gid));
+        env_text.push('\n');
+        env_text.push_str(&format_env(&[(
+            "VM_BACKEND",
+            backend.env_value().to_string(),
+        )]));
+        if let Some(runtime) = &runtime {
+            env_text.push_str(&format_env(&get_host_env(runtime)));
+        }
+        env_text.push_str(&format_env(&limits.to_env()));
+        if let Ok(network_env) = Utils::get_server_env() {
+            env_text.push_str(&format_env(&get_port_env(&network_env)));
+        }
         fs::write(&env_path, env_text)
-            .map_err(|e| format!("Error writing file {}: {}", env_path.display(), e))?;
+            .context(format!("Error writing file {}", env_path.display()))?;
 
-        for file in VM_FILES.files() {
-            let relative_path = file.path();
+        for (relative_path, contents) in runtime.iter().flat_map(get_embedded_files) {
             let target_path = vm_path.join(relative_path);
-            fs::write(&target_path, file.contents())
-                .map_err(|e| format!("Error copying file to {}: {}", target_path.display(), e))?;
+            fs::write(&target_path, contents)
+                .context(format!("Error copying file to {}", target_path.display()))?;
         }
 
         if !work_dir.exists() {
             fs::create_dir_all(&work_dir)
-                .map_err(|e| format!("Error creating directory {}: {}", work_dir.display(), e))?;
+                .context(format!("Error creating directory {}", work_dir.display()))?;
+        }
+
+        if backend == VmBackend::Qemu {
+            return Self::create_qemu(&vm_path, &limits, on_progress);
         }
-        Self::run_compose_command(&vm_path, &["up", "--build", "-d", "--wait"])?;
+
+        on_progress(VmProgress::new(VmPhase::BuildingImage));
+        let on_output = output_reporter(VmPhase::BuildingImage, on_progress);
+        Self::run_compose_command_streaming(&vm_path, &["build"], &on_output)?;
+
+        on_progress(VmProgress::new(VmPhase::Starting));
+        let on_output = output_reporter(VmPhase::Starting, on_progress);
+        Self::run_compose_command_streaming(&vm_path, &["up", "-d"], &on_output)?;
+
+        // a second `up` is a no-op for the running container but blocks until the healthcheck passes
+        on_progress(VmProgress::new(VmPhase::WaitingForHealth));
+        let on_output = output_reporter(VmPhase::WaitingForHealth, on_progress);
+        Self::run_compose_command_streaming(&vm_path, &["up", "-d", "--wait"], &on_output)?;
+
+        on_progress(VmProgress::new(VmPhase::ResolvingPort));
         Self::get_vm(&vm_path)
     }
 
-    pub fn get_vm(vm_path: &PathBuf) -> anyhow::Result<Vm, String> {
+    /// Brings an existing VM up to the embedded `local-machine` files without touching the `app`
+    /// work dir, so synced chain data survives. Only changed files are rewritten, and the `.env`
+    /// keeps everything but the host specific values, which are refreshed.
+    pub fn upgrade(
+        vm_path: &PathBuf,
+        on_progress: &dyn Fn(VmProgress),
+    ) -> Result<Vm, CommanderError> {
         if !vm_path.exists() {
-            return Err(format!("VM path {} does not exist", vm_path.display()));
+            return Err(CommanderError::new(
+                ErrorCode::VmNotFound,
+                format!("VM path {} does not exist", vm_path.display()),
+            ));
+        }
+        if VmBackend::of(vm_path) == VmBackend::Qemu {
+            return Err(CommanderError::new(
+                ErrorCode::InvalidInput,
+                "A QEMU VM isn't built from the app's VM files, so there is nothing to upgrade",
+            ));
+        }
+        on_progress(VmProgress::new(VmPhase::WritingFiles));
+        let runtime = get_runtime()?;
+        let changed_files = get_changed_files(vm_path, &runtime);
+        for (relative_path, contents) in get_embedded_files(&runtime) {
+            if !changed_files.contains(&relative_path) {
+                continue;
+            }
+            log::info!("Upgrading VM file {}", relative_path.display());
+            let target_path = vm_path.join(relative_path);
+            fs::write(&target_path, contents)
+                .context(format!("Error copying file to {}", target_path.display()))?;
+        }
+
+        update_env_file(vm_path, &get_host_env(&runtime))?;
+
+        on_progress(VmProgress::new(VmPhase::BuildingImage));
+        let on_output = output_reporter(VmPhase::BuildingImage, on_progress);
+        Self::run_compose_command_streaming(vm_path, &["build"], &on_output)?;
+
+        on_progress(VmProgress::new(VmPhase::Starting));
+        let on_output = output_reporter(VmPhase::Starting, on_progress);
+        Self::run_compose_command_streaming(
+            vm_path,
+            &["up", "-d", "--force-recreate"],
+            &on_output,
+        )?;
+
+        on_progress(VmProgress::new(VmPhase::WaitingForHealth));
+        let on_output = output_reporter(VmPhase::WaitingForHealth, on_progress);
+        Self::run_compose_command_streaming(vm_path, &["up", "-d", "--wait"], &on_output)?;
+
+        on_progress(VmProgress::new(VmPhase::ResolvingPort));
+        Self::get_vm(vm_path)
+    }
+
+    pub fn get_vm(vm_path: &PathBuf) -> Result<Vm, CommanderError> {
+        if !vm_path.exists() {
+            return Err(CommanderError::new(
+                ErrorCode::VmNotFound,
+                format!("VM path {} does not exist", vm_path.display()),
+            ));
+        }
+        if VmBackend::of(vm_path) == VmBackend::Qemu {
+            let ssh_port = QemuVm::new(vm_path).get_ssh_port()?;
+            return Ok(Vm { ssh_port });
+        }
+        if let Some(api) = DockerApi::detect() {
+            match Self::get_vm_from_api(&api, vm_path) {
+                Ok(vm) => return Ok(vm),
+                Err(e) if e.code == ErrorCode::DockerNotRunning => {
+                    log::warn!("{}. Falling back to the docker cli", e);
+                }
+                Err(e) => return Err(e),
+            }
         }
         let port_output = Self::run_compose_command(vm_path, &["port", "vm", "22"])?;
-        let port = port_output
-            .split(':')
-            .next_back()
-            .ok_or("Failed to parse port")?;
+        let port = port_output.split(':').next_back().ok_or_else(|| {
+            CommanderError::new(ErrorCode::DockerCommandFailed, "Failed to parse port")
+        })?;
         log::info!("VM SSH port: {}", port);
-        let vm_port = port.parse::<u16>().map_err(|e| e.to_string())?;
+        let vm_port = port
+            .parse::<u16>()
+            .map_err(|e| CommanderError::new(ErrorCode::DockerCommandFailed, e))?;
         Ok(Vm { ssh_port: vm_port })
     }
 
-    pub fn destroy(vm_path: &PathBuf) -> anyhow::Result<(), String> {
-        log::info!("Removing local VM at {}", vm_path.display());
+    /// The rest of [`Vm::create`] for a QEMU VM, once the `.env` and work dir are written. The ssh
+    /// key `Security` generated comes through the `.env`, the same as for the docker image build.
+    fn create_qemu(
+        vm_path: &Path,
+        limits: &VmResourceLimits,
+        on_progress: &dyn Fn(VmProgress),
+    ) -> Result<Vm, CommanderError> {
+        let ssh_pubkey = get_env_value(vm_path, "SSH_PUBKEY").ok_or_else(|| {
+            CommanderError::new(ErrorCode::InvalidInput, "The VM env has no SSH_PUBKEY")
+        })?;
+        let instance_id = get_env_project_name(vm_path).unwrap_or_else(get_compose_project_name);
+        let qemu = QemuVm::new(vm_path);
+
+        on_progress(VmProgress::new(VmPhase::BuildingImage));
+        let on_output = output_reporter(VmPhase::BuildingImage, on_progress);
+        qemu.create(&ssh_pubkey, &instance_id, limits, &on_output)?;
+
+        let ssh_port = qemu.start(limits, &get_qemu_port_forwards(), on_progress)?;
+        Ok(Vm { ssh_port })
+    }
+
+    fn get_vm_from_api(api: &DockerApi, vm_path: &Path) -> Result<Vm, CommanderError> {
+        let project_name = get_env_project_name(vm_path).unwrap_or_else(get_compose_project_name);
+        let container = api
+            .get_project_service(&project_name, "vm")?
+            .ok_or_else(|| {
+                CommanderError::new(
+                    ErrorCode::DockerCommandFailed,
+                    format!("The VM container for {} has not been created", project_name),
+                )
+            })?;
+
+        let state = api.get_container_state(&container.id)?;
+        if !state.running {
+            let mut message = format!(
+                "The VM container is {} (exit code {})",
+                state.status, state.exit_code
+            );
+            if !state.error.is_empty() {
+                message.push_str(&format!(": {}", state.error));
+            }
+            return Err(CommanderError::new(ErrorCode::DockerCommandFailed, message));
+        }
+        if let Some(health) = state.health.filter(|h| h.status == "unhealthy") {
+            return Err(CommanderError::new(
+                ErrorCode::DockerCommandFailed,
+                format!(
+                    "The VM container is {} ({})",
+                    health.status, container.status
+                ),
+            ));
+        }
+
+        let ssh_port = container.public_port(22).ok_or_else(|| {
+            CommanderError::new(
+                ErrorCode::DockerCommandFailed,
+                "The VM container has no published ssh port",
+            )
+        })?;
+        log::info!("VM SSH port: {}", ssh_port);
+        Ok(Vm { ssh_port })
+    }
+
+    /// Saves new limits to the `.env` and pushes the cpu and memory ones to the running container
+    /// with `<engine> update`. Lifting a limit can't be done live, so the running container is
+    /// recreated instead. QEMU fixes cpu and memory at boot, so a running QEMU VM is restarted to
+    /// apply them.
+    pub fn set_limits(vm_path: &PathBuf, limits: &VmResourceLimits) -> Result<(), CommanderError> {
+        if !vm_path.exists() {
+            return Err(CommanderError::new(
+                ErrorCode::VmNotFound,
+                format!("VM path {} does not exist", vm_path.display()),
+            ));
+        }
+        limits.validate(vm_path)?;
+        let previous_limits = VmResourceLimits::from_env(vm_path);
+        update_env_file(vm_path, &limits.to_env())?;
+        if VmBackend::of(vm_path) == VmBackend::Qemu {
+            if previous_limits == *limits {
+                return Ok(());
+            }
+            return QemuVm::new(vm_path).apply_limits(limits, &get_qemu_port_forwards());
+        }
+
+        let container_id = Self::run_compose_command(vm_path, &["ps", "-q", "vm"])?;
+        if container_id.is_empty() {
+            return Ok(());
+        }
+        // `update` can't lift a limit on every runtime (docker ignores 0), so lifting one has
+        // compose recreate the container, which reads 0 in the `.env` as unlimited
+        let is_lifted = (previous_limits.cpus.is_some() && limits.cpus.is_none())
+            || (previous_limits.memory_bytes.is_some() && limits.memory_bytes.is_none());
+        if is_lifted {
+            Self::run_compose_command(vm_path, &["up", "-d", "vm"])?;
+            return Ok(());
+        }
+
+        let mut args = vec!["update".to_string()];
+        if let Some(cpus) = limits.cpus {
+            args.extend(["--cpus".to_string(), cpus.to_string()]);
+        }
+        if let Some(memory_bytes) = limits.memory_bytes {
+            // swap is capped to the same value so the limit can't be sidestepped
+            args.extend([
+                "--memory".to_string(),
+                memory_bytes.to_string(),
+                "--memory-swap".to_string(),
+                memory_bytes.to_string(),
+            ]);
+        }
+        if args.len() == 1 {
+            return Ok(());
+        }
+        args.push(container_id);
+        let runtime = get_runtime()?;
+        let output = std::process::Command::new(runtime.kind.binary())
+            .args(&args)
+            .output()
+            .context("Error running container update")
+            .code(ErrorCode::DockerNotRunning)?;
+        if !output.status.success() {
+            return Err(CommanderError::new(
+                ErrorCode::DockerCommandFailed,
+                format!(
+                    "Unable to update the VM limits: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Everything that can be learned about the VM without changing it. Missing pieces (ie, stats
+    /// when the runtime serves no API socket) are left empty rather than failing the whole status.
+    #[cfg(feature = "gui")]
+    pub fn get_status(vm_path: &Path) -> Result<VmStatus, CommanderError> {
+        let mut status = VmStatus::default();
         if !vm_path.exists() {
+            return Ok(status);
+        }
+        status.exists = true;
+        status.limits = VmResourceLimits::from_env(vm_path);
+        status.app_disk_usage_bytes = get_dir_size(&vm_path.join("app"));
+        let backend = VmBackend::of(vm_path);
+        status.backend = Some(backend);
+        if backend == VmBackend::Qemu {
+            let qemu = QemuVm::new(vm_path);
+            let state = if qemu.is_running() {
+                "running"
+            } else {
+                "exited"
+            };
+            status.state = Some(state.to_string());
+            status.uptime_seconds = qemu.get_uptime_seconds();
+            status.ssh_port = qemu.get_ssh_port().ok();
+            return Ok(status);
+        }
+
+        let Some(runtime) = ContainerRuntime::get() else {
+            return Ok(status);
+        };
+        status.changed_files = get_changed_files(vm_path, &runtime)
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+
+        let Some(api) = DockerApi::detect() else {
+            status.ssh_port = Self::get_vm(&vm_path.to_path_buf())
+                .ok()
+                .map(|vm| vm.ssh_port);
+            return Ok(status);
+        };
+        let project_name = get_env_project_name(vm_path).unwrap_or_else(get_compose_project_name);
+        let Some(container) = api.get_project_service(&project_name, "vm")? else {
+            return Ok(status);
+        };
+        let inspect = api.inspect_container(&container.id)?;
+        status.ssh_port = container.public_port(22);
+        status.state = Some(inspect.state.status);
+        status.health = inspect.state.health.map(|h| h.status);
+        status.image = Some(inspect.config.image);
+        status.image_id = Some(inspect.image);
+        if inspect.state.running {
+            status.uptime_seconds = get_uptime_seconds(&inspect.state.started_at);
+            status.started_at = Some(inspect.state.started_at);
+            match api.get_container_stats(&container.id) {
+                Ok(stats) => {
+                    status.cpu_percent = Some(stats.cpu_percent);
+                    status.memory_usage_bytes = Some(stats.memory_usage_bytes);
+                    status.memory_limit_bytes = Some(stats.memory_limit_bytes);
+                }
+                Err(e) => log::warn!("Unable to read local VM stats: {}", e),
+            }
+        }
+        Ok(status)
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn get_logs(vm_path: &Path, lines: u32) -> Result<Vec<VmLogLine>, CommanderError> {
+        if !vm_path.exists() {
+            return Err(CommanderError::new(
+                ErrorCode::VmNotFound,
+                format!("VM path {} does not exist", vm_path.display()),
+            ));
+        }
+        if VmBackend::of(vm_path) == VmBackend::Qemu {
+            let console_lines = QemuVm::new(vm_path).get_console_tail(lines as usize);
+            return Ok(console_lines
+                .into_iter()
+                .map(|line| VmLogLine {
+                    source: VmLogSource::Vm,
+                    line,
+                })
+                .collect());
+        }
+        let tail = lines.to_string();
+        let mut logs = vec![];
+        for (source, dir, services) in get_log_sources(vm_path) {
+            let mut args = vec!["logs", "--no-color", "--timestamps", "--tail", &tail];
+            args.extend(services);
+            // one source failing (ie, the server stack not created yet) shouldn't hide the others
+            match Self::run_compose_command(&dir, &args) {
+                Ok(output) => logs.extend(output.lines().map(|line| VmLogLine {
+                    source,
+                    line: line.to_string(),
+                })),
+                Err(e) => logs.push(VmLogLine {
+                    source,
+                    line: format!("Unable to read these logs: {}", e),
+                }),
+            }
+        }
+        Ok(logs)
+    }
+
+    /// Starts a `compose logs --follow` per log source, or a `tail` of a QEMU VM's console, replacing
+    /// any already running. Lines are handed to `on_line` from reader threads as they arrive.
+    #[cfg(feature = "gui")]
+    pub fn follow_logs(
+        vm_path: &Path,
+        on_line: impl Fn(VmLogLine) + Send + Sync + 'static,
+    ) -> Result<(), CommanderError> {
+        if !vm_path.exists() {
+            return Err(CommanderError::new(
+                ErrorCode::VmNotFound,
+                format!("VM path {} does not exist", vm_path.display()),
+            ));
+        }
+        Self::stop_following_logs();
+        let mut followers = LOG_FOLLOWERS.lock().unwrap_or_else(|e| e.into_inner());
+        if VmBackend::of(vm_path) == VmBackend::Qemu {
+            let console_log_path = QemuVm::new(vm_path).get_console_log_path();
+            let mut child = std::process::Command::new("tail")
+                .args(["-n", "0", "-F"])
+                .arg(&console_log_path)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .context(format!("Error following {}", console_log_path.display()))
+                .code(ErrorCode::QemuCommandFailed)?;
+            if let Some(stdout) = child.stdout.take() {
+                spawn_line_reader(stdout, move |line| {
+                    on_line(VmLogLine {
+                        source: VmLogSource::Vm,
+                        line,
+                    })
+                });
+            }
+            followers.push(child);
             return Ok(());
         }
-        let server_path = vm_path.join("server");
+        let runtime = get_runtime()?;
+        let on_line = std::sync::Arc::new(on_line);
+        for (source, dir, services) in get_log_sources(vm_path) {
+            let mut child = runtime
+                .compose_command()
+                .args([
+                    "logs",
+                    "--follow",
+                    "--no-color",
+                    "--timestamps",
+                    "--tail",
+                    "0",
+                ])
+                .args(services)
+                .current_dir(&dir)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|e| {
+                    CommanderError::new(
+                        ErrorCode::DockerNotRunning,
+                        format!(
+                            "Failed to follow {} compose logs at {}: {}",
+                            runtime.kind.binary(),
+                            dir.display(),
+                            e
+                        ),
+                    )
+                })?;
+            for pipe in [
+                child
+                    .stdout
+                    .take()
+                    .map(|x| Box::new(x) as Box<dyn Read + Send>),
+                child
+                    .stderr
+                    .take()
+                    .map(|x| Box::new(x) as Box<dyn Read + Send>),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                let on_line = on_line.clone();
+                spawn_line_reader(pipe, move |line| on_line(VmLogLine { source, line }));
+            }
+            followers.push(child);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn stop_following_logs() {
+        let mut followers = LOG_FOLLOWERS.lock().unwrap_or_else(|e| e.into_inner());
+        for mut child in followers.drain(..) {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    /// Writes each log source to its own file in a new temp dir and returns it. Exports left
+    /// behind by an earlier run that never removed them are cleared first.
+    #[cfg(feature = "gui")]
+    pub fn export_logs(vm_path: &Path, lines: u32) -> Result<PathBuf, CommanderError> {
+        let logs = Self::get_logs(vm_path, lines)?;
+        for stale_dir in get_log_export_dirs() {
+            if let Err(e) = fs::remove_dir_all(&stale_dir) {
+                log::warn!("Unable to remove {}: {}", stale_dir.display(), e);
+            }
+        }
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_millis());
+        let export_dir =
+            std::env::temp_dir().join(format!("{}{}", get_log_export_prefix(), millis));
+        fs::create_dir_all(&export_dir)
+            .context(format!("Error creating directory {}", export_dir.display()))?;
+        for (source, _, _) in get_log_sources(vm_path) {
+            let text = logs
+                .iter()
+                .filter(|log| log.source == source)
+                .map(|log| format!("{}\n", log.line))
+                .collect::<String>();
+            let path = export_dir.join(source.file_name());
+            fs::write(&path, text).context(format!("Error writing file {}", path.display()))?;
+        }
+        Ok(export_dir)
+    }
+
+    /// Deletes a dir from [`Vm::export_logs`]. Anything else is refused, since the path comes from
+    /// the webview.
+    #[cfg(feature = "gui")]
+    pub fn remove_log_export(export_dir: &Path) -> Result<(), CommanderError> {
+        if !get_log_export_dirs().iter().any(|dir| dir == export_dir) {
+            return Err(CommanderError::new(
+                ErrorCode::InvalidInput,
+                format!("{} is not a VM log export", export_dir.display()),
+            ));
+        }
+        fs::remove_dir_all(export_dir)
+            .context(format!("Error removing directory {}", export_dir.display()))?;
+        Ok(())
+    }
+
+    /// Stops the containers, or powers off a QEMU VM, but keeps the images and files, so `activate`
+    /// can bring them back.
+    pub fn stop(vm_path: &PathBuf) -> Result<(), CommanderError> {
+        if !vm_path.exists() {
+            return Ok(());
+        }
+        if VmBackend::of(vm_path) == VmBackend::Qemu {
+            return QemuVm::new(vm_path).stop();
+        }
+        let server_path = vm_path.join("app").join("server");
         if server_path.exists() {
-            Self::run_compose_command(&server_path, &["down", "--rmi", "all"])?;
+            Self::run_compose_command(&server_path, &["down"])?;
+        }
+        Self::run_compose_command(vm_path, &["down"])?;
+        Ok(())
+    }
+
+    /// The server services that are up on the host engine, for bringing the same ones back with
+    /// [`Vm::start_server_services`]. Always empty for a QEMU VM, whose server runs in the guest.
+    pub fn get_running_server_services(vm_path: &Path) -> Result<Vec<String>, CommanderError> {
+        let server_path = vm_path.join("app").join("server");
+        if VmBackend::of(vm_path) == VmBackend::Qemu || !server_path.join(".env").exists() {
+            return Ok(vec![]);
+        }
+        let services = Self::run_compose_command(&server_path, &["ps", "--services"])?;
+        Ok(services.lines().map(|line| line.to_string()).collect())
+    }
+
+    /// Runs `compose up` for the named server services. Naming them brings up their profiles,
+    /// which a bare `up` would leave out.
+    pub fn start_server_services(
+        vm_path: &Path,
+        services: &[String],
+    ) -> Result<(), CommanderError> {
+        if services.is_empty() {
+            return Ok(());
+        }
+        let mut args = vec!["up", "-d"];
+        args.extend(services.iter().map(|service| service.as_str()));
+        Self::run_compose_command(&vm_path.join("app").join("server"), &args)?;
+        Ok(())
+    }
+
+    pub fn destroy(vm_path: &PathBuf) -> Result<(), CommanderError> {
+        log::info!("Removing local VM at {}", vm_path.display());
+        if !vm_path.exists() {
+            return Ok(());
         }
+        if VmBackend::of(vm_path) == VmBackend::Qemu {
+            QemuVm::new(vm_path).kill()?;
+        } else {
+            let server_path = vm_path.join("app").join("server");
+            if server_path.exists() {
+                Self::run_compose_command(&server_path, &["down", "--rmi", "all"])?;
+            }
 
-        Self::run_compose_command(vm_path, &["down", "--rmi", "all"])?;
+            Self::run_compose_command(vm_path, &["down", "--rmi", "all"])?;
+        }
         std::fs::remove_dir_all(vm_path)
-            .map_err(|e| format!("Error removing VM directory {}: {}", vm_path.display(), e))?;
+            .context(format!("Error removing VM directory {}", vm_path.display()))?;
+        forget_dir_size(vm_path);
 
         Ok(())
     }
 
-    fn run_compose_command(vm_path: &PathBuf, args: &[&str]) -> anyhow::Result<String, String> {
-        let output = Command::new("docker")
-            .args(["compose"].iter().chain(args))
+    fn run_compose_command(vm_path: &PathBuf, args: &[&str]) -> Result<String, CommanderError> {
+        let runtime = get_runtime()?;
+        let binary = runtime.kind.binary();
+        let output = runtime
+            .compose_command()
+            .args(args)
             .current_dir(vm_path)
             .output()
             .map_err(|e| {
-                format!(
-                    "Failed to run docker compose: {} at {:?}. {:?}",
-                    e, vm_path, args
+                CommanderError::new(
+                    ErrorCode::DockerNotRunning,
+                    format!(
+                        "Failed to run {} compose: {} at {:?}. {:?}",
+                        binary, e, vm_path, args
+                    ),
                 )
             })?;
 
         if !output.status.success() {
-            return Err(format!(
-                "docker compose {:?} failed: {}",
-                args,
-                String::from_utf8_lossy(&output.stderr)
-            ));
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(compose_error(binary, args, &stderr));
         }
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
     }
+
+    /// Like [`Vm::run_compose_command`], but hands each line of stdout and stderr to `on_output` as
+    /// it arrives. Build output in particular goes to stderr.
+    fn run_compose_command_streaming(
+        vm_path: &PathBuf,
+        args: &[&str],
+        on_output: &dyn Fn(String),
+    ) -> Result<(), CommanderError> {
+        let runtime = get_runtime()?;
+        let binary = runtime.kind.binary();
+        let spawn_error = |e: std::io::Error| {
+            CommanderError::new(
+                ErrorCode::DockerNotRunning,
+                format!(
+                    "Failed to run {} compose: {} at {:?}. {:?}",
+                    binary, e, vm_path, args
+                ),
+            )
+        };
+        let mut child = runtime
+            .compose_command()
+            .args(args)
+            .current_dir(vm_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(spawn_error)?;
+
+        let (tx, rx) = std::sync::mpsc::channel::<String>();
+        let mut readers = vec![];
+        if let Some(stdout) = child.stdout.take() {
+            let tx = tx.clone();
+            readers.push(spawn_line_reader(stdout, move |line| {
+                let _ = tx.send(line);
+            }));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            let tx = tx.clone();
+            readers.push(spawn_line_reader(stderr, move |line| {
+                let _ = tx.send(line);
+            }));
+        }
+        drop(tx);
+
+        let mut output = vec![];
+        for line in rx {
+            on_output(line.clone());
+            output.push(line);
+        }
+        for reader in readers {
+            let _ = reader.join();
+        }
+        let status = child.wait().map_err(spawn_error)?;
+        if !status.success() {
+            return Err(compose_error(binary, args, &output.join("\n")));
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,734 @@
+use crate::error::{CommanderError, ErrorCode, ResultExt};
+use crate::vm::{self, VmPhase, VmProgress, VmResourceLimits};
+use std::fs;
+use std::io::Read;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+#[cfg(feature = "gui")]
+use std::time::SystemTime;
+use std::time::{Duration, Instant};
+
+/// The cloud image the disk is layered on, overridable with `COMMANDER_VM_IMAGE_URL` for hosts
+/// that have to pull from a mirror.
+const IMAGE_URL_BASE: &str =
+    "https://cloud-images.ubuntu.com/minimal/releases/noble/release/ubuntu-24.04-minimal-cloudimg";
+
+/// Published beside the image, with a detached `.gpg` signature
+const SUMS_FILE: &str = "SHA256SUMS";
+/// Where Ubuntu's `ubuntu-cloudimage-keyring` package installs the key the sums are signed with.
+/// `COMMANDER_VM_IMAGE_KEYRING` points elsewhere, ie, to go with a mirror.
+const IMAGE_KEYRING_PATH: &str = "/usr/share/keyrings/ubuntu-cloudimage-keyring.gpg";
+
+const BASE_IMAGE_FILE: &str = "base.img";
+/// Kept in the `app` work dir so snapshots and disk usage cover it like the docker VM's bind mount
+const DISK_FILE: &str = "app/disk.qcow2";
+const SEED_DIR: &str = "cloud-init";
+const SEED_FILE: &str = "seed.iso";
+const PID_FILE: &str = "qemu.pid";
+const QMP_SOCKET_FILE: &str = "qmp.sock";
+const CONSOLE_LOG_FILE: &str = "console.log";
+
+const DEFAULT_MEMORY_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+/// qcow2 only allocates what the guest writes, so an unlimited disk is just a large virtual size
+const DEFAULT_DISK_BYTES: u64 = 1024 * 1024 * 1024 * 1024;
+
+/// How long to wait for sshd on a boot. Emulation without KVM is several times slower, and the
+/// first boot also runs cloud-init.
+const KVM_BOOT_TIMEOUT: Duration = Duration::from_secs(180);
+const TCG_BOOT_TIMEOUT: Duration = Duration::from_secs(900);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// The local VM as a QEMU machine booted from an Ubuntu cloud image, for Linux hosts that won't
+/// run privileged systemd containers. cloud-init installs the ssh key, and the ssh and server
+/// ports are forwarded from the host through user-mode networking. Runs under KVM when /dev/kvm
+/// can be opened, and falls back to emulation (TCG) when it can't.
+pub struct QemuVm {
+    vm_path: PathBuf,
+}
+
+impl QemuVm {
+    pub fn new(vm_path: &Path) -> Self {
+        Self {
+            vm_path: vm_path.to_path_buf(),
+        }
+    }
+
+    /// Whether the qemu binary for this machine's architecture is on the PATH.
+    pub fn is_available() -> bool {
+        get_qemu_binary().is_some_and(|binary| {
+            Command::new(binary)
+                .arg("--version")
+                .output()
+                .is_ok_and(|output| output.status.success())
+        })
+    }
+
+    pub fn has_kvm() -> bool {
+        fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/kvm")
+            .is_ok()
+    }
+
+    /// Downloads the cloud image, layers the VM's disk on it and writes the cloud-init seed. The
+    /// VM dir and its `.env` must already exist.
+    pub fn create(
+        &self,
+        ssh_pubkey: &str,
+        instance_id: &str,
+        limits: &VmResourceLimits,
+        on_output: &dyn Fn(String),
+    ) -> Result<(), CommanderError> {
+        let image_path = self.vm_path.join(BASE_IMAGE_FILE);
+        let image_url = get_image_url()?;
+        // checked before the download so a host that can't verify the image finds out right away
+        let keyring = get_image_keyring()?;
+        on_output(format!("Downloading {}", image_url));
+        let download_path = image_path.with_extension("img.part");
+        download(&image_url, &download_path).context("Error downloading the VM image")?;
+        on_output("Verifying the VM image".to_string());
+        if let Err(e) = self.verify_image(&image_url, &download_path, &keyring) {
+            let _ = fs::remove_file(&download_path);
+            return Err(e);
+        }
+        fs::rename(&download_path, &image_path)
+            .context(format!("Error writing file {}", image_path.display()))?;
+
+        // the backing path is relative to the overlay, so the VM dir can move
+        on_output("Creating the VM disk".to_string());
+        let disk_size = limits
+            .reserved_disk_bytes
+            .unwrap_or(DEFAULT_DISK_BYTES)
+            .to_string();
+        let backing_path = format!("../{}", BASE_IMAGE_FILE);
+        run(Command::new("qemu-img")
+            .args(["create", "-f", "qcow2", "-F", "qcow2", "-b"])
+            .args([backing_path.as_str(), DISK_FILE, disk_size.as_str()])
+            .current_dir(&self.vm_path))
+        .context("Error creating the VM disk")?;
+
+        on_output("Writing the cloud-init seed".to_string());
+        self.write_seed(ssh_pubkey, instance_id)
+    }
+
+    /// Checks a downloaded image against the `SHA256SUMS` published beside it, once the sums file
+    /// has passed its signature check with the Ubuntu cloud image key in `keyring`.
+    fn verify_image(
+        &self,
+        image_url: &str,
+        image_path: &Path,
+        keyring: &Path,
+    ) -> Result<(), CommanderError> {
+        let (release_url, image_name) = image_url.rsplit_once('/').ok_or_else(|| {
+            CommanderError::new(
+                ErrorCode::QemuCommandFailed,
+                format!("{} is not an image URL", image_url),
+            )
+        })?;
+        let sums_path = self.vm_path.join(SUMS_FILE);
+        let signature_path = self.vm_path.join(format!("{}.gpg", SUMS_FILE));
+        let result = (|| {
+            download(&format!("{}/{}", release_url, SUMS_FILE), &sums_path)
+                .context("Error downloading the VM image checksums")?;
+            download(
+                &format!("{}/{}.gpg", release_url, SUMS_FILE),
+                &signature_path,
+            )
+            .context("Error downloading the VM image signature")?;
+            run(Command::new("gpgv")
+                .arg("--keyring")
+                .arg(keyring)
+                .arg(&signature_path)
+                .arg(&sums_path))
+            .context("The VM image checksums failed their signature check")?;
+            let sums = fs::read_to_string(&sums_path)
+                .context(format!("Error reading file {}", sums_path.display()))?;
+            let expected_hash = find_sha256(&sums, image_name).ok_or_else(|| {
+                CommanderError::new(
+                    ErrorCode::QemuCommandFailed,
+                    format!("{} has no checksum for {}", SUMS_FILE, image_name),
+                )
+            })?;
+            let actual_hash = get_sha256(image_path)?;
+            if actual_hash != expected_hash {
+                return Err(CommanderError::new(
+                    ErrorCode::QemuCommandFailed,
+                    format!(
+                        "The VM image checksum is {}, but {} lists {}",
+                        actual_hash, SUMS_FILE, expected_hash
+                    ),
+                ));
+            }
+            Ok(())
+        })();
+        for path in [&sums_path, &signature_path] {
+            let _ = fs::remove_file(path);
+        }
+        result
+    }
+
+    /// Boots the VM unless it's already running, and waits for sshd. Returns the forwarded ssh
+    /// port. `port_forwards` are (host, guest) pairs for the server's ports.
+    pub fn start(
+        &self,
+        limits: &VmResourceLimits,
+        port_forwards: &[(u16, u16)],
+        on_progress: &dyn Fn(VmProgress),
+    ) -> Result<u16, CommanderError> {
+        let has_kvm = Self::has_kvm();
+        if !self.is_running() {
+            on_progress(VmProgress::new(VmPhase::Starting));
+            let ssh_port = self.reserve_ssh_port()?;
+            if !has_kvm {
+                log::warn!("/dev/kvm is not available, so the local VM will run emulated (TCG)");
+            }
+            let binary = get_qemu_binary().ok_or_else(|| {
+                CommanderError::new(
+                    ErrorCode::QemuCommandFailed,
+                    format!("The QEMU VM is not supported on {}", std::env::consts::ARCH),
+                )
+            })?;
+            let mut command = Command::new(binary);
+            command
+                .args(self.get_qemu_args(limits, ssh_port, port_forwards, has_kvm)?)
+                .current_dir(&self.vm_path);
+            run(&mut command).context("Error starting the VM")?;
+        }
+
+        on_progress(VmProgress::new(VmPhase::WaitingForHealth));
+        let ssh_port = self.get_ssh_port()?;
+        let timeout = if has_kvm {
+            KVM_BOOT_TIMEOUT
+        } else {
+            TCG_BOOT_TIMEOUT
+        };
+        let started = Instant::now();
+        while !is_ssh_ready(ssh_port) {
+            if !self.is_running() {
+                return Err(CommanderError::new(
+                    ErrorCode::QemuCommandFailed,
+                    format!(
+                        "The VM exited while booting: {}",
+                        self.get_console_tail(20).join("\n")
+                    ),
+                ));
+            }
+            if started.elapsed() > timeout {
+                return Err(CommanderError::new(
+                    ErrorCode::QemuCommandFailed,
+                    format!(
+                        "The VM did not start ssh within {} seconds",
+                        timeout.as_secs()
+                    ),
+                ));
+            }
+            std::thread::sleep(Duration::from_secs(2));
+        }
+        on_progress(VmProgress::new(VmPhase::ResolvingPort));
+        Ok(ssh_port)
+    }
+
+    /// The forwarded ssh port, as long as the VM is running.
+    pub fn get_ssh_port(&self) -> Result<u16, CommanderError> {
+        if !self.is_running() {
+            return Err(CommanderError::new(
+                ErrorCode::QemuCommandFailed,
+                "The VM is not running",
+            ));
+        }
+        vm::get_env_value(&self.vm_path, "VM_SSH_PORT")
+            .and_then(|port| port.parse::<u16>().ok())
+            .ok_or_else(|| {
+                CommanderError::new(ErrorCode::QemuCommandFailed, "The VM has no ssh port")
+            })
+    }
+
+    /// Asks the guest to power off through ACPI so the nodes can flush, and pulls the plug if it
+    /// hasn't within [`SHUTDOWN_TIMEOUT`].
+    pub fn stop(&self) -> Result<(), CommanderError> {
+        if !self.is_running() {
+            return Ok(());
+        }
+        self.send_qmp_command("system_powerdown")?;
+        let started = Instant::now();
+        while self.is_running() {
+            if started.elapsed() > SHUTDOWN_TIMEOUT {
+                log::warn!("The local VM didn't power off in time, so it is being stopped");
+                self.send_qmp_command("quit")?;
+                break;
+            }
+            std::thread::sleep(Duration::from_secs(1));
+        }
+        Ok(())
+    }
+
+    /// Stops the VM without waiting on the guest, for when it's about to be deleted.
+    pub fn kill(&self) -> Result<(), CommanderError> {
+        if self.is_running() {
+            self.send_qmp_command("quit")?;
+        }
+        Ok(())
+    }
+
+    /// Restarts a running VM so new cpu and memory limits take effect, since QEMU fixes them at
+    /// boot. A larger disk reservation grows the disk while it's stopped, and cloud-init grows the
+    /// root partition into it on the next boot. qcow2 disks can't shrink safely, so a smaller one
+    /// keeps the current size.
+    pub fn apply_limits(
+        &self,
+        limits: &VmResourceLimits,
+        port_forwards: &[(u16, u16)],
+    ) -> Result<(), CommanderError> {
+        let was_running = self.is_running();
+        let disk_bytes = limits.reserved_disk_bytes.unwrap_or(DEFAULT_DISK_BYTES);
+        let needs_resize = disk_bytes > self.get_disk_size()?;
+        if !was_running && !needs_resize {
+            return Ok(());
+        }
+        self.stop()?;
+        if needs_resize {
+            let disk_size = disk_bytes.to_string();
+            run(Command::new("qemu-img")
+                .args(["resize", DISK_FILE, disk_size.as_str()])
+                .current_dir(&self.vm_path))
+            .context("Error resizing the VM disk")?;
+        }
+        if was_running {
+            self.start(limits, port_forwards, &|_| {})?;
+        }
+        Ok(())
+    }
+
+    /// The virtual size of the VM's disk, which is what the guest sees rather than what's
+    /// allocated on the host.
+    fn get_disk_size(&self) -> Result<u64, CommanderError> {
+        self.get_disk_info()?["virtual-size"]
+            .as_u64()
+            .ok_or_else(|| {
+                CommanderError::new(
+                    ErrorCode::QemuCommandFailed,
+                    "qemu-img info returned no virtual size",
+                )
+            })
+    }
+
+    /// Rewrites the VM's disk without the base image behind it, so the `app` dir holds everything
+    /// the VM boots from, ie, for a snapshot restored on a machine with a different base image.
+    /// The VM must be stopped.
+    pub fn flatten_disk(&self) -> Result<(), CommanderError> {
+        if self.get_disk_info()?.get("backing-filename").is_none() {
+            return Ok(());
+        }
+        let flat_file = format!("{}.flat", DISK_FILE);
+        let result = run(Command::new("qemu-img")
+            .args(["convert", "-O", "qcow2", DISK_FILE, flat_file.as_str()])
+            .current_dir(&self.vm_path))
+        .context("Error flattening the VM disk");
+        let flat_path = self.vm_path.join(&flat_file);
+        if result.is_err() {
+            let _ = fs::remove_file(&flat_path);
+            return result;
+        }
+        let disk_path = self.vm_path.join(DISK_FILE);
+        fs::rename(&flat_path, &disk_path)
+            .context(format!("Error writing file {}", disk_path.display()))?;
+        Ok(())
+    }
+
+    fn get_disk_info(&self) -> Result<serde_json::Value, CommanderError> {
+        let output = run_with_output(
+            Command::new("qemu-img")
+                .args(["info", "--output=json", DISK_FILE])
+                .current_dir(&self.vm_path),
+        )
+        .context("Error reading the VM disk")?;
+        serde_json::from_str(&output)
+            .map_err(|e| CommanderError::new(ErrorCode::QemuCommandFailed, e))
+    }
+
+    pub fn is_running(&self) -> bool {
+        let Some(pid) = fs::read_to_string(self.vm_path.join(PID_FILE))
+            .ok()
+            .and_then(|pid| pid.trim().parse::<u32>().ok())
+        else {
+            return false;
+        };
+        // a stale pid file can point at an unrelated process
+        fs::read(format!("/proc/{}/cmdline", pid))
+            .is_ok_and(|cmdline| String::from_utf8_lossy(&cmdline).contains("qemu-system"))
+    }
+
+    /// Seconds since the running VM was booted, from when qemu wrote its pid file.
+    #[cfg(feature = "gui")]
+    pub fn get_uptime_seconds(&self) -> Option<u64> {
+        if !self.is_running() {
+            return None;
+        }
+        let started_at = fs::metadata(self.vm_path.join(PID_FILE))
+            .and_then(|metadata| metadata.modified())
+            .ok()?;
+        SystemTime::now()
+            .duration_since(started_at)
+            .ok()
+            .map(|x| x.as_secs())
+    }
+
+    pub fn get_console_log_path(&self) -> PathBuf {
+        self.vm_path.join(CONSOLE_LOG_FILE)
+    }
+
+    /// The last `lines` lines of the guest's serial console.
+    pub fn get_console_tail(&self, lines: usize) -> Vec<String> {
+        let text = fs::read(self.get_console_log_path())
+            .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+            .unwrap_or_default();
+        let all_lines = text.lines().collect::<Vec<_>>();
+        all_lines[all_lines.len().saturating_sub(lines)..]
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    fn write_seed(&self, ssh_pubkey: &str, instance_id: &str) -> Result<(), CommanderError> {
+        let seed_dir = self.vm_path.join(SEED_DIR);
+        fs::create_dir_all(&seed_dir)
+            .context(format!("Error creating directory {}", seed_dir.display()))?;
+        // root gets the key too since disable_root is off, which is the user the app connects as
+        let key = serde_json::to_string(ssh_pubkey.trim()).unwrap_or_default();
+        let user_data = format!(
+            "#cloud-config\n\
+             disable_root: false\n\
+             ssh_pwauth: false\n\
+             ssh_authorized_keys:\n  - {key}\n\
+             users:\n\
+             \x20 - default\n\
+             \x20 - name: commander\n\
+             \x20   shell: /bin/bash\n\
+             \x20   sudo: ALL=(ALL) NOPASSWD:ALL\n\
+             \x20   ssh_authorized_keys:\n\
+             \x20     - {key}\n\
+             runcmd:\n\
+             \x20 - mkdir -p /app\n"
+        );
+        let meta_data = format!(
+            "instance-id: {}\nlocal-hostname: {}\n",
+            instance_id, instance_id
+        );
+        for (file_name, contents) in [("user-data", user_data), ("meta-data", meta_data)] {
+            let path = seed_dir.join(file_name);
+            fs::write(&path, contents).context(format!("Error writing file {}", path.display()))?;
+        }
+
+        // NoCloud finds the seed by its "cidata" volume label
+        let seed_path = self.vm_path.join(SEED_FILE);
+        let files = [seed_dir.join("user-data"), seed_dir.join("meta-data")];
+        let mut command = if is_on_path("cloud-localds") {
+            let mut command = Command::new("cloud-localds");
+            command.arg(&seed_path).args(&files);
+            command
+        } else {
+            let mut command = match ["genisoimage", "mkisofs", "xorrisofs"]
+                .into_iter()
+                .find(|binary| is_on_path(binary))
+            {
+                Some(binary) => Command::new(binary),
+                None => {
+                    return Err(CommanderError::new(
+                        ErrorCode::QemuCommandFailed,
+                        "Creating the VM needs cloud-localds, genisoimage, mkisofs or xorrisofs",
+                    ));
+                }
+            };
+            command
+                .arg("-output")
+                .arg(&seed_path)
+                .args(["-volid", "cidata", "-joliet", "-rock"])
+                .args(&files);
+            command
+        };
+        run(&mut command).context("Error creating the cloud-init seed")?;
+        Ok(())
+    }
+
+    /// Reuses the ssh port from the last boot while it's free, so saved server details stay valid.
+    fn reserve_ssh_port(&self) -> Result<u16, CommanderError> {
+        let saved_port = vm::get_env_value(&self.vm_path, "VM_SSH_PORT")
+            .and_then(|port| port.parse::<u16>().ok())
+            .filter(|port| TcpListener::bind((Ipv4Addr::LOCALHOST, *port)).is_ok());
+        if let Some(port) = saved_port {
+            return Ok(port);
+        }
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .context("Error finding a free ssh port")
+            .code(ErrorCode::PortInUse)?
+            .port();
+        vm::update_env_file(&self.vm_path, &[("VM_SSH_PORT", port.to_string())])?;
+        Ok(port)
+    }
+
+    fn get_qemu_args(
+        &self,
+        limits: &VmResourceLimits,
+        ssh_port: u16,
+        port_forwards: &[(u16, u16)],
+        has_kvm: bool,
+    ) -> Result<Vec<String>, CommanderError> {
+        let cpus = match limits.cpus {
+            Some(cpus) => cpus.ceil().max(1.0) as usize,
+            None => std::thread::available_parallelism().map_or(1, |x| x.get()),
+        };
+        let memory_mib = limits.memory_bytes.unwrap_or(DEFAULT_MEMORY_BYTES) / (1024 * 1024);
+        // ssh stays on loopback; the server ports are published on every interface like docker does
+        let mut netdev = format!("user,id=net0,hostfwd=tcp:127.0.0.1:{}-:22", ssh_port);
+        for (host_port, guest_port) in port_forwards {
+            netdev.push_str(&format!(",hostfwd=tcp::{}-:{}", host_port, guest_port));
+        }
+
+        let mut args = vec![
+            "-name".to_string(),
+            vm::get_env_value(&self.vm_path, "COMPOSE_PROJECT_NAME")
+                .unwrap_or_else(|| "argon-commander".to_string()),
+            "-machine".to_string(),
+            get_machine_type().to_string(),
+            "-accel".to_string(),
+            if has_kvm { "kvm" } else { "tcg" }.to_string(),
+            "-cpu".to_string(),
+            if has_kvm { "host" } else { "max" }.to_string(),
+            "-smp".to_string(),
+            cpus.to_string(),
+            "-m".to_string(),
+            memory_mib.to_string(),
+            "-drive".to_string(),
+            format!("file={},if=virtio,format=qcow2", DISK_FILE),
+            "-drive".to_string(),
+            format!("file={},if=virtio,format=raw,readonly=on", SEED_FILE),
+            "-netdev".to_string(),
+            netdev,
+            "-device".to_string(),
+            "virtio-net-pci,netdev=net0".to_string(),
+            "-display".to_string(),
+            "none".to_string(),
+            "-serial".to_string(),
+            format!("file:{}", CONSOLE_LOG_FILE),
+            "-qmp".to_string(),
+            format!("unix:{},server=on,wait=off", QMP_SOCKET_FILE),
+            "-pidfile".to_string(),
+            PID_FILE.to_string(),
+            "-daemonize".to_string(),
+        ];
+        if let Some(firmware) = get_firmware_path()? {
+            args.extend(["-bios".to_string(), firmware.display().to_string()]);
+        }
+        Ok(args)
+    }
+
+    /// Runs one command over the QMP socket, ie, system_powerdown.
+    #[cfg(unix)]
+    fn send_qmp_command(&self, execute: &str) -> Result<(), CommanderError> {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixStream;
+
+        let socket_path = self.vm_path.join(QMP_SOCKET_FILE);
+        let qmp_error = |e: std::io::Error| {
+            CommanderError::new(
+                ErrorCode::QemuCommandFailed,
+                format!(
+                    "Unable to reach the VM monitor at {}: {}",
+                    socket_path.display(),
+                    e
+                ),
+            )
+        };
+        let mut stream = UnixStream::connect(&socket_path).map_err(qmp_error)?;
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .map_err(qmp_error)?;
+        let mut reader = BufReader::new(stream.try_clone().map_err(qmp_error)?);
+        let mut greeting = String::new();
+        reader.read_line(&mut greeting).map_err(qmp_error)?;
+
+        // commands are refused until capabilities are negotiated
+        for command in ["qmp_capabilities", execute] {
+            writeln!(stream, "{{\"execute\":\"{}\"}}", command).map_err(qmp_error)?;
+            loop {
+                let mut line = String::new();
+                // quit can close the socket before replying
+                if reader.read_line(&mut line).map_err(qmp_error)? == 0 {
+                    return Ok(());
+                }
+                // async events are interleaved with replies
+                let reply = serde_json::from_str::<serde_json::Value>(&line).unwrap_or_default();
+                if let Some(error) = reply.get("error") {
+                    return Err(CommanderError::new(
+                        ErrorCode::QemuCommandFailed,
+                        format!("The VM monitor refused {}: {}", command, error),
+                    ));
+                }
+                if reply.get("return").is_some() {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn send_qmp_command(&self, _execute: &str) -> Result<(), CommanderError> {
+        Err(CommanderError::new(
+            ErrorCode::QemuCommandFailed,
+            "The QEMU VM is not supported on this platform",
+        ))
+    }
+}
+
+fn get_qemu_binary() -> Option<&'static str> {
+    match std::env::consts::ARCH {
+        "x86_64" => Some("qemu-system-x86_64"),
+        "aarch64" => Some("qemu-system-aarch64"),
+        _ => None,
+    }
+}
+
+fn get_machine_type() -> &'static str {
+    match std::env::consts::ARCH {
+        "aarch64" => "virt",
+        _ => "q35",
+    }
+}
+
+fn get_image_url() -> Result<String, CommanderError> {
+    if let Ok(url) = std::env::var("COMMANDER_VM_IMAGE_URL") {
+        return Ok(url);
+    }
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch => {
+            return Err(CommanderError::new(
+                ErrorCode::QemuCommandFailed,
+                format!("There is no VM image for {}", arch),
+            ));
+        }
+    };
+    Ok(format!("{}-{}.img", IMAGE_URL_BASE, arch))
+}
+
+/// arm machines have no default firmware, so they boot the UEFI build the distro packages.
+fn get_firmware_path() -> Result<Option<PathBuf>, CommanderError> {
+    if std::env::consts::ARCH != "aarch64" {
+        return Ok(None);
+    }
+    [
+        "/usr/share/AAVMF/AAVMF_CODE.fd",
+        "/usr/share/qemu-efi-aarch64/QEMU_EFI.fd",
+        "/usr/share/edk2/aarch64/QEMU_EFI.fd",
+    ]
+    .into_iter()
+    .map(PathBuf::from)
+    .find(|path| path.exists())
+    .map(Some)
+    .ok_or_else(|| {
+        CommanderError::new(
+            ErrorCode::QemuCommandFailed,
+            "No aarch64 UEFI firmware was found. Install qemu-efi-aarch64",
+        )
+    })
+}
+
+/// User-mode networking accepts forwarded connections before the guest listens, so a port is
+/// only ready once sshd sends its banner.
+fn is_ssh_ready(port: u16) -> bool {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let Ok(mut stream) = TcpStream::connect_timeout(&address, Duration::from_secs(2)) else {
+        return false;
+    };
+    if stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .is_err()
+    {
+        return false;
+    }
+    let mut banner = [0u8; 4];
+    stream.read_exact(&mut banner).is_ok() && banner == *b"SSH-"
+}
+
+/// The keyring to check the image checksums' signature with. Unsigned checksums are never
+/// trusted, so a QEMU VM can't be created without the keyring and `gpgv`.
+fn get_image_keyring() -> Result<PathBuf, CommanderError> {
+    let keyring = std::env::var_os("COMMANDER_VM_IMAGE_KEYRING")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(IMAGE_KEYRING_PATH));
+    if !keyring.is_file() || !is_on_path("gpgv") {
+        return Err(CommanderError::new(
+            ErrorCode::QemuCommandFailed,
+            format!(
+                "Verifying the VM image needs gpgv and the Ubuntu cloud image keyring at {}. \
+                 Install the gpgv and ubuntu-cloudimage-keyring packages, or set \
+                 COMMANDER_VM_IMAGE_KEYRING to the keyring",
+                keyring.display()
+            ),
+        ));
+    }
+    Ok(keyring)
+}
+
+/// The lowercase hash `sums` lists for `file_name`. Lines are `<hash> *<name>` for binary mode and
+/// `<hash>  <name>` for text mode.
+fn find_sha256(sums: &str, file_name: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(' ')?;
+        let name = name.trim_start_matches([' ', '*']);
+        (name == file_name).then(|| hash.to_lowercase())
+    })
+}
+
+fn get_sha256(path: &Path) -> Result<String, CommanderError> {
+    use sha2::{Digest, Sha256};
+
+    let mut file = fs::File::open(path).context(format!("Opening {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).context(format!("Reading {}", path.display()))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn download(url: &str, path: &Path) -> Result<(), CommanderError> {
+    run(Command::new("curl")
+        .args(["--fail", "--location", "--silent", "--show-error"])
+        .arg("--output")
+        .arg(path)
+        .arg(url))
+}
+
+fn is_on_path(binary: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(binary).is_file()))
+}
+
+fn run(command: &mut Command) -> Result<(), CommanderError> {
+    run_with_output(command).map(|_| ())
+}
+
+/// Runs the command and returns its stdout, failing with its stderr on a non-zero exit.
+fn run_with_output(command: &mut Command) -> Result<String, CommanderError> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command.output().map_err(|e| {
+        CommanderError::new(
+            ErrorCode::QemuCommandFailed,
+            format!("Failed to run {}: {}", program, e),
+        )
+    })?;
+    if !output.status.success() {
+        return Err(CommanderError::new(
+            ErrorCode::QemuCommandFailed,
+            format!(
+                "{} failed: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
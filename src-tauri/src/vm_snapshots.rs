@@ -0,0 +1,470 @@
+use crate::error::{CommanderError, ErrorCode, ResultExt};
+#[cfg(feature = "gui")]
+use crate::utils::Utils;
+use crate::vm::{self, Vm, VmBackend};
+use crate::vm_qemu::QemuVm;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "gui")]
+use tauri::AppHandle;
+use zip::write::SimpleFileOptions;
+
+/// Kept beside `virtual-machine` rather than in it, so recreating the VM leaves snapshots alone.
+const SNAPSHOT_DIR_NAME: &str = "vm-snapshots";
+const SNAPSHOT_PREFIX: &str = "vm-";
+const SNAPSHOT_EXTENSION: &str = ".zip";
+
+#[derive(serde::Serialize, specta::Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VmSnapshot {
+    /// Identifies the snapshot to the other snapshot commands
+    pub file_name: String,
+    pub path: PathBuf,
+    pub created_at_millis: u64,
+    pub size_bytes: u64,
+}
+
+impl VmSnapshot {
+    fn from_path(path: PathBuf) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?.to_string();
+        let created_at_millis = file_name
+            .strip_prefix(SNAPSHOT_PREFIX)?
+            .strip_suffix(SNAPSHOT_EXTENSION)?
+            .parse()
+            .ok()?;
+        let size_bytes = fs::metadata(&path).ok()?.len();
+        Some(Self {
+            file_name,
+            path,
+            created_at_millis,
+            size_bytes,
+        })
+    }
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn create_local_vm_snapshot(app: AppHandle) -> Result<VmSnapshot, CommanderError> {
+    let instance_dir = Utils::get_absolute_config_instance_dir(&app);
+    tauri::async_runtime::spawn_blocking(move || create_snapshot(&instance_dir))
+        .await
+        .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))?
+}
+
+/// Snapshots for this instance, newest first.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub fn list_local_vm_snapshots(app: AppHandle) -> Vec<VmSnapshot> {
+    list_snapshots(&Utils::get_absolute_config_instance_dir(&app))
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn restore_local_vm_snapshot(
+    app: AppHandle,
+    file_name: String,
+) -> Result<(), CommanderError> {
+    let instance_dir = Utils::get_absolute_config_instance_dir(&app);
+    tauri::async_runtime::spawn_blocking(move || restore_snapshot(&instance_dir, &file_name))
+        .await
+        .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))?
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub fn delete_local_vm_snapshot(app: AppHandle, file_name: String) -> Result<(), CommanderError> {
+    let path = get_snapshot_path(&Utils::get_absolute_config_instance_dir(&app), &file_name)?;
+    fs::remove_file(&path).context(format!("Error removing {}", path.display()))?;
+    Ok(())
+}
+
+/// Copies a snapshot taken on another machine into this instance's snapshots.
+#[cfg(feature = "gui")]
+#[tauri::command]
+#[specta::specta]
+pub async fn import_local_vm_snapshot(
+    app: AppHandle,
+    path: PathBuf,
+) -> Result<VmSnapshot, CommanderError> {
+    let instance_dir = Utils::get_absolute_config_instance_dir(&app);
+    tauri::async_runtime::spawn_blocking(move || import_snapshot(&instance_dir, &path))
+        .await
+        .map_err(|e| CommanderError::new(ErrorCode::Unknown, e))?
+}
+
+fn new_snapshot_file_name() -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    format!("{}{:013}{}", SNAPSHOT_PREFIX, timestamp, SNAPSHOT_EXTENSION)
+}
+
+fn get_snapshot_dir(instance_dir: &Path) -> PathBuf {
+    instance_dir.join(SNAPSHOT_DIR_NAME)
+}
+
+/// Only names the snapshot commands produced are accepted, so a name can't point outside the
+/// snapshot dir.
+fn get_snapshot_path(instance_dir: &Path, file_name: &str) -> Result<PathBuf, CommanderError> {
+    let path = get_snapshot_dir(instance_dir).join(file_name);
+    match VmSnapshot::from_path(path.clone()) {
+        Some(snapshot) if snapshot.file_name == file_name => Ok(path),
+        _ => Err(CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!("{} is not a local VM snapshot", file_name),
+        )),
+    }
+}
+
+pub fn list_snapshots(instance_dir: &Path) -> Vec<VmSnapshot> {
+    let Ok(entries) = fs::read_dir(get_snapshot_dir(instance_dir)) else {
+        return vec![];
+    };
+    let mut snapshots = entries
+        .flatten()
+        .filter_map(|entry| VmSnapshot::from_path(entry.path()))
+        .collect::<Vec<_>>();
+    snapshots.sort_by(|a, b| b.created_at_millis.cmp(&a.created_at_millis));
+    snapshots
+}
+
+/// Runs `operation` with the VM and its server stopped so the chain databases aren't being
+/// written, then brings back whatever was running.
+fn with_vm_stopped<T>(
+    vm_path: &PathBuf,
+    operation: impl FnOnce() -> Result<T, CommanderError>,
+) -> Result<T, CommanderError> {
+    let was_running = Vm::get_vm(vm_path).is_ok();
+    // the server's containers run beside the VM's on the host engine, so they can be up either way
+    let server_services = Vm::get_running_server_services(vm_path)?;
+    if was_running || !server_services.is_empty() {
+        log::info!("Stopping the local VM");
+        Vm::stop(vm_path)?;
+    }
+    let result = operation();
+    if was_running {
+        log::info!("Restarting the local VM");
+        if let Err(e) = Vm::activate(vm_path, &vm_path.join("app"), &|_| {}) {
+            log::error!("Unable to restart the local VM: {}", e);
+            // the operation's own error is the more useful one to surface
+            return result.and(Err(e));
+        }
+    }
+    if !server_services.is_empty() {
+        log::info!("Restarting the local VM server");
+        if let Err(e) = Vm::start_server_services(vm_path, &server_services) {
+            log::error!("Unable to restart the local VM server: {}", e);
+            return result.and(Err(e));
+        }
+    }
+    result
+}
+
+pub fn create_snapshot(instance_dir: &Path) -> Result<VmSnapshot, CommanderError> {
+    let vm_path = vm::get_vm_path_in(instance_dir);
+    let app_dir = vm_path.join("app");
+    if !app_dir.exists() {
+        return Err(CommanderError::new(
+            ErrorCode::VmNotFound,
+            format!("VM data {} does not exist", app_dir.display()),
+        ));
+    }
+    let snapshot_dir = get_snapshot_dir(instance_dir);
+    fs::create_dir_all(&snapshot_dir).context(format!(
+        "Error creating directory {}",
+        snapshot_dir.display()
+    ))?;
+    let file_name = new_snapshot_file_name();
+    let snapshot_path = snapshot_dir.join(&file_name);
+    // written under a temporary name so a failed snapshot never shows up in the list
+    let partial_path = snapshot_dir.join(format!("{}.partial", file_name));
+
+    with_vm_stopped(&vm_path, || {
+        // the disk is otherwise layered on a base image outside `app`
+        if VmBackend::of(&vm_path) == VmBackend::Qemu {
+            QemuVm::new(&vm_path).flatten_disk()?;
+        }
+        log::info!("Snapshotting {} to {}", app_dir.display(), file_name);
+        let result = write_archive(&app_dir, &partial_path);
+        if result.is_err() {
+            let _ = fs::remove_file(&partial_path);
+        }
+        result
+    })?;
+    fs::rename(&partial_path, &snapshot_path)
+        .context(format!("Error writing {}", snapshot_path.display()))?;
+
+    VmSnapshot::from_path(snapshot_path).ok_or_else(|| {
+        CommanderError::new(ErrorCode::FileSystemError, "The snapshot was not written")
+    })
+}
+
+/// Zips `app_dir` with every entry under `app/`. Zip64 is on since synced chain data runs well
+/// past 4GB.
+fn write_archive(app_dir: &Path, archive_path: &Path) -> Result<(), CommanderError> {
+    let zip_error = |e: zip::result::ZipError| CommanderError::new(ErrorCode::FileSystemError, e);
+    let file =
+        fs::File::create(archive_path).context(format!("Creating {}", archive_path.display()))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(true);
+
+    for entry in walkdir::WalkDir::new(app_dir) {
+        let entry = entry.map_err(|e| CommanderError::new(ErrorCode::FileSystemError, e))?;
+        let path = entry.path();
+        let relative_path = path.strip_prefix(app_dir).unwrap_or(path);
+        let name = Path::new("app")
+            .join(relative_path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        // keeps the node binaries and scripts in the data dir executable after a restore
+        #[cfg(unix)]
+        let entry_options = {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = entry
+                .metadata()
+                .map_err(|e| CommanderError::new(ErrorCode::FileSystemError, e))?;
+            options.unix_permissions(metadata.permissions().mode())
+        };
+        #[cfg(not(unix))]
+        let entry_options = options;
+
+        if entry.file_type().is_dir() {
+            zip.add_directory(name, entry_options).map_err(zip_error)?;
+        } else if entry.file_type().is_symlink() {
+            let target = fs::read_link(path).context(format!("Reading {}", path.display()))?;
+            zip.add_symlink(name, target.to_string_lossy(), entry_options)
+                .map_err(zip_error)?;
+        } else {
+            zip.start_file(name, entry_options).map_err(zip_error)?;
+            let mut f = fs::File::open(path).context(format!("Opening {}", path.display()))?;
+            std::io::copy(&mut f, &mut zip)?;
+        }
+    }
+    zip.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+/// Replaces the VM's `app` data with a snapshot. The archive is extracted beside the current data
+/// first and only swapped in once it's complete, so a failed restore leaves the VM as it was.
+pub fn restore_snapshot(instance_dir: &Path, file_name: &str) -> Result<(), CommanderError> {
+    let snapshot_path = get_snapshot_path(instance_dir, file_name)?;
+    let vm_path = vm::get_vm_path_in(instance_dir);
+    if !vm_path.exists() {
+        return Err(CommanderError::new(
+            ErrorCode::VmNotFound,
+            format!(
+                "VM path {} does not exist. Create the local VM before restoring into it",
+                vm_path.display()
+            ),
+        ));
+    }
+    let app_dir = vm_path.join("app");
+    let restore_dir = vm_path.join("app.restoring");
+    let previous_dir = vm_path.join("app.previous");
+
+    with_vm_stopped(&vm_path, || {
+        log::info!("Restoring {} into {}", file_name, app_dir.display());
+        for dir in [&restore_dir, &previous_dir] {
+            if dir.exists() {
+                fs::remove_dir_all(dir)
+                    .context(format!("Error removing directory {}", dir.display()))?;
+            }
+        }
+        let restored_app_dir = restore_dir.join("app");
+        if let Err(e) = extract_archive(&snapshot_path, &restore_dir) {
+            let _ = fs::remove_dir_all(&restore_dir);
+            return Err(e);
+        }
+        if !restored_app_dir.exists() {
+            let _ = fs::remove_dir_all(&restore_dir);
+            return Err(CommanderError::new(
+                ErrorCode::InvalidInput,
+                format!("{} has no app directory", file_name),
+            ));
+        }
+
+        if app_dir.exists() {
+            fs::rename(&app_dir, &previous_dir)
+                .context(format!("Error moving {}", app_dir.display()))?;
+        }
+        if let Err(e) = fs::rename(&restored_app_dir, &app_dir) {
+            let _ = fs::rename(&previous_dir, &app_dir);
+            return Err(CommanderError::from(e).context("Error moving restored data"));
+        }
+        let _ = fs::remove_dir_all(&restore_dir);
+        // the restore itself succeeded, and the next one clears out whatever is left
+        if previous_dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&previous_dir) {
+                log::warn!("Unable to remove {}: {}", previous_dir.display(), e);
+            }
+        }
+        Ok(())
+    })
+}
+
+fn extract_archive(archive_path: &Path, target_dir: &Path) -> Result<(), CommanderError> {
+    let file =
+        fs::File::open(archive_path).context(format!("Opening {}", archive_path.display()))?;
+    zip::ZipArchive::new(file)
+        .and_then(|mut archive| archive.extract(target_dir))
+        .map_err(|e| CommanderError::new(ErrorCode::FileSystemError, e))
+        .context(format!("Extracting {}", archive_path.display()))
+}
+
+/// Refuses a snapshot whose name is already taken, ie, one that was imported before.
+#[cfg(any(feature = "gui", test))]
+fn import_snapshot(instance_dir: &Path, path: &Path) -> Result<VmSnapshot, CommanderError> {
+    let is_snapshot = fs::File::open(path)
+        .ok()
+        .and_then(|file| zip::ZipArchive::new(file).ok())
+        .is_some_and(|archive| archive.file_names().any(|name| name.starts_with("app/")));
+    if !is_snapshot {
+        return Err(CommanderError::new(
+            ErrorCode::InvalidInput,
+            format!("{} is not a local VM snapshot", path.display()),
+        ));
+    }
+    let snapshot_dir = get_snapshot_dir(instance_dir);
+    fs::create_dir_all(&snapshot_dir).context(format!(
+        "Error creating directory {}",
+        snapshot_dir.display()
+    ))?;
+    // keep the original timestamp when the name carries one
+    let file_name = VmSnapshot::from_path(path.to_path_buf())
+        .map(|snapshot| snapshot.file_name)
+        .unwrap_or_else(new_snapshot_file_name);
+    let target_path = snapshot_dir.join(&file_name);
+    let mut target = match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&target_path)
+    {
+        Ok(target) => target,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            return Err(CommanderError::new(
+                ErrorCode::InvalidInput,
+                format!("Snapshot {} already exists", file_name),
+            ));
+        }
+        Err(e) => {
+            return Err(CommanderError::from(e)
+                .context(format!("Error creating {}", target_path.display())));
+        }
+    };
+    let copied =
+        fs::File::open(path).and_then(|mut source| std::io::copy(&mut source, &mut target));
+    if let Err(e) = copied {
+        let _ = fs::remove_file(&target_path);
+        return Err(CommanderError::from(e).context(format!("Error copying {}", path.display())));
+    }
+    VmSnapshot::from_path(target_path).ok_or_else(|| {
+        CommanderError::new(ErrorCode::FileSystemError, "The snapshot was not copied")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_snapshots_from_their_file_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vm-1700000000000.zip");
+        fs::write(&path, b"12345").unwrap();
+        let snapshot = VmSnapshot::from_path(path.clone()).unwrap();
+        assert_eq!(snapshot.file_name, "vm-1700000000000.zip");
+        assert_eq!(snapshot.path, path);
+        assert_eq!(snapshot.created_at_millis, 1_700_000_000_000);
+        assert_eq!(snapshot.size_bytes, 5);
+
+        let file_name = new_snapshot_file_name();
+        fs::write(dir.path().join(&file_name), b"").unwrap();
+        assert!(VmSnapshot::from_path(dir.path().join(&file_name)).is_some());
+    }
+
+    #[test]
+    fn ignores_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "vm-1700000000000.zip.partial",
+            "vm-latest.zip",
+            "snapshot-1700000000000.zip",
+            "vm-1700000000000.tar",
+        ] {
+            let path = dir.path().join(name);
+            fs::write(&path, b"").unwrap();
+            assert!(VmSnapshot::from_path(path).is_none(), "{}", name);
+        }
+        assert!(VmSnapshot::from_path(dir.path().join("vm-1700000000001.zip")).is_none());
+    }
+
+    #[test]
+    fn lists_newest_first_and_refuses_paths_outside_the_snapshot_dir() {
+        let instance_dir = tempfile::tempdir().unwrap();
+        let snapshot_dir = get_snapshot_dir(instance_dir.path());
+        fs::create_dir_all(&snapshot_dir).unwrap();
+        for name in ["vm-0000000000001.zip", "vm-0000000000003.zip", "notes.txt"] {
+            fs::write(snapshot_dir.join(name), b"").unwrap();
+        }
+        let names = list_snapshots(instance_dir.path())
+            .into_iter()
+            .map(|x| x.file_name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["vm-0000000000003.zip", "vm-0000000000001.zip"]);
+
+        assert!(get_snapshot_path(instance_dir.path(), "vm-0000000000001.zip").is_ok());
+        for name in [
+            "../vm-0000000000001.zip",
+            "notes.txt",
+            "vm-0000000000002.zip",
+        ] {
+            let error = get_snapshot_path(instance_dir.path(), name).unwrap_err();
+            assert_eq!(error.code, ErrorCode::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn imports_a_snapshot_once() {
+        let source = tempfile::tempdir().unwrap();
+        let app_dir = source.path().join("app");
+        fs::create_dir_all(app_dir.join("bitcoin")).unwrap();
+        fs::write(app_dir.join("bitcoin").join("blocks.dat"), b"blocks").unwrap();
+        let archive_path = source.path().join("vm-1700000000000.zip");
+        write_archive(&app_dir, &archive_path).unwrap();
+        let instance_dir = tempfile::tempdir().unwrap();
+
+        let snapshot = import_snapshot(instance_dir.path(), &archive_path).unwrap();
+        assert_eq!(snapshot.file_name, "vm-1700000000000.zip");
+        assert_eq!(
+            snapshot.size_bytes,
+            fs::metadata(&archive_path).unwrap().len()
+        );
+
+        fs::write(app_dir.join("bitcoin").join("blocks.dat"), b"newer blocks").unwrap();
+        write_archive(&app_dir, &archive_path).unwrap();
+        let error = import_snapshot(instance_dir.path(), &archive_path).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidInput);
+        assert_eq!(
+            fs::metadata(&snapshot.path).unwrap().len(),
+            snapshot.size_bytes
+        );
+
+        let not_a_snapshot = source.path().join("notes.zip");
+        fs::write(&not_a_snapshot, b"notes").unwrap();
+        let error = import_snapshot(instance_dir.path(), &not_a_snapshot).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidInput);
+
+        let restore_dir = instance_dir.path().join("restored");
+        extract_archive(&snapshot.path, &restore_dir).unwrap();
+        let restored = restore_dir.join("app").join("bitcoin").join("blocks.dat");
+        assert_eq!(fs::read(restored).unwrap(), b"blocks");
+    }
+}
@@ -14,12 +14,25 @@ vi.mock('../../lib/Countries', async () => {
 });
 
 vi.mock('../../lib/tauriApi', async () => {
+  const actual = await vi.importActual<typeof import('../../lib/tauriApi')>('../../lib/tauriApi');
   return {
+    ...actual,
     invokeWithTimeout: vi.fn((command: string, args: any) => {
       console.log('invokeWithTimeout', command, args);
 
       return Promise.resolve();
     }),
+    commands: new Proxy(
+      {},
+      {
+        get: (_, command) =>
+          vi.fn((...args: any[]) => {
+            console.log('commands', command, args);
+
+            return Promise.resolve();
+          }),
+      },
+    ),
   };
 });
 
@@ -65,6 +65,8 @@ export const ConfigServerDetailsSchema = z.object({
   sshUser: z.string(),
   type: z.nativeEnum(ServerType),
   workDir: z.string(),
+  // what runs a LocalComputer server. Missing on machines created before QEMU was an option
+  localVmBackend: z.enum(['docker', 'qemu']).optional(),
 });
 
 export const ConfigInstallStep = z.object({
@@ -1,5 +1,5 @@
-export default interface ISecurity {
-  masterMnemonic: string;
-  sshPublicKey: string;
-  sshPrivateKeyPath: string;
-}
+import type { Security } from '../lib/bindings.ts';
+
+type ISecurity = Security;
+
+export default ISecurity;
@@ -25,6 +25,7 @@ import { BITCOIN_BLOCK_MILLIS, ESPLORA_HOST } from './Env.ts';
 import { type AddressTxsUtxo } from '@mempool/mempool.js/lib/interfaces/bitcoin/addresses';
 import { type TxStatus } from '@mempool/mempool.js/lib/interfaces/bitcoin/transactions';
 import { MiningFrames } from '@argonprotocol/commander-core';
+import { invokeWithTimeout } from './tauriApi.ts';
 
 export default class BitcoinLocksStore {
   data: {
@@ -191,15 +192,34 @@ export default class BitcoinLocksStore {
 
   async getNextUtxoPubkey(args: { vault: Vault; bip39Seed: Uint8Array }) {
     const { vault, bip39Seed } = args;
-    const table = await this.getTable();
 
-    // get bitcoin xpriv to generate the pubkey
-    const nextIndex = await table.getNextVaultHdKeyIndex(vault.vaultId);
-    const hdPath = `m/1018'/0'/${vault.vaultId}'/0/${nextIndex}`;
-    const ownerBitcoinXpriv = getChildXpriv(bip39Seed, hdPath, this.bitcoinNetwork);
-    const ownerBitcoinPubkey = getCompressedPubkey(ownerBitcoinXpriv.publicKey!);
+    // get bitcoin xpriv to generate the pubkey. The index is reserved in rust so concurrent locks can't reuse it.
+    const { hdPath, hdIndex } = await invokeWithTimeout<{ hdPath: string; hdIndex: number }>(
+      'reserve_bitcoin_lock_hd_path',
+      { vaultId: vault.vaultId },
+      10_000,
+    );
+    try {
+      const ownerBitcoinXpriv = getChildXpriv(bip39Seed, hdPath, this.bitcoinNetwork);
+      const ownerBitcoinPubkey = getCompressedPubkey(ownerBitcoinXpriv.publicKey!);
 
-    return { ownerBitcoinPubkey, hdPath };
+      return { ownerBitcoinPubkey, hdPath, hdIndex };
+    } catch (e) {
+      await this.releaseUtxoPubkey({ vaultId: vault.vaultId, hdIndex });
+      throw e;
+    }
+  }
+
+  /**
+   * Hands a reserved hd index back so the next lock can use it. Only call this when the lock was never submitted,
+   * or its transaction failed.
+   */
+  async releaseUtxoPubkey(args: { vaultId: number; hdIndex: number }) {
+    try {
+      await invokeWithTimeout('release_bitcoin_lock_hd_path', args, 10_000);
+    } catch (e) {
+      console.error(`Failed to release hd index ${args.hdIndex} for vault ${args.vaultId}`, e);
+    }
   }
 
   async createInitializeTx(args: {
@@ -212,8 +232,27 @@ export default class BitcoinLocksStore {
     tip?: bigint;
     txProgressCallback?: ITxProgressCallback;
   }) {
-    const { ownerBitcoinPubkey, hdPath } = await this.getNextUtxoPubkey(args);
-    const { vault, argonKeyring, maxMicrogonSpend, tip = 0n, addingVaultSpace = 0n } = args;
+    const { ownerBitcoinPubkey, hdPath, hdIndex } = await this.getNextUtxoPubkey(args);
+    try {
+      const result = await this.#createInitializeTx({ ...args, ownerBitcoinPubkey });
+      return { hdPath, hdIndex, ownerBitcoinPubkey, ...result };
+    } catch (e) {
+      await this.releaseUtxoPubkey({ vaultId: args.vault.vaultId, hdIndex });
+      throw e;
+    }
+  }
+
+  async #createInitializeTx(args: {
+    vault: Vault;
+    ownerBitcoinPubkey: Uint8Array;
+    argonKeyring: KeyringPair;
+    microgonLiquidity: bigint;
+    maxMicrogonSpend?: bigint;
+    addingVaultSpace?: bigint;
+    tip?: bigint;
+    txProgressCallback?: ITxProgressCallback;
+  }) {
+    const { vault, ownerBitcoinPubkey, argonKeyring, maxMicrogonSpend, tip = 0n, addingVaultSpace = 0n } = args;
 
     const client = await getMainchainClient(false);
 
@@ -251,11 +290,10 @@ export default class BitcoinLocksStore {
     }
     const { tx, securityFee } = await this.#bitcoinLocksApi.createInitializeLockTx({
       ...args,
-      ownerBitcoinPubkey,
       satoshis,
     });
 
-    return { hdPath, tx, ownerBitcoinPubkey, satoshis, securityFee };
+    return { tx, satoshis, securityFee };
   }
 
   public async saveBitcoinLock(args: {
@@ -29,8 +29,9 @@ import ISecurity from '../interfaces/ISecurity';
 import { getMainchainClients } from '../stores/mainchain';
 import { WalletBalances } from './WalletBalances';
 import { SECURITY } from './Env.ts';
-import { invokeWithTimeout } from './tauriApi.ts';
+import { commands, invokeWithTimeout } from './tauriApi.ts';
 import { LocalMachine } from './LocalMachine.ts';
+import { VmProgress } from './bindings.ts';
 
 export class Config {
   public readonly version: string = packageJson.version;
@@ -42,6 +43,8 @@ export class Config {
     return this._loadedDeferred.promise;
   }
   public hasDbMigrationError: boolean;
+  // the local machine's startup progress while load() waits on it
+  public localMachineProgress: VmProgress | null = null;
 
   private _loadedDeferred!: IDeferred<void>;
 
@@ -167,7 +170,9 @@ export class Config {
       }
 
       if (loadedData.serverDetails.type === ServerType.LocalComputer && loadedData.isMinerInstalled) {
-        const { sshPort } = await LocalMachine.activate();
+        const { sshPort } = await LocalMachine.activate(progress => (this.localMachineProgress = progress)).finally(
+          () => (this.localMachineProgress = null),
+        );
         await invokeWithTimeout('toggle_nosleep', { enable: true }, 5000);
         loadedData.serverDetails.ipAddress = `127.0.0.1`;
         loadedData.serverDetails.port = sshPort;
@@ -175,7 +180,9 @@ export class Config {
         rawData[dbFields.serverDetails] = JsonExt.stringify(loadedData.serverDetails, 2);
       }
       const dataToSave = Config.extractDataToSave(fieldsToSave, rawData);
-      await db.configTable.insertOrReplace(dataToSave);
+      if (!db.hasMigrationError) {
+        await db.configTable.insertOrReplace(dataToSave);
+      }
 
       if (this.miningAccount.address !== loadedData.miningAccountAddress) {
         await tauriMessage(
@@ -191,6 +198,7 @@ export class Config {
       this._loadedData = loadedData as IConfig;
       this._rawData = rawData;
       this._loadedDeferred.resolve();
+      void this._updateInstanceMetadata();
       if (this.miningAccountHadPreviousLife && !this.miningAccountPreviousHistory) {
         await this._bootupFromMiningAccountPreviousHistory();
       }
@@ -569,6 +577,9 @@ export class Config {
     if (Object.keys(dataToSave).length === 0) return;
 
     await this._db.configTable.insertOrReplace(dataToSave);
+    if (dbFields.serverDetails in dataToSave) {
+      void this._updateInstanceMetadata();
+    }
   }
 
   public resetField(field: keyof typeof dbFields) {
@@ -581,6 +592,14 @@ export class Config {
     if (!this.isLoaded) throw new Error('Config is not yet loaded. You must wait for isLoaded to be true.');
   }
 
+  private async _updateInstanceMetadata() {
+    try {
+      await commands.updateInstanceMetadata(this.serverDetails.type, this.miningAccount.address);
+    } catch (e) {
+      console.warn('Config: Unable to update instance metadata', e);
+    }
+  }
+
   private _tryFieldsToSave(field: keyof typeof dbFields, value: any) {
     const stringifiedValue = JsonExt.stringify(value, 2);
     if (this._rawData[field] === stringifiedValue) return;
@@ -1,4 +1,5 @@
 import PluginSql, { QueryResult } from '@tauri-apps/plugin-sql';
+import { invoke, isCommanderError } from './tauriApi';
 import { CohortFramesTable } from './db/CohortFramesTable';
 import { CohortsTable } from './db/CohortsTable';
 import { ConfigTable } from './db/ConfigTable';
@@ -37,19 +38,28 @@ export class Db {
     this.bitcoinLocksTable = new BitcoinLocksTable(this);
   }
 
-  static async load(retries: number = 0): Promise<Db> {
+  static async load(): Promise<Db> {
+    // migrations are owned by the Rust side. A failed run restores the pre-migration snapshot, which
+    // is opened read-only so the user can still see their data while they recreate it.
+    let hasMigrationError = false;
     try {
-      const sql = await PluginSql.load(`sqlite:${Db.relativePath}`);
-      return new Db(sql, !!retries);
+      await invoke('await_db_migrations');
     } catch (error) {
-      if (typeof error == 'string' && error.startsWith('migration ') && retries < 1) {
-        return this.load(retries + 1);
-      }
-      throw error;
+      // another process owns this instance's database, so don't touch it at all
+      if (isCommanderError(error, 'InstanceInUse')) throw error;
+      console.error('Database migrations failed:', error);
+      hasMigrationError = true;
     }
+    const sql = await PluginSql.load(`sqlite:${Db.relativePath}`);
+    return new Db(sql, hasMigrationError);
   }
 
+  // Frames and Cohorts are written through the Rust commands. The other tables still write raw sql here, which is why
+  // the app keeps the sql:allow-execute capability.
   async execute(query: string, bindValues?: unknown[]): Promise<QueryResult> {
+    if (this.hasMigrationError) {
+      throw new Error(`Database is read-only until it's recreated after a failed migration: ${query}`);
+    }
     try {
       return await this.sql.execute(query, bindValues);
     } catch (error) {
@@ -74,6 +84,7 @@ export class Db {
   public async reconnect() {
     const sql = await PluginSql.load(`sqlite:${Db.relativePath}`);
     this.sql = sql;
+    this.hasMigrationError = false;
   }
 
   public static get relativeDir() {
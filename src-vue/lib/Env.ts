@@ -4,13 +4,27 @@ import ISecurity from '../interfaces/ISecurity.ts';
 console.log('__ARGON_NETWORK_NAME__', __ARGON_NETWORK_NAME__);
 console.log('__COMMANDER_INSTANCE__', __COMMANDER_INSTANCE__);
 
+const CHAIN_TO_NETWORK: Record<string, string> = {
+  'dev-docker': 'dev-docker',
+  local: 'localnet',
+  testnet: 'testnet',
+  mainnet: 'mainnet',
+};
+
 export const NETWORK_NAME = __ARGON_NETWORK_NAME__ || 'mainnet';
-MiningFrames.setNetwork(NETWORK_NAME as any);
 export const ENABLE_AUTO_UPDATE = __COMMANDER_ENABLE_AUTOUPDATE__ ?? false;
 
 export const SERVER_ENV_VARS = __SERVER_ENV_VARS__ ?? {};
-const networkConfig = NetworkConfig[NETWORK_NAME as keyof typeof NetworkConfig] ?? NetworkConfig.mainnet;
-export const NETWORK_URL = networkConfig.archiveUrl;
+// set when the network's env file couldn't be read. The app shows it instead of loading
+export const SERVER_ENV_ERROR: string | null = __SERVER_ENV_ERROR__ ?? null;
+export const IS_CUSTOM_NETWORK = !(NETWORK_NAME in NetworkConfig);
+// custom networks (an .env.<name> file in the app config dir) reuse the timings of the chain they run
+const CONFIG_NETWORK_NAME = (
+  IS_CUSTOM_NETWORK ? CHAIN_TO_NETWORK[SERVER_ENV_VARS.ARGON_CHAIN] : NETWORK_NAME
+) as keyof typeof NetworkConfig;
+MiningFrames.setNetwork(CONFIG_NETWORK_NAME ?? 'mainnet');
+const networkConfig = NetworkConfig[CONFIG_NETWORK_NAME] ?? NetworkConfig.mainnet;
+export const NETWORK_URL = IS_CUSTOM_NETWORK ? SERVER_ENV_VARS.ARGON_ARCHIVE_NODE : networkConfig.archiveUrl;
 export const [INSTANCE_NAME, INSTANCE_PORT] = (__COMMANDER_INSTANCE__ || 'default:1420').split(':');
 
 export const env = (import.meta as any).env ?? {};
@@ -2,7 +2,7 @@ import { JsonExt } from '@argonprotocol/commander-core';
 import { type Config } from './Config';
 import Restarter from './Restarter';
 import { Db } from './Db';
-import { invokeWithTimeout } from './tauriApi';
+import { commands, withTimeout } from './tauriApi';
 import { ITryServerData, SSH } from './SSH';
 import { IConfigServerDetails } from '../interfaces/IConfig';
 
@@ -33,7 +33,8 @@ export default class Importer {
 
     const restarter = new Restarter(this.dbPromise, this.config);
     await restarter.recreateLocalDatabase();
-    await invokeWithTimeout('overwrite_security', { ...this.data.security }, 10_000);
+    const { masterMnemonic, sshPublicKey, sshPrivateKeyPath } = this.data.security;
+    await withTimeout(commands.overwriteSecurity(masterMnemonic, sshPublicKey, sshPrivateKeyPath), 10_000);
     await this.config.load();
 
     this.config.oldestFrameIdToSync = this.data.oldestFrameIdToSync ?? this.config.oldestFrameIdToSync;
@@ -60,7 +61,7 @@ export default class Importer {
 
   async importFromMnemonic(mnemonic: string) {
     const restarter = new Restarter(this.dbPromise, this.config);
-    await invokeWithTimeout('overwrite_mnemonic', { mnemonic }, 10_000);
+    await withTimeout(commands.overwriteMnemonic(mnemonic), 10_000);
     await restarter.recreateLocalDatabase();
     await restarter.restart();
     this.onFinished?.();
@@ -17,6 +17,7 @@ import { exit as tauriExit } from '@tauri-apps/plugin-process';
 import { Server } from './Server';
 import { invokeWithTimeout } from './tauriApi.ts';
 import { MiningMachine, MiningMachineError } from './MiningMachine.ts';
+import { LocalMachine } from './LocalMachine.ts';
 
 dayjs.extend(utc);
 
@@ -44,16 +45,22 @@ export default class Installer {
   public isRunning: boolean;
   public isRunningInBackground: boolean;
   public fileUploadProgress: number = 0;
+  // set while a local machine is being created, from its progress events
+  public serverConnectProgress: number = 0;
 
   public reasonToSkipInstall: string;
   public reasonToSkipInstallData: any;
 
   public hasMiningMachineError = '';
 
-  public get isDockerHostProxy(): boolean {
+  public get isLocalVm(): boolean {
     return this.config.serverDetails.type === ServerType.LocalComputer;
   }
 
+  public get isDockerHostProxy(): boolean {
+    return this.isLocalVm && this.config.serverDetails.localVmBackend !== 'qemu';
+  }
+
   private isLoadedDeferred!: IDeferred<void>;
   private config: Config;
   private installerCheck: InstallerCheck;
@@ -169,6 +176,7 @@ export default class Installer {
 
     this.installerCheck.shouldUseCachedInstallSteps = true;
     this.fileUploadProgress = 0;
+    this.serverConnectProgress = 0;
     this.installerCheck.start();
 
     try {
@@ -176,7 +184,9 @@ export default class Installer {
 
       if (!this.config.isMiningMachineCreated) {
         try {
-          const serverDetails = await MiningMachine.setup(this.config);
+          const serverDetails = await MiningMachine.setup(this.config, progress => {
+            this.serverConnectProgress = LocalMachine.getProgressPct(progress.phase);
+          });
           this.config.serverDetails = serverDetails;
           this.config.isMiningMachineCreated = true;
           this.remoteFilesNeedUpdating = true;
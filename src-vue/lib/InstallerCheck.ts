@@ -190,7 +190,9 @@ export class InstallerCheck {
     stepPending: IConfigInstallStep,
     estimatedMinutes: number,
   ): Promise<number> {
-    if (stepName === InstallStepKey.FileUpload) {
+    if (stepName === InstallStepKey.ServerConnect && this.installer.serverConnectProgress > 0) {
+      return this.installer.serverConnectProgress;
+    } else if (stepName === InstallStepKey.FileUpload) {
       return this.installer.fileUploadProgress;
     } else if (stepName === InstallStepKey.BitcoinInstall) {
       return await this.server.fetchBitcoinInstallProgress();
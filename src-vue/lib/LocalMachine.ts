@@ -1,50 +1,210 @@
 import { DOCKER_COMPOSE_PROJECT_NAME } from './Server.ts';
-import { invokeWithTimeout } from './tauriApi.ts';
+import { commands, isCommanderError, withTimeout } from './tauriApi.ts';
 import { message } from '@tauri-apps/plugin-dialog';
+import {
+  events,
+  PortConflict,
+  VmBackend,
+  VmLogLine,
+  VmPhase,
+  VmProgress,
+  VmResourceLimits,
+  VmSnapshot,
+} from './bindings.ts';
+import { SERVER_ENV_VARS } from './Env.ts';
 
 export class LocalMachine {
-  public static async create(sshPubkey: string): Promise<{ sshPort: number }> {
+  public static async create(
+    sshPubkey: string,
+    limits: VmResourceLimits = { cpus: null, memoryBytes: null, reservedDiskBytes: null },
+    onProgress?: (progress: VmProgress) => void,
+  ): Promise<{ sshPort: number }> {
     console.log(`Creating local machine`);
-    const sshPort = await invokeWithTimeout<number>(
-      'create_local_vm',
-      { envText: `COMPOSE_PROJECT_NAME=${DOCKER_COMPOSE_PROJECT_NAME}\nSSH_PUBKEY="${sshPubkey.trim()}"` },
-      120_000,
+    const backend = await this.getBackend();
+    const sshPort = await this.withProgress(
+      () =>
+        commands.createLocalVm(
+          `COMPOSE_PROJECT_NAME=${DOCKER_COMPOSE_PROJECT_NAME}\nSSH_PUBKEY="${sshPubkey.trim()}"`,
+          limits,
+        ),
+      // the image build can take several minutes on a first run, and a QEMU VM downloads its image
+      // and may boot emulated
+      backend === 'qemu' ? 1_800_000 : 600_000,
+      onProgress,
     );
     console.log(`Local machine created SSH port: ${sshPort}`);
 
     return { sshPort };
   }
 
-  public static async checkBlockedPorts(): Promise<number[]> {
+  public static async checkBlockedPorts(): Promise<PortConflict[]> {
     console.log(`Finding blocked (needed) ports for local machine`);
-    const neededPorts = await invokeWithTimeout<number[]>('check_needed_ports', {}, 10_000);
-    if (neededPorts.length) {
-      console.log(`Local machine is blocked on SSH ports: [${neededPorts.join(',')}]`);
+    const conflicts = await withTimeout(commands.checkNeededPorts(), 30_000);
+    if (conflicts.length) {
+      console.log(`Local machine is blocked on ports: [${conflicts.map(x => x.port).join(',')}]`, conflicts);
     }
-    return neededPorts;
+    return conflicts;
+  }
+
+  /**
+   * Moves each conflicting port to its suggested replacement for this instance.
+   */
+  public static async remapBlockedPorts(conflicts: PortConflict[]): Promise<void> {
+    const ports: { [key in string]: number } = {};
+    for (const conflict of conflicts) {
+      if (conflict.suggestedPort === null) {
+        throw new Error(`No free port was found to replace ${conflict.port}`);
+      }
+      ports[conflict.envKey] = conflict.suggestedPort;
+    }
+    const networkEnv = await withTimeout(commands.remapNeededPorts(ports), 30_000);
+    // the window was handed the env at load, so keep it in step with the new ports
+    Object.assign(SERVER_ENV_VARS, networkEnv);
+  }
+
+  /**
+   * What runs the local machine, or would for a new one. Null when neither Docker nor QEMU is available.
+   */
+  public static async getBackend(): Promise<VmBackend | null> {
+    return await withTimeout(commands.getLocalVmBackend(), 60e3);
   }
 
   public static async isDockerRunning(): Promise<boolean> {
-    return await invokeWithTimeout<boolean>('is_docker_running', {}, 60e3);
+    return await withTimeout(commands.isDockerRunning(), 60e3);
   }
 
-  public static async activate(): Promise<{ sshPort: number }> {
+  public static async activate(onProgress?: (progress: VmProgress) => void): Promise<{ sshPort: number }> {
     console.log(`Loading local machine`);
-    while (!(await this.isDockerRunning())) {
+    const backend = await this.getBackend();
+    while (backend === 'docker' && !(await this.isDockerRunning())) {
       await message(`Docker does not appear to be running. Please open Docker Desktop and try again.`, {
         title: 'Docker Not Running',
         kind: 'error',
         okLabel: "It's Running Now",
       });
     }
-    const sshPort = await invokeWithTimeout<number>('activate_local_vm', {}, 120_000);
+    // an emulated QEMU VM can take several minutes to boot
+    const timeoutMs = backend === 'qemu' ? 900_000 : 120_000;
+    const sshPort = await this.withProgress(() => commands.activateLocalVm(), timeoutMs, onProgress);
     console.log(`Local machine SSH port: ${sshPort}`);
     return { sshPort };
   }
 
+  public static async upgrade(onProgress?: (progress: VmProgress) => void): Promise<{ sshPort: number }> {
+    console.log(`Upgrading local machine`);
+    const sshPort = await this.withProgress(() => commands.upgradeLocalVm(), 600_000, onProgress);
+    console.log(`Local machine upgraded SSH port: ${sshPort}`);
+    return { sshPort };
+  }
+
+  public static async setLimits(limits: VmResourceLimits) {
+    console.log(`Setting local machine limits`, limits);
+    await withTimeout(commands.setLocalVmLimits(limits), 60e3);
+  }
+
+  public static async getLogs(lines = 500): Promise<VmLogLine[]> {
+    return await withTimeout(commands.getLocalVmLogs(lines), 60e3);
+  }
+
+  /**
+   * Calls onLine for each new log line until the returned function is called.
+   */
+  public static async followLogs(onLine: (line: VmLogLine) => void): Promise<() => Promise<void>> {
+    const unlisten = await events.vmLogLine.listen(event => onLine(event.payload));
+    try {
+      await commands.followLocalVmLogs();
+    } catch (error) {
+      unlisten();
+      throw error;
+    }
+    return async () => {
+      unlisten();
+      await commands.stopLocalVmLogs();
+    };
+  }
+
+  /**
+   * Writes recent VM logs to a temp dir for a troubleshooting zip. Returns null if there's no local VM. Pass the
+   * dir to removeLogExport once it's zipped.
+   */
+  public static async exportLogs(lines = 5_000): Promise<string | null> {
+    try {
+      return await withTimeout(commands.exportLocalVmLogs(lines), 60e3);
+    } catch (error) {
+      if (!isCommanderError(error, 'VmNotFound')) {
+        console.error('Unable to export local machine logs', error);
+      }
+      return null;
+    }
+  }
+
+  public static async removeLogExport(exportDir: string): Promise<void> {
+    try {
+      await commands.removeLocalVmLogsExport(exportDir);
+    } catch (error) {
+      console.error('Unable to remove local machine log export', error);
+    }
+  }
+
+  public static async createSnapshot(): Promise<VmSnapshot> {
+    console.log(`Snapshotting local machine`);
+    // archiving synced chain data can take a long time
+    const snapshot = await withTimeout(commands.createLocalVmSnapshot(), 3 * 60 * 60e3);
+    console.log(`Local machine snapshot created: ${snapshot.fileName}`);
+    return snapshot;
+  }
+
+  public static async listSnapshots(): Promise<VmSnapshot[]> {
+    return await withTimeout(commands.listLocalVmSnapshots(), 10e3);
+  }
+
+  public static async restoreSnapshot(fileName: string) {
+    console.log(`Restoring local machine from ${fileName}`);
+    await withTimeout(commands.restoreLocalVmSnapshot(fileName), 3 * 60 * 60e3);
+    console.log(`Local machine restored`);
+  }
+
+  /**
+   * A rough completion percentage for a progress phase, for progress bars. Stays below 100 until the VM is up.
+   */
+  public static getProgressPct(phase: VmPhase): number {
+    const pctByPhase: Record<VmPhase, number> = {
+      writingFiles: 5,
+      buildingImage: 15,
+      starting: 60,
+      waitingForHealth: 75,
+      resolvingPort: 90,
+      succeeded: 99,
+      failed: 0,
+    };
+    return pctByPhase[phase];
+  }
+
+  /**
+   * Calls run once the progress listener is attached, so no early events are missed.
+   */
+  private static async withProgress<T>(
+    run: () => Promise<T>,
+    timeoutMs: number,
+    onProgress?: (progress: VmProgress) => void,
+  ): Promise<T> {
+    const unlisten = await events.vmProgress.listen(event => {
+      const progress = event.payload;
+      if (progress.output === null) {
+        console.log(`Local machine: ${progress.phase}`);
+      }
+      onProgress?.(progress);
+    });
+    try {
+      return await withTimeout(run(), timeoutMs);
+    } finally {
+      unlisten();
+    }
+  }
+
   public static async remove() {
     console.log(`Removing local machine`);
-    await invokeWithTimeout('remove_local_vm', {}, 60e3);
+    await withTimeout(commands.removeLocalVm(), 60e3);
     console.log(`Local machine removed`);
   }
 }
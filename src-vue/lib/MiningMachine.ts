@@ -6,6 +6,7 @@ import { SSH } from './SSH';
 import { invokeWithTimeout } from './tauriApi';
 import { SSHFingerprint } from './SSHFingerprint';
 import { IS_TEST } from './Env';
+import { PortConflict, VmProgress } from './bindings';
 
 export class MiningMachineError extends Error {
   constructor(message: string) {
@@ -28,7 +29,10 @@ type IDroplet = {
 };
 
 export class MiningMachine {
-  public static async setup(config: Config): Promise<IConfigServerDetails> {
+  public static async setup(
+    config: Config,
+    onLocalMachineProgress?: (progress: VmProgress) => void,
+  ): Promise<IConfigServerDetails> {
     const sshPublicKey = config.security.sshPublicKey;
 
     if (config.serverCreation?.digitalOcean) {
@@ -38,7 +42,7 @@ export class MiningMachine {
       const { port, sshUser, ipAddress } = config.serverCreation.customServer;
       return await this.setupCustomServer(port, sshUser, ipAddress, config);
     } else if (config.serverCreation?.localComputer) {
-      return await this.setupLocalComputer(config.security.sshPublicKey);
+      return await this.setupLocalComputer(config.security.sshPublicKey, onLocalMachineProgress);
     } else {
       throw new MiningMachineError('No server creation data found');
     }
@@ -178,6 +182,7 @@ export class MiningMachine {
     const response = {
       isDockerStarted: false,
       blockedPorts: [] as number[],
+      portConflicts: [] as PortConflict[],
     };
     try {
       response.isDockerStarted = await LocalMachine.isDockerRunning();
@@ -187,7 +192,8 @@ export class MiningMachine {
 
     // check for blocked ports
     try {
-      response.blockedPorts = await LocalMachine.checkBlockedPorts();
+      response.portConflicts = await LocalMachine.checkBlockedPorts();
+      response.blockedPorts = response.portConflicts.map(x => x.port);
     } catch (e) {
       /* no action */
     }
@@ -195,7 +201,10 @@ export class MiningMachine {
     return response;
   }
 
-  public static async setupLocalComputer(sshPublicKey: string): Promise<IConfigServerDetails> {
+  public static async setupLocalComputer(
+    sshPublicKey: string,
+    onProgress?: (progress: VmProgress) => void,
+  ): Promise<IConfigServerDetails> {
     const newServerDetails: IConfigServerDetails = {
       type: ServerType.LocalComputer,
       ipAddress: `127.0.0.1`,
@@ -204,8 +213,10 @@ export class MiningMachine {
       workDir: '/app',
     };
 
+    // without docker, linux hosts can run the machine under QEMU instead
+    const backend = await LocalMachine.getBackend();
     const dockerChecks = await this.runDockerChecks();
-    if (!dockerChecks.isDockerStarted) {
+    if (!backend) {
       throw new MiningMachineError('Docker is not running');
     } else if (dockerChecks.blockedPorts.length) {
       throw new MiningMachineError(
@@ -213,8 +224,9 @@ export class MiningMachine {
       );
     }
     try {
-      const { sshPort } = await LocalMachine.create(sshPublicKey);
+      const { sshPort } = await LocalMachine.create(sshPublicKey, undefined, onProgress);
       newServerDetails.port = sshPort;
+      newServerDetails.localVmBackend = backend;
     } catch (err) {
       throw new MiningMachineError(
         `Something went wrong trying to create your local Docker server. Try restarting Docker.`,
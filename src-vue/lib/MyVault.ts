@@ -431,11 +431,12 @@ export class MyVault {
       txs.push(tx);
     }
     let bitcoinArgs: { satoshis: bigint; hdPath: string; securityFee: bigint } | undefined;
+    let bitcoinHdIndex: number | undefined;
     if (!this.metadata?.personalUtxoId && rules.personalBtcPct > 0n) {
       const personalBtcInMicrogons = bigNumberToBigInt(
         BigNumber(rules.personalBtcPct).div(100).times(microgonsForSecuritization),
       );
-      const { tx, satoshis, hdPath, securityFee } = await bitcoinLocksStore.createInitializeTx({
+      const { tx, satoshis, hdPath, hdIndex, securityFee } = await bitcoinLocksStore.createInitializeTx({
         argonKeyring,
         bip39Seed,
         vault,
@@ -444,6 +445,7 @@ export class MyVault {
         tip,
       });
       bitcoinArgs = { satoshis, hdPath, securityFee };
+      bitcoinHdIndex = hdIndex;
       txs.push(tx);
     }
 
@@ -453,9 +455,19 @@ export class MyVault {
     txProgressCallback(5);
     const table = await this.getTable();
 
-    const txSubmitter = new TxSubmitter(client, client.tx.utility.batchAll(txs), argonKeyring);
-    const txResult = await txSubmitter.submit({ tip, txProgressCallback });
-    const blockHash = await txResult.inBlockPromise;
+    let txResult: TxResult;
+    let blockHash: Uint8Array;
+    try {
+      const txSubmitter = new TxSubmitter(client, client.tx.utility.batchAll(txs), argonKeyring);
+      txResult = await txSubmitter.submit({ tip, txProgressCallback });
+      blockHash = await txResult.inBlockPromise;
+    } catch (e) {
+      // the batch is atomic, so a failed submission means the lock was never created
+      if (bitcoinHdIndex !== undefined) {
+        await bitcoinLocksStore.releaseUtxoPubkey({ vaultId, hdIndex: bitcoinHdIndex });
+      }
+      throw e;
+    }
     const api = await client.at(blockHash);
     const tick = await api.query.ticks.currentTick();
 
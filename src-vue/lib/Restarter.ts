@@ -1,4 +1,4 @@
-import { invoke } from '@tauri-apps/api/core';
+import { invoke, isCommanderError } from './tauriApi';
 import { Db } from './Db';
 import { remove, BaseDirectory } from '@tauri-apps/plugin-fs';
 import { AdvancedRestartOption } from '../interfaces/IAdvancedRestartOption';
@@ -37,9 +37,9 @@ export default class Restarter {
       } catch (error) {
         const errorString = String(error).toLowerCase();
         console.log('Error connecting to server to wipe it:', errorString);
-        if (errorString.includes('connection refused') || errorString.includes('host unreachable')) {
+        if (isCommanderError(error, 'SshConnectionRefused', 'SshHostUnreachable')) {
           // Server is likely already wiped, continue
-          if (installer.isDockerHostProxy) {
+          if (installer.isLocalVm) {
             await LocalMachine.remove().catch(() => null);
           }
         }
@@ -1,6 +1,6 @@
 import { Config } from './Config';
 import { IConfigServerDetails } from '../interfaces/IConfig';
-import { InvokeTimeout } from './tauriApi';
+import { InvokeTimeout, isCommanderError } from './tauriApi';
 import { SSHConnection } from './SSHConnection';
 import { IBiddingRules } from '@argonprotocol/commander-core';
 import { Server } from './Server';
@@ -81,7 +81,7 @@ export class SSH {
   }
 
   private static shouldReconnect(error: Error): boolean {
-    return error instanceof InvokeTimeout || String(error) === 'No SSH connection';
+    return error instanceof InvokeTimeout || isCommanderError(error, 'NoConnection');
   }
 
   public static async runCommand(command: string, retries = 3): Promise<[string, number]> {
@@ -90,7 +90,7 @@ export class SSH {
       return await connection.runCommandWithTimeout(command, 60 * 1e3);
     } catch (e) {
       const hasRetries = retries > 0;
-      let shouldRetry = e === 'SSHCommandMissingExitStatus' && hasRetries;
+      let shouldRetry = isCommanderError(e, 'SshMissingExitStatus') && hasRetries;
       if (this.shouldReconnect(e as any) && hasRetries) {
         await this.reconnect();
         shouldRetry = true;
@@ -1,4 +1,4 @@
-import { invoke, invokeWithTimeout } from './tauriApi';
+import { commands, isCommanderError, withTimeout } from './tauriApi';
 import { listen } from '@tauri-apps/api/event';
 import { IConfigServerDetails, ServerType } from '../interfaces/IConfig.ts';
 
@@ -18,6 +18,7 @@ export class SSHConnection {
   public port: number;
   public username: string;
   public privateKeyPath?: string;
+  public isLocalVm = false;
   public isDockerHostProxy = false;
   public isDestroyed = false;
 
@@ -26,7 +27,9 @@ export class SSHConnection {
     this.port = sshConfig.port ?? 22;
     this.username = sshConfig.sshUser;
     this.privateKeyPath = sshConfig.privateKeyPath;
-    this.isDockerHostProxy = sshConfig.type === ServerType.LocalComputer;
+    this.isLocalVm = sshConfig.type === ServerType.LocalComputer;
+    // a QEMU machine runs its own docker, so it's set up like any other server
+    this.isDockerHostProxy = this.isLocalVm && sshConfig.localVmBackend !== 'qemu';
   }
 
   public async connect(retries = 3): Promise<void> {
@@ -36,23 +39,20 @@ export class SSHConnection {
 
     this.isConnected = false;
     this.isConnectedPromise = new Promise(async (resolve, reject) => {
-      const sshConfig = {
-        address: this.address,
-        host: this.host,
-        port: this.port,
-        username: this.username,
-        privateKeyPath: this.privateKeyPath,
-      };
-      if (!sshConfig.host) {
+      if (!this.host) {
         reject(new Error('No SSH host config provided'));
         return;
       }
+      if (!this.privateKeyPath) {
+        reject(new Error('No SSH private key path provided'));
+        return;
+      }
       try {
-        await invoke('open_ssh_connection', sshConfig);
+        await commands.openSshConnection(this.address, this.host, this.port, this.username, this.privateKeyPath);
         this.isConnected = true;
         resolve();
       } catch (error) {
-        if (String(error).toLowerCase().includes('connection refused') && retries > 0 && !this.isDestroyed) {
+        if (isCommanderError(error, 'SshConnectionRefused') && retries > 0 && !this.isDestroyed) {
           console.log(`Connection refused... retrying ${3 - retries + 1}/3`);
           await new Promise(r => setTimeout(r, 1000 * (4 - retries)));
           await this.close();
@@ -66,13 +66,11 @@ export class SSHConnection {
   }
 
   public async runCommandWithTimeout(command: string, timeout: number): Promise<[string, number]> {
-    const payload = { address: this.address, command };
-    return await invokeWithTimeout('ssh_run_command', payload, timeout);
+    return await withTimeout(commands.sshRunCommand(this.address, command), timeout);
   }
 
   public async uploadFileWithTimeout(contents: string, remotePath: string, timeout: number): Promise<void> {
-    const payload = { address: this.address, contents, remotePath };
-    return await invokeWithTimeout('ssh_upload_file', payload, timeout);
+    await withTimeout(commands.sshUploadFile(this.address, contents, remotePath), timeout);
   }
 
   public async uploadEmbeddedFileWithTimeout(
@@ -89,8 +87,10 @@ export class SSHConnection {
       }
     });
     try {
-      const payload = { address: this.address, localRelativePath, remotePath, eventProgressKey };
-      await invokeWithTimeout('ssh_upload_embedded_file', payload, timeout);
+      await withTimeout(
+        commands.sshUploadEmbeddedFile(this.address, localRelativePath, remotePath, eventProgressKey),
+        timeout,
+      );
     } catch (e) {
       unsub();
       throw e;
@@ -111,8 +111,10 @@ export class SSHConnection {
       }
     });
     try {
-      const payload = { address: this.address, downloadPath, remotePath, eventProgressKey };
-      await invokeWithTimeout('ssh_download_file', payload, timeout);
+      await withTimeout(
+        commands.sshDownloadFile(this.address, remotePath, downloadPath, eventProgressKey),
+        timeout,
+      );
     } catch (e) {
       unsub();
       throw e;
@@ -120,8 +122,7 @@ export class SSHConnection {
   }
 
   public async close(destroy = false): Promise<void> {
-    const payload = { address: this.address };
-    await invokeWithTimeout('close_ssh_connection', payload, 5_000);
+    await withTimeout(commands.closeSshConnection(this.address), 5_000);
     this.isConnectedPromise = undefined;
     this.isConnected = false;
     if (destroy) {
@@ -8,6 +8,7 @@ import { IConfigServerDetails, InstallStepKey } from '../interfaces/IConfig';
 import { appConfigDir, join, tempDir } from '@tauri-apps/api/path';
 import { LocalMachine } from './LocalMachine.ts';
 import { fetch } from '@tauri-apps/plugin-http';
+import { commands } from './tauriApi.ts';
 
 export enum InstallStepStatusType {
   Pending = 'Pending',
@@ -247,6 +248,11 @@ export class Server {
   public async startInstallerScript(): Promise<void> {
     const remoteScriptPath = this.installerScriptPath;
     const remoteScriptLogPath = `${this.workDir}/logs/installer.log`;
+    const customNetworkEnv = await commands.readCustomNetworkEnv();
+    if (customNetworkEnv) {
+      // custom networks aren't part of the uploaded server files
+      await this.connection.uploadFileWithTimeout(customNetworkEnv, `${this.workDir}/server/${DEPLOY_ENV_FILE}`, 10e3);
+    }
     await this.connection.runCommandWithTimeout(
       `cd ${this.workDir}/server && cp ${DEPLOY_ENV_FILE} .env && echo "COMPOSE_PROJECT_NAME=${DOCKER_COMPOSE_PROJECT_NAME}" >> .env`,
       10e3,
@@ -257,6 +263,8 @@ export class Server {
       const sedCommand = `sed -i -e 's|^ROOT=.*|ROOT="${fullVmPath}/app"|' ${this.workDir}/server/.env`;
       await this.connection.runCommandWithTimeout(sedCommand, 10e3);
       await this.connection.runCommandWithTimeout(`sedCommand`, 10e3);
+      // the copied env has the network's default ports, not the ones this instance remapped
+      await commands.syncLocalVmPorts();
     }
     if (await this.isInstallerScriptRunning()) {
       console.log('Restart the installer script: stopping existing one first');
@@ -397,7 +405,7 @@ export class Server {
     } catch (error) {
       console.error('Error wiping server:', error);
     }
-    if (this.connection.isDockerHostProxy) {
+    if (this.connection.isLocalVm) {
       await LocalMachine.remove();
     }
   }
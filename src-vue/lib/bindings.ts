@@ -0,0 +1,481 @@
+// This file was generated by [tauri-specta](https://github.com/oscartbeaumont/tauri-specta). Do not edit this file manually.
+
+/** user-defined commands **/
+
+
+export const commands = {
+async openSshConnection(address: string, host: string, port: number, username: string, privateKeyPath: string) : Promise<string> {
+    return await TAURI_INVOKE("open_ssh_connection", { address, host, port, username, privateKeyPath });
+},
+async closeSshConnection(address: string) : Promise<string> {
+    return await TAURI_INVOKE("close_ssh_connection", { address });
+},
+async sshRunCommand(address: string, command: string) : Promise<[string, number]> {
+    return await TAURI_INVOKE("ssh_run_command", { address, command });
+},
+async sshUploadFile(address: string, contents: string, remotePath: string) : Promise<string> {
+    return await TAURI_INVOKE("ssh_upload_file", { address, contents, remotePath });
+},
+async sshDownloadFile(address: string, remotePath: string, downloadPath: string, eventProgressKey: string) : Promise<string> {
+    return await TAURI_INVOKE("ssh_download_file", { address, remotePath, downloadPath, eventProgressKey });
+},
+async sshUploadEmbeddedFile(address: string, localRelativePath: string, remotePath: string, eventProgressKey: string) : Promise<string> {
+    return await TAURI_INVOKE("ssh_upload_embedded_file", { address, localRelativePath, remotePath, eventProgressKey });
+},
+async readEmbeddedFile(localRelativePath: string) : Promise<string> {
+    return await TAURI_INVOKE("read_embedded_file", { localRelativePath });
+},
+async readCustomNetworkEnv() : Promise<string | null> {
+    return await TAURI_INVOKE("read_custom_network_env");
+},
+async overwriteSecurity(masterMnemonic: string, sshPublicKey: string, sshPrivateKeyPath: string) : Promise<string> {
+    return await TAURI_INVOKE("overwrite_security", { masterMnemonic, sshPublicKey, sshPrivateKeyPath });
+},
+async overwriteMnemonic(mnemonic: string) : Promise<string> {
+    return await TAURI_INVOKE("overwrite_mnemonic", { mnemonic });
+},
+async runDbMigrations() : Promise<null> {
+    return await TAURI_INVOKE("run_db_migrations");
+},
+async awaitDbMigrations() : Promise<null> {
+    return await TAURI_INVOKE("await_db_migrations");
+},
+async dbMigrationStatus() : Promise<MigrationStatus[]> {
+    return await TAURI_INVOKE("db_migration_status");
+},
+async dbMigrationDryRun() : Promise<MigrationDryRun[]> {
+    return await TAURI_INVOKE("db_migration_dry_run");
+},
+async getFrames(range: FrameRange) : Promise<Frame[]> {
+    return await TAURI_INVOKE("get_frames", { range });
+},
+async getFrame(frameId: number) : Promise<Frame | null> {
+    return await TAURI_INVOKE("get_frame", { frameId });
+},
+async upsertFrame(frame: Frame) : Promise<null> {
+    return await TAURI_INVOKE("upsert_frame", { frame });
+},
+async getCohorts(range: FrameRange) : Promise<Cohort[]> {
+    return await TAURI_INVOKE("get_cohorts", { range });
+},
+async upsertCohort(cohort: Cohort) : Promise<null> {
+    return await TAURI_INVOKE("upsert_cohort", { cohort });
+},
+async getCohortFrames(range: FrameRange) : Promise<CohortFrame[]> {
+    return await TAURI_INVOKE("get_cohort_frames", { range });
+},
+async upsertCohortFrame(cohortFrame: CohortFrame) : Promise<null> {
+    return await TAURI_INVOKE("upsert_cohort_frame", { cohortFrame });
+},
+async getFrameBids(frameId: number, limit: number | null) : Promise<FrameBid[]> {
+    return await TAURI_INVOKE("get_frame_bids", { frameId, limit });
+},
+async upsertFrameBid(frameBid: FrameBid) : Promise<null> {
+    return await TAURI_INVOKE("upsert_frame_bid", { frameBid });
+},
+async transitionBitcoinLock(transition: BitcoinLockTransition) : Promise<BitcoinLockStatus> {
+    return await TAURI_INVOKE("transition_bitcoin_lock", { transition });
+},
+async getBitcoinLockTransitions(utxoId: number) : Promise<BitcoinLockTransitionRecord[]> {
+    return await TAURI_INVOKE("get_bitcoin_lock_transitions", { utxoId });
+},
+async reserveBitcoinLockHdPath(vaultId: number) : Promise<HdReservation> {
+    return await TAURI_INVOKE("reserve_bitcoin_lock_hd_path", { vaultId });
+},
+async releaseBitcoinLockHdPath(vaultId: number, hdIndex: number) : Promise<null> {
+    return await TAURI_INVOKE("release_bitcoin_lock_hd_path", { vaultId, hdIndex });
+},
+async scanBitcoinLockHdPaths(vaultId: number) : Promise<HdIndexScan[]> {
+    return await TAURI_INVOKE("scan_bitcoin_lock_hd_paths", { vaultId });
+},
+async getFrameEarnings(range: FrameRange) : Promise<FrameEarnings[]> {
+    return await TAURI_INVOKE("get_frame_earnings", { range });
+},
+async getCohortEarnings(cohortId: number) : Promise<CohortEarnings | null> {
+    return await TAURI_INVOKE("get_cohort_earnings", { cohortId });
+},
+async getRollingEarnings(windowFrames: number) : Promise<EarningsTotals> {
+    return await TAURI_INVOKE("get_rolling_earnings", { windowFrames });
+},
+async exportHistory(request: ExportHistoryRequest) : Promise<string[]> {
+    return await TAURI_INVOKE("export_history", { request });
+},
+async generateCostBasisReport(request: CostBasisRequest) : Promise<string[]> {
+    return await TAURI_INVOKE("generate_cost_basis_report", { request });
+},
+async createZip(pathsWithPrefixes: ([string, string])[], zipName: string) : Promise<string> {
+    return await TAURI_INVOKE("create_zip", { pathsWithPrefixes, zipName });
+},
+async toggleNosleep(enable: boolean) : Promise<null> {
+    return await TAURI_INVOKE("toggle_nosleep", { enable });
+},
+async calculateFreeSpace(path: string | null) : Promise<number> {
+    return await TAURI_INVOKE("calculate_free_space", { path });
+},
+async createLocalVm(envText: string, limits: VmResourceLimits) : Promise<number> {
+    return await TAURI_INVOKE("create_local_vm", { envText, limits });
+},
+async activateLocalVm() : Promise<number> {
+    return await TAURI_INVOKE("activate_local_vm");
+},
+async upgradeLocalVm() : Promise<number> {
+    return await TAURI_INVOKE("upgrade_local_vm");
+},
+async removeLocalVm() : Promise<null> {
+    return await TAURI_INVOKE("remove_local_vm");
+},
+async localVmStatus() : Promise<VmStatus> {
+    return await TAURI_INVOKE("local_vm_status");
+},
+async setLocalVmLimits(limits: VmResourceLimits) : Promise<null> {
+    return await TAURI_INVOKE("set_local_vm_limits", { limits });
+},
+async getLocalVmLogs(lines: number) : Promise<VmLogLine[]> {
+    return await TAURI_INVOKE("get_local_vm_logs", { lines });
+},
+async followLocalVmLogs() : Promise<null> {
+    return await TAURI_INVOKE("follow_local_vm_logs");
+},
+async stopLocalVmLogs() : Promise<null> {
+    return await TAURI_INVOKE("stop_local_vm_logs");
+},
+async exportLocalVmLogs(lines: number) : Promise<string> {
+    return await TAURI_INVOKE("export_local_vm_logs", { lines });
+},
+async removeLocalVmLogsExport(exportDir: string) : Promise<null> {
+    return await TAURI_INVOKE("remove_local_vm_logs_export", { exportDir });
+},
+async createLocalVmSnapshot() : Promise<VmSnapshot> {
+    return await TAURI_INVOKE("create_local_vm_snapshot");
+},
+async listLocalVmSnapshots() : Promise<VmSnapshot[]> {
+    return await TAURI_INVOKE("list_local_vm_snapshots");
+},
+async restoreLocalVmSnapshot(fileName: string) : Promise<null> {
+    return await TAURI_INVOKE("restore_local_vm_snapshot", { fileName });
+},
+async deleteLocalVmSnapshot(fileName: string) : Promise<null> {
+    return await TAURI_INVOKE("delete_local_vm_snapshot", { fileName });
+},
+async importLocalVmSnapshot(path: string) : Promise<VmSnapshot> {
+    return await TAURI_INVOKE("import_local_vm_snapshot", { path });
+},
+async isDockerRunning() : Promise<boolean> {
+    return await TAURI_INVOKE("is_docker_running");
+},
+async getContainerRuntime() : Promise<ContainerRuntime | null> {
+    return await TAURI_INVOKE("get_container_runtime");
+},
+async getLocalVmBackend() : Promise<VmBackend | null> {
+    return await TAURI_INVOKE("get_local_vm_backend");
+},
+async checkNeededPorts() : Promise<PortConflict[]> {
+    return await TAURI_INVOKE("check_needed_ports");
+},
+async remapNeededPorts(ports: { [key in string]: number }) : Promise<NetworkEnv> {
+    return await TAURI_INVOKE("remap_needed_ports", { ports });
+},
+async syncLocalVmPorts() : Promise<null> {
+    return await TAURI_INVOKE("sync_local_vm_ports");
+},
+async listNetworks() : Promise<Network[]> {
+    return await TAURI_INVOKE("list_networks");
+},
+async listInstances() : Promise<Instance[]> {
+    return await TAURI_INVOKE("list_instances");
+},
+async createInstance(networkName: string, instanceName: string) : Promise<null> {
+    return await TAURI_INVOKE("create_instance", { networkName, instanceName });
+},
+async cloneInstance(networkName: string, sourceInstanceName: string, targetInstanceName: string, includeKeys: boolean) : Promise<null> {
+    return await TAURI_INVOKE("clone_instance", { networkName, sourceInstanceName, targetInstanceName, includeKeys });
+},
+async deleteInstance(networkName: string, instanceName: string) : Promise<null> {
+    return await TAURI_INVOKE("delete_instance", { networkName, instanceName });
+},
+async relaunchIntoInstance(networkName: string, instanceName: string) : Promise<null> {
+    return await TAURI_INVOKE("relaunch_into_instance", { networkName, instanceName });
+},
+async updateInstanceMetadata(serverType: string | null, miningAccountAddress: string | null) : Promise<null> {
+    return await TAURI_INVOKE("update_instance_metadata", { serverType, miningAccountAddress });
+}
+}
+
+/** user-defined events **/
+
+
+export const events = __makeEvents__<{
+migrationProgress: MigrationProgress,
+vmLogLine: VmLogLine,
+vmProgress: VmProgress
+}>({
+migrationProgress: "migration-progress",
+vmLogLine: "vm-log-line",
+vmProgress: "vm-progress"
+})
+
+/** user-defined constants **/
+
+
+
+/** user-defined types **/
+
+export type BigIntJson = string[]
+export type BitcoinChain = "mainnet" | "testnet" | "signet" | "regtest"
+export type BitcoinLockStatus = "initialized" | "pendingMint" | "verificationFailed" | "minted" | "releaseRequested" | "vaultCosigned" | "released"
+export type BitcoinLockTransition = { utxoId: number; status: BitcoinLockStatus; txid: string | null; vout: number | null; requestedReleaseAtHeight: number | null; releaseBitcoinNetworkFee: string | null; releaseToDestinationAddress: string | null; releaseCosignSignature: number[] | null; releaseCosignHeight: number | null; releasedAtHeight: number | null; releasedTxid: string | null }
+export type BitcoinLockTransitionRecord = { id: number; utxoId: number; fromStatus: string | null; toStatus: string; createdAt: string }
+export type Cohort = { id: number; progress: number; seatCountWon: number; transactionFeesTotal: string; micronotsStakedPerSeat: string; microgonsBidPerSeat: string; microgonsToBeMinedPerSeat: string; micronotsToBeMinedPerSeat: string }
+export type CohortEarnings = { cohortId: number; seatCountWon: number; progress: number; totals: EarningsTotals }
+export type CohortFrame = { frameId: number; cohortId: number; blocksMinedTotal: number; microgonFeesCollectedTotal: string; micronotsMinedTotal: string; microgonsMinedTotal: string; microgonsMintedTotal: string }
+export type CommanderError = { code: ErrorCode; message: string; context: string[] }
+export type ContainerRuntime = { kind: ContainerRuntimeKind; isRootless: boolean; socketPath: string | null }
+export type ContainerRuntimeKind = "docker" | "podman" | "nerdctl"
+export type CostBasisMethod = "fifo" | 
+/**
+ * Disposals consume the lots listed in `lot_selections` first, then fall back to FIFO
+ */
+"specificId"
+export type CostBasisRequest = { outputDir: string; method: CostBasisMethod; startFrameId: number | null; endFrameId: number | null; tickMillis: number; 
+/**
+ * Disposal id (the cohort id) to the lot ids it should be matched against
+ */
+lotSelections?: { [key in number]: string[] } }
+export type EarningsTotals = { frameCount: number; blocksMined: number; microgonsMined: string; microgonsMinted: string; micronotsMined: string; micronotsMinedAsMicrogons: string | null; microgonFeesCollected: string; seatCostMicrogons: string; earningsMicrogons: string | null; profitMicrogons: string | null; earningsUsd: number | null; seatCostUsd: number | null; profitUsd: number | null; roiPct: number | null; apyPct: number | null }
+export type ErrorCode = "SshAuthFailed" | "SshTimeout" | "SshConnectionRefused" | "SshHostUnreachable" | "SshMissingExitStatus" | "NoConnection" | "DockerNotRunning" | "DockerCommandFailed" | "QemuCommandFailed" | "VmNotFound" | "PortInUse" | "MigrationFailed" | "InstanceInUse" | "DatabaseError" | "KeychainUnavailable" | "FileSystemError" | "InvalidInput" | "Unknown"
+export type ExportFormat = "csv" | "json"
+export type ExportHistoryRequest = { format: ExportFormat; outputDir: string; startFrameId: number | null; endFrameId: number | null; startDateMillis: number | null; endDateMillis: number | null; tickMillis: number }
+export type Frame = { id: number; firstTick: number; lastTick: number; firstBlockNumber: number; lastBlockNumber: number; microgonToUsd: BigIntJson; microgonToBtc: BigIntJson; microgonToArgonot: BigIntJson; allMinersCount: number; seatCountActive: number; seatCostTotalFramed: string; blocksMinedTotal: number; microgonFeesCollectedTotal: string; micronotsMinedTotal: string; microgonsMinedTotal: string; microgonsMintedTotal: string; accruedMicrogonProfits: string; progress: number; isProcessed: boolean }
+export type FrameBid = { frameId: number; confirmedAtBlockNumber: number; address: string; subAccountIndex: number | null; microgonsPerSeat: string; bidPosition: number; lastBidAtTick: number | null }
+export type FrameEarnings = { frameId: number; firstTick: number; isProcessed: boolean; 
+/**
+ * Average of the frame's price samples, None until one is recorded
+ */
+microgonsPerUsd: number | null; microgonsPerArgonot: number | null; totals: EarningsTotals }
+export type FrameRange = { startFrameId: number | null; endFrameId: number | null; limit: number | null }
+export type HdIndexScan = { hdIndex: number; hdPath: string; state: HdIndexState; utxoId: number | null }
+export type HdIndexState = 
+/**
+ * A bitcoin lock exists with this path
+ */
+"used" | 
+/**
+ * Handed out and not yet used or released. A stale one may still have been locked on-chain
+ * if the app stopped before saving the lock, so check before releasing it.
+ */
+"reserved" | 
+/**
+ * Released, so the next reservation will reuse it
+ */
+"abandoned" | 
+/**
+ * Below the high-water mark, but never reserved or used
+ */
+"unused"
+export type HdReservation = { vaultId: number; hdIndex: number; hdPath: string }
+export type Instance = { networkName: string; instanceName: string; 
+/**
+ * This process is running the instance
+ */
+isCurrent: boolean; 
+/**
+ * Another Commander process has the instance open
+ */
+isInUse: boolean; metadata: InstanceMetadata }
+export type InstanceMetadata = { lastOpenedAtMillis: number | null; serverType: string | null; miningAccountAddress: string | null; 
+/**
+ * Host ports moved off the network defaults to avoid conflicts, keyed by env name
+ */
+portOverrides?: { [key in string]: number } }
+export type MigrationDryRun = { version: number; description: string; error: string | null }
+export type MigrationPhase = "checking" | "backingUp" | "applying" | "restoringBackup" | "succeeded" | "failed"
+export type MigrationProgress = { phase: MigrationPhase; version: number | null; description: string | null; completed: number; total: number; error: string | null }
+export type MigrationState = "applied" | "pending" | 
+/**
+ * Applied, but the checksum stored in `_sqlx_migrations` differs from the embedded sql
+ */
+"checksumMismatch" | 
+/**
+ * Recorded as applied, but no longer part of the embedded migrations
+ */
+"unknown" | 
+/**
+ * Recorded in `_sqlx_migrations` with success = false
+ */
+"failed"
+export type MigrationStatus = { version: number; description: string; state: MigrationState; checksum: string; appliedChecksum: string | null; appliedAt: string | null }
+export type Network = { name: string; 
+/**
+ * Loaded from an `.env.<name>` file in the app config dir rather than compiled in
+ */
+isCustom: boolean; 
+/**
+ * Why the env file can't be used, if it fails validation
+ */
+error: string | null }
+export type NetworkEnv = 
+/**
+ * Keys Commander doesn't read itself (ie, ROOT or ARGON_DATA_FOLDER), passed through as is
+ */
+(Partial<{ [key in string]: string }>) & { DATA_FOLDER: string; CONFIG_FOLDER: string; BITCOIN_CONFIG: string; BITCOIN_CHAIN: BitcoinChain; BITCOIN_VERSION: string; BITCOIN_P2P_PORT: number; BITCOIN_DATA_SLUG: string; BITCOIN_TAR_PATHS: string; ARGON_VERSION: string; ARGON_CHAIN: string; ARGON_ARCHIVE_NODE: string; 
+/**
+ * Comma separated in the env file
+ */
+NOTEBOOK_ARCHIVE_HOSTS: string[]; ARGON_P2P_PORT: number; ARGON_RPC_PORT: number; ARGON_BOOTNODES: string | null; BOT_PORT: number; STATUS_PORT: number }
+export type PortBinding = "tcpV4" | "tcpV6" | "udpV4" | "udpV6"
+export type PortConflict = { 
+/**
+ * The env setting the port comes from, ie, BITCOIN_P2P_PORT
+ */
+envKey: string; port: number; 
+/**
+ * Bindings that failed. Empty when only a container engine reports holding the port
+ */
+blocked: PortBinding[]; 
+/**
+ * Who holds the port, when the OS or the container engine will say
+ */
+owner: PortOwner | null; 
+/**
+ * The nearest higher port that's free on every binding
+ */
+suggestedPort: number | null }
+export type PortOwner = { 
+/**
+ * None when the port is held by a container rather than a local process
+ */
+pid: number | null; 
+/**
+ * The process or container name
+ */
+name: string }
+export type Security = { masterMnemonic: string; sshPublicKey: string; sshPrivateKeyPath: string }
+export type Vm = { sshPort: number }
+export type VmBackend = 
+/**
+ * A systemd container that drives the host's container engine
+ */
+"docker" | 
+/**
+ * A QEMU machine that runs its own engine, for Linux hosts without a usable container runtime
+ */
+"qemu"
+export type VmLogLine = { source: VmLogSource; 
+/**
+ * Prefixed by compose with the container name and a timestamp. Console lines from a QEMU VM
+ * are passed through as they are
+ */
+line: string }
+export type VmLogSource = 
+/**
+ * The VM container, or a QEMU VM's serial console
+ */
+"vm" | 
+/**
+ * The server compose stack the VM runs in `app/server`
+ */
+"server"
+export type VmPhase = "writingFiles" | "buildingImage" | "starting" | "waitingForHealth" | "resolvingPort" | "succeeded" | "failed"
+export type VmProgress = { phase: VmPhase; 
+/**
+ * A line of compose or build output, if this event carries one
+ */
+output: string | null; error: string | null }
+export type VmResourceLimits = { cpus: number | null; memoryBytes: number | null; 
+/**
+ * Free space the `app` work dir must have room to grow into. Bind mounts can't be capped by
+ * the engine, so for docker this is only a check against free space whenever limits are set,
+ * not a cap. A QEMU VM's disk is sized to it.
+ */
+reservedDiskBytes: number | null }
+export type VmSnapshot = { 
+/**
+ * Identifies the snapshot to the other snapshot commands
+ */
+fileName: string; path: string; createdAtMillis: number; sizeBytes: number }
+export type VmStatus = { 
+/**
+ * The VM dir exists. Everything else is empty when it doesn't
+ */
+exists: boolean; backend: VmBackend | null; 
+/**
+ * created, running, paused, exited, ..., or None if the container hasn't been created
+ */
+state: string | null; 
+/**
+ * starting, healthy or unhealthy
+ */
+health: string | null; startedAt: string | null; uptimeSeconds: number | null; sshPort: number | null; image: string | null; imageId: string | null; 
+/**
+ * Percent of a single cpu
+ */
+cpuPercent: number | null; memoryUsageBytes: number | null; memoryLimitBytes: number | null; 
+/**
+ * Disk space used by the `app` bind mount, which holds the synced chain data. Measured at
+ * most every few minutes
+ */
+appDiskUsageBytes: number; 
+/**
+ * Embedded `local-machine` files that differ from the ones the VM was built from
+ */
+changedFiles: string[]; limits: VmResourceLimits }
+
+/** tauri-specta globals **/
+
+import {
+	invoke as TAURI_INVOKE,
+	Channel as TAURI_CHANNEL,
+} from "@tauri-apps/api/core";
+import * as TAURI_API_EVENT from "@tauri-apps/api/event";
+import { type WebviewWindow as __WebviewWindow__ } from "@tauri-apps/api/webviewWindow";
+
+type __EventObj__<T> = {
+	listen: (
+		cb: TAURI_API_EVENT.EventCallback<T>,
+	) => ReturnType<typeof TAURI_API_EVENT.listen<T>>;
+	once: (
+		cb: TAURI_API_EVENT.EventCallback<T>,
+	) => ReturnType<typeof TAURI_API_EVENT.once<T>>;
+	emit: null extends T
+		? (payload?: T) => ReturnType<typeof TAURI_API_EVENT.emit>
+		: (payload: T) => ReturnType<typeof TAURI_API_EVENT.emit>;
+};
+
+export type Result<T, E> =
+	| { status: "ok"; data: T }
+	| { status: "error"; error: E };
+
+function __makeEvents__<T extends Record<string, any>>(
+	mappings: Record<keyof T, string>,
+) {
+	return new Proxy(
+		{} as unknown as {
+			[K in keyof T]: __EventObj__<T[K]> & {
+				(handle: __WebviewWindow__): __EventObj__<T[K]>;
+			};
+		},
+		{
+			get: (_, event) => {
+				const name = mappings[event as keyof T];
+
+				return new Proxy((() => {}) as any, {
+					apply: (_, __, [window]: [__WebviewWindow__]) => ({
+						listen: (arg: any) => window.listen(name, arg),
+						once: (arg: any) => window.once(name, arg),
+						emit: (arg: any) => window.emit(name, arg),
+					}),
+					get: (_, command: keyof __EventObj__<any>) => {
+						switch (command) {
+							case "listen":
+								return (arg: any) => TAURI_API_EVENT.listen(name, arg);
+							case "once":
+								return (arg: any) => TAURI_API_EVENT.once(name, arg);
+							case "emit":
+								return (arg: any) => TAURI_API_EVENT.emit(name, arg);
+						}
+					},
+				});
+			},
+		},
+	);
+}
@@ -2,6 +2,8 @@ import { BaseTable, IFieldTypes } from './BaseTable';
 import { IBitcoinLock } from '@argonprotocol/mainchain';
 import { JsonExt } from '@argonprotocol/commander-core';
 import { convertFromSqliteFields, toSqlParams } from '../Utils.ts';
+import { commands } from '../tauriApi';
+import { BitcoinLockTransition } from '../bindings.ts';
 
 export interface IRatchet {
   mintAmount: bigint;
@@ -86,17 +88,6 @@ export class BitcoinLocksTable extends BaseTable {
     return record;
   }
 
-  async getNextVaultHdKeyIndex(vaultId: number): Promise<number> {
-    const [{ latestIndex }] = await this.db.select<{ latestIndex: number }[]>(
-      `INSERT INTO BitcoinLockVaultHdSeq (vaultId, latestIndex) VALUES ($1, $2)
-       ON CONFLICT (vaultId) DO UPDATE SET latestIndex = BitcoinLockVaultHdSeq.latestIndex + 1
-       RETURNING latestIndex`,
-      toSqlParams([vaultId, 0]),
-    );
-
-    return latestIndex;
-  }
-
   async requestedRelease(
     lock: IBitcoinLockRecord,
     height: number,
@@ -107,15 +98,11 @@ export class BitcoinLocksTable extends BaseTable {
     lock.requestedReleaseAtHeight = height;
     lock.releaseToDestinationAddress = toDestinationAddress;
     lock.releaseBitcoinNetworkFee = networkFee;
-    await this.db.execute(
-      `UPDATE BitcoinLocks SET status = 'releaseRequested', requestedReleaseAtHeight=$2, releaseToDestinationAddress=$3, releaseBitcoinNetworkFee=$4 WHERE utxoId = $1`,
-      toSqlParams([
-        lock.utxoId,
-        lock.requestedReleaseAtHeight,
-        lock.releaseToDestinationAddress,
-        lock.releaseBitcoinNetworkFee,
-      ]),
-    );
+    await this.transition(lock, {
+      requestedReleaseAtHeight: height,
+      releaseToDestinationAddress: toDestinationAddress,
+      releaseBitcoinNetworkFee: networkFee.toString(),
+    });
   }
 
   async get(utxoId: number): Promise<IBitcoinLockRecord | undefined> {
@@ -144,33 +131,48 @@ export class BitcoinLocksTable extends BaseTable {
 
   async saveRatchetUpdates(lock: IBitcoinLockRecord): Promise<void> {
     const ratchets = JsonExt.stringify(lock.ratchets);
-    await this.db.execute(
-      `UPDATE BitcoinLocks SET ratchets = $2, status = $3 WHERE utxoId = $1`,
-      toSqlParams([lock.utxoId, ratchets, lock.status]),
-    );
+    await this.db.execute(`UPDATE BitcoinLocks SET ratchets = $2 WHERE utxoId = $1`, toSqlParams([lock.utxoId, ratchets]));
+    await this.transition(lock);
   }
 
   async recordCosigned(lock: IBitcoinLockRecord, signature: Uint8Array, atHeight: number): Promise<void> {
     lock.status = 'vaultCosigned';
     lock.releaseCosignSignature = signature;
     lock.releaseCosignHeight = atHeight;
-    await this.db.execute(
-      'UPDATE BitcoinLocks SET status = $1, releaseCosignSignature = $2, releaseCosignHeight = $3 WHERE utxoId = $4',
-      toSqlParams([lock.status, lock.releaseCosignSignature, lock.releaseCosignHeight, lock.utxoId]),
-    );
+    await this.transition(lock, {
+      releaseCosignSignature: Array.from(signature),
+      releaseCosignHeight: atHeight,
+    });
   }
 
   async recordVerifiedStatus(lock: IBitcoinLockRecord) {
-    await this.db.execute(
-      'UPDATE BitcoinLocks SET status = $1, txid = $2, vout = $3 WHERE utxoId = $4',
-      toSqlParams([lock.status, lock.txid, lock.vout, lock.utxoId]),
-    );
+    await this.transition(lock, { txid: lock.txid ?? null, vout: lock.vout ?? null });
   }
 
   async recordReleaseTxid(lock: IBitcoinLockRecord): Promise<void> {
-    await this.db.execute(
-      'UPDATE BitcoinLocks SET status = $1, releasedAtHeight = $2, releasedTxId = $3 WHERE utxoId = $4',
-      toSqlParams([lock.status, lock.releasedAtHeight, lock.releasedTxId, lock.utxoId]),
-    );
+    await this.transition(lock, {
+      releasedAtHeight: lock.releasedAtHeight ?? null,
+      releasedTxid: lock.releasedTxId ?? null,
+    });
+  }
+
+  /**
+   * Status changes go through the Rust lifecycle checks. Fields left out keep their stored value.
+   */
+  private async transition(lock: IBitcoinLockRecord, fields: Partial<BitcoinLockTransition> = {}): Promise<void> {
+    await commands.transitionBitcoinLock({
+      utxoId: lock.utxoId,
+      status: lock.status,
+      txid: null,
+      vout: null,
+      requestedReleaseAtHeight: null,
+      releaseBitcoinNetworkFee: null,
+      releaseToDestinationAddress: null,
+      releaseCosignSignature: null,
+      releaseCosignHeight: null,
+      releasedAtHeight: null,
+      releasedTxid: null,
+      ...fields,
+    });
   }
 }
@@ -1,6 +1,7 @@
 import { ICohortRecord } from '../../interfaces/db/ICohortRecord';
 import { BaseTable } from './BaseTable';
-import { convertSqliteBigInts, fromSqliteBigInt, toSqlParams } from '../Utils';
+import { convertSqliteBigInts, fromSqliteBigInt } from '../Utils';
+import { commands } from '../tauriApi';
 import BigNumber from 'bignumber.js';
 import { bigNumberToBigInt, MiningFrames } from '@argonprotocol/commander-core';
 
@@ -130,48 +131,14 @@ export class CohortsTable extends BaseTable {
     microgonsToBeMinedPerSeat: bigint;
     micronotsToBeMinedPerSeat: bigint;
   }): Promise<void> {
-    const {
-      id,
-      progress,
-      transactionFeesTotal,
-      micronotsStakedPerSeat,
-      microgonsBidPerSeat,
-      seatCountWon,
-      microgonsToBeMinedPerSeat,
-      micronotsToBeMinedPerSeat,
-    } = args;
-    await this.db.execute(
-      `INSERT INTO Cohorts (
-          id, 
-          progress, 
-          transactionFeesTotal, 
-          micronotsStakedPerSeat,
-          microgonsBidPerSeat,
-          seatCountWon,
-          microgonsToBeMinedPerSeat,
-          micronotsToBeMinedPerSeat
-        ) VALUES (
-          ?, ?, ?, ?, ?, ?, ?, ?
-        ) ON CONFLICT(id) DO UPDATE SET 
-          progress = excluded.progress, 
-          transactionFeesTotal = excluded.transactionFeesTotal, 
-          micronotsStakedPerSeat = excluded.micronotsStakedPerSeat, 
-          microgonsBidPerSeat = excluded.microgonsBidPerSeat, 
-          seatCountWon = excluded.seatCountWon, 
-          microgonsToBeMinedPerSeat = excluded.microgonsToBeMinedPerSeat, 
-          micronotsToBeMinedPerSeat = excluded.micronotsToBeMinedPerSeat
-      `,
-      toSqlParams([
-        id,
-        progress,
-        transactionFeesTotal,
-        micronotsStakedPerSeat,
-        microgonsBidPerSeat,
-        seatCountWon,
-        microgonsToBeMinedPerSeat,
-        micronotsToBeMinedPerSeat,
-      ]),
-    );
+    await commands.upsertCohort({
+      ...args,
+      transactionFeesTotal: args.transactionFeesTotal.toString(),
+      micronotsStakedPerSeat: args.micronotsStakedPerSeat.toString(),
+      microgonsBidPerSeat: args.microgonsBidPerSeat.toString(),
+      microgonsToBeMinedPerSeat: args.microgonsToBeMinedPerSeat.toString(),
+      micronotsToBeMinedPerSeat: args.micronotsToBeMinedPerSeat.toString(),
+    });
   }
 
   async fetchCount(): Promise<number> {
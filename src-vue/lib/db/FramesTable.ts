@@ -1,6 +1,7 @@
 import { IFrameRecord } from '../../interfaces/db/IFrameRecord';
 import { BaseTable, IFieldTypes } from './BaseTable';
-import { convertFromSqliteFields, fromSqliteBigInt, toSqlParams } from '../Utils';
+import { convertFromSqliteFields, fromSqliteBigInt } from '../Utils';
+import { commands } from '../tauriApi';
 import { bigNumberToBigInt, MiningFrames } from '@argonprotocol/commander-core';
 import BigNumber from 'bignumber.js';
 import dayjs from 'dayjs';
@@ -24,6 +25,7 @@ export class FramesTable extends BaseTable {
     ],
   };
 
+  // writes go through the Rust commands so the schema is checked against the queries at build time
   async insertOrUpdate(
     id: number,
     firstTick: number,
@@ -36,35 +38,29 @@ export class FramesTable extends BaseTable {
     progress: number,
     isProcessed: boolean,
   ): Promise<void> {
-    await this.db.execute(
-      `INSERT INTO Frames (
-          id, firstTick, lastTick, firstBlockNumber, lastBlockNumber, microgonToUsd, microgonToBtc, microgonToArgonot, progress, isProcessed
-        ) VALUES (
-          ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
-        ) ON CONFLICT(id) DO UPDATE SET 
-          firstTick = excluded.firstTick, 
-          lastTick = excluded.lastTick, 
-          firstBlockNumber = excluded.firstBlockNumber, 
-          lastBlockNumber = excluded.lastBlockNumber, 
-          microgonToUsd = excluded.microgonToUsd, 
-          microgonToBtc = excluded.microgonToBtc, 
-          microgonToArgonot = excluded.microgonToArgonot, 
-          progress = excluded.progress, 
-          isProcessed = excluded.isProcessed
-      `,
-      toSqlParams([
-        id,
-        firstTick,
-        lastTick,
-        firstBlockNumber,
-        lastBlockNumber,
-        microgonToUsd,
-        microgonToBtc,
-        microgonToArgonot,
-        progress,
-        isProcessed,
-      ]),
-    );
+    const existing = await commands.getFrame(id);
+    await commands.upsertFrame({
+      allMinersCount: 0,
+      seatCountActive: 0,
+      seatCostTotalFramed: '0',
+      blocksMinedTotal: 0,
+      microgonFeesCollectedTotal: '0',
+      micronotsMinedTotal: '0',
+      microgonsMinedTotal: '0',
+      microgonsMintedTotal: '0',
+      accruedMicrogonProfits: '0',
+      ...existing,
+      id,
+      firstTick,
+      lastTick,
+      firstBlockNumber,
+      lastBlockNumber,
+      microgonToUsd: microgonToUsd.map(String),
+      microgonToBtc: microgonToBtc.map(String),
+      microgonToArgonot: microgonToArgonot.map(String),
+      progress,
+      isProcessed,
+    });
   }
 
   async update(args: {
@@ -88,70 +84,18 @@ export class FramesTable extends BaseTable {
     progress: number;
     isProcessed: boolean;
   }): Promise<void> {
-    const {
-      id,
-      firstTick,
-      lastTick,
-      firstBlockNumber,
-      lastBlockNumber,
-      microgonToUsd,
-      microgonToBtc,
-      microgonToArgonot,
-      allMinersCount,
-      seatCountActive,
-      seatCostTotalFramed,
-      blocksMinedTotal,
-      micronotsMinedTotal,
-      microgonsMinedTotal,
-      microgonsMintedTotal,
-      microgonFeesCollectedTotal,
-      accruedMicrogonProfits,
-      progress,
-      isProcessed,
-    } = args;
-    await this.db.execute(
-      `UPDATE Frames SET 
-        firstTick = ?, 
-        lastTick = ?, 
-        firstBlockNumber = ?, 
-        lastBlockNumber = ?, 
-        microgonToUsd = ?, 
-        microgonToBtc = ?, 
-        microgonToArgonot = ?,
-        allMinersCount = ?,
-        seatCountActive = ?, 
-        seatCostTotalFramed = ?,
-        blocksMinedTotal = ?,
-        micronotsMinedTotal = ?,
-        microgonsMinedTotal = ?,
-        microgonsMintedTotal = ?,
-        microgonFeesCollectedTotal = ?,
-        accruedMicrogonProfits = ?,
-        progress = ?, 
-        isProcessed = ? 
-      WHERE id = ?`,
-      toSqlParams([
-        firstTick,
-        lastTick,
-        firstBlockNumber,
-        lastBlockNumber,
-        microgonToUsd,
-        microgonToBtc,
-        microgonToArgonot,
-        allMinersCount,
-        seatCountActive,
-        seatCostTotalFramed,
-        blocksMinedTotal,
-        micronotsMinedTotal,
-        microgonsMinedTotal,
-        microgonsMintedTotal,
-        microgonFeesCollectedTotal,
-        accruedMicrogonProfits,
-        progress,
-        isProcessed,
-        id,
-      ]),
-    );
+    await commands.upsertFrame({
+      ...args,
+      microgonToUsd: args.microgonToUsd.map(String),
+      microgonToBtc: args.microgonToBtc.map(String),
+      microgonToArgonot: args.microgonToArgonot.map(String),
+      seatCostTotalFramed: args.seatCostTotalFramed.toString(),
+      micronotsMinedTotal: args.micronotsMinedTotal.toString(),
+      microgonsMinedTotal: args.microgonsMinedTotal.toString(),
+      microgonsMintedTotal: args.microgonsMintedTotal.toString(),
+      microgonFeesCollectedTotal: args.microgonFeesCollectedTotal.toString(),
+      accruedMicrogonProfits: args.accruedMicrogonProfits.toString(),
+    });
   }
 
   async fetchLastYear(): Promise<Omit<IDashboardFrameStats, 'score' | 'expected'>[]> {
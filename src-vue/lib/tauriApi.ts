@@ -1,6 +1,5 @@
-import { invoke } from '@tauri-apps/api/core';
-
-export { invoke };
+import { invoke as tauriInvoke, InvokeArgs } from '@tauri-apps/api/core';
+import { commands as generatedCommands, ErrorCode } from './bindings';
 
 export class InvokeTimeout extends Error {
   constructor(message: string) {
@@ -8,16 +7,71 @@ export class InvokeTimeout extends Error {
   }
 }
 
-export function invokeWithTimeout<T>(cmd: string, args: Record<string, any>, timeoutMs: number): Promise<T> {
+export type CommanderErrorCode = ErrorCode;
+
+export class CommanderError extends Error {
+  constructor(
+    public readonly code: CommanderErrorCode,
+    message: string,
+    public readonly context: string[] = [],
+  ) {
+    super([...context, message].join(': '));
+  }
+
+  public toString(): string {
+    return this.message;
+  }
+
+  public static from(error: unknown): CommanderError | unknown {
+    if (error && typeof error === 'object' && 'code' in error && 'message' in error) {
+      const { code, message, context } = error as { code: CommanderErrorCode; message: string; context?: string[] };
+      return new CommanderError(code, message, context ?? []);
+    }
+    return error;
+  }
+}
+
+export function isCommanderError(error: unknown, ...codes: CommanderErrorCode[]): error is CommanderError {
+  return error instanceof CommanderError && (codes.length === 0 || codes.includes(error.code));
+}
+
+export async function invoke<T>(cmd: string, args?: InvokeArgs): Promise<T> {
+  try {
+    return await tauriInvoke<T>(cmd, args);
+  } catch (e) {
+    throw CommanderError.from(e);
+  }
+}
+
+export function withTimeout<T>(promise: Promise<T>, timeoutMs: number): Promise<T> {
   const timeout = new Promise<never>((_, reject) =>
     setTimeout(() => reject(new InvokeTimeout('Invoke timed out')), timeoutMs),
   );
+  return Promise.race([promise, timeout]);
+}
 
+export function invokeWithTimeout<T>(cmd: string, args: Record<string, any>, timeoutMs: number): Promise<T> {
   try {
-    const invocation = invoke<T>(cmd, args);
-    return Promise.race([invocation, timeout]);
+    return withTimeout(invoke<T>(cmd, args), timeoutMs);
   } catch (e) {
     console.error(`Error invoking ${cmd}`, e);
     throw e;
   }
 }
+
+/**
+ * Typed wrappers for every Rust command (generated into ./bindings.ts by debug builds). Errors are rethrown as
+ * CommanderError so callers can branch on the code.
+ */
+export const commands = Object.fromEntries(
+  Object.entries(generatedCommands).map(([name, command]) => [
+    name,
+    async (...args: any[]) => {
+      try {
+        return await (command as (...args: any[]) => Promise<unknown>)(...args);
+      } catch (e) {
+        throw CommanderError.from(e);
+      }
+    },
+  ]),
+) as typeof generatedCommands;
@@ -13,10 +13,5 @@ declare const __COMMANDER_INSTANCE__: string;
 declare const __COMMANDER_ENABLE_AUTOUPDATE__: boolean;
 declare const __COMMANDER_SECURITY__: any;
 declare const __IS_TEST__: boolean;
-declare const __SERVER_ENV_VARS__: {
-  STATUS_PORT: string;
-  BOT_PORT: string;
-  ARGON_RPC_PORT: string;
-  BITCOIN_VERSION: string;
-  ARGON_VERSION: string;
-};
+declare const __SERVER_ENV_VARS__: import('./lib/bindings').NetworkEnv;
+declare const __SERVER_ENV_ERROR__: string | null;